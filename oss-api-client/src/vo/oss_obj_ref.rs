@@ -20,6 +20,10 @@ pub struct OssObjRefVo {
     pub download_url: String,
     /// 预览URL
     pub preview_url: Option<String>,
+    /// MIME类型
+    pub content_type: Option<String>,
+    /// 缩略图URL(多个用逗号分隔)
+    pub thumbnail_urls: Option<String>,
     /// 创建者ID
     pub creator_id: u64,
     /// 创建时间戳
@@ -0,0 +1,7 @@
+-- 新增 oss_bucket 表的 previewable_types 字段及 oss_obj_ref 表的 content_type 字段
+
+alter table oss_bucket
+    add column previewable_types varchar(500) comment '可预览的MIME类型(多个用逗号分隔)';
+
+alter table oss_obj_ref
+    add column content_type varchar(100) comment 'MIME类型';
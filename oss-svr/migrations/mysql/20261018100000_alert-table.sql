@@ -0,0 +1,7 @@
+-- 新增 oss_obj 表的 content_type 字段及 oss_bucket 表的 mime_mismatch_policy 字段
+
+alter table oss_obj
+    add column content_type varchar(100) comment '检测出的MIME类型';
+
+alter table oss_bucket
+    add column mime_mismatch_policy varchar(20) comment 'MIME类型不符时的策略(reject/downgrade)';
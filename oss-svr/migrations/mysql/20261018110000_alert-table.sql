@@ -0,0 +1,4 @@
+-- 新增 oss_bucket 表的 access 字段
+
+alter table oss_bucket
+    add column access varchar(20) comment '访问策略(public-read/private/authenticated)';
@@ -0,0 +1,21 @@
+-- 新增 oss_bucket_acl 表（存储桶的访问控制）
+
+create table oss_bucket_acl
+(
+    _id                  bigint not null  comment 'ID',
+    bucket_id            bigint not null  comment '桶ID',
+    grantee_type         varchar(10) not null  comment '授权对象的类型(user/role)',
+    grantee              varchar(50) not null  comment '授权对象(用户ID或角色名称)',
+    permission           varchar(10) not null  comment '权限(read/write/admin)',
+    _creator_id          bigint not null  comment '创建人的用户ID',
+    _create_timestamp    bigint not null  comment '建立时间戳',
+    _updator_id          bigint not null  comment '修改人的用户ID',
+    _update_timestamp    bigint not null  comment '修改时间戳',
+    primary key (_id),
+    unique key AK_GRANTEE (bucket_id, grantee_type, grantee)
+);
+
+alter table oss_bucket_acl comment '存储桶的访问控制';
+
+alter table oss_bucket_acl add constraint fk_acl_bucket_id__from__oss_bucket foreign key (bucket_id)
+    references oss_bucket (_id) on delete restrict on update restrict;
@@ -0,0 +1,12 @@
+-- 新增 oss_bucket 表的 thumbnail_sizes 字段、oss_obj 表的 source_id 及 derivation 字段、oss_obj_ref 表的 thumbnail_urls 字段
+
+alter table oss_bucket
+    add column thumbnail_sizes varchar(200) comment '缩略图的尺寸(多个用逗号分隔，如200x200,400x300)';
+
+alter table oss_obj
+    add column source_id bigint comment '派生对象的来源对象ID',
+    add column derivation varchar(200) comment '派生对象的处理参数',
+    add unique key AK_SOURCE_AND_DERIVATION (source_id, derivation);
+
+alter table oss_obj_ref
+    add column thumbnail_urls varchar(2000) comment '缩略图URL(多个用逗号分隔)';
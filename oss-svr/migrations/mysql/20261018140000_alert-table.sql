@@ -0,0 +1,4 @@
+-- 新增 oss_bucket 表的 exif_policy 字段
+
+alter table oss_bucket
+    add column exif_policy varchar(20) comment '上传图片时处理EXIF等元数据的策略(keep: 按原样保存，orient: 应用方向，strip: 应用方向并去除元数据)';
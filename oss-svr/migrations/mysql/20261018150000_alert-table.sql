@@ -0,0 +1,4 @@
+-- 新增 oss_bucket 表的 watermark 字段
+
+alter table oss_bucket
+    add column watermark varchar(500) comment '预览图片时添加的水印(格式与图片处理的watermark操作的参数相同)';
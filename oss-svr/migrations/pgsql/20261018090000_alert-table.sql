@@ -0,0 +1,13 @@
+-- 新增 oss_bucket 表的 previewable_types 字段及 oss_obj_ref 表的 content_type 字段
+
+-- 1. 新增可预览的MIME类型字段（可空，为空时使用配置中的默认值）
+ALTER TABLE oss_bucket
+    ADD COLUMN previewable_types VARCHAR(500) NULL;
+
+-- 2. 新增MIME类型字段（可空，早期的对象引用没有记录）
+ALTER TABLE oss_obj_ref
+    ADD COLUMN content_type VARCHAR(100) NULL;
+
+-- 3. 更新字段注释
+COMMENT ON COLUMN oss_bucket.previewable_types IS '可预览的MIME类型(多个用逗号分隔)';
+COMMENT ON COLUMN oss_obj_ref.content_type IS 'MIME类型';
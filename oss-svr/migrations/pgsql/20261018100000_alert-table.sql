@@ -0,0 +1,13 @@
+-- 新增 oss_obj 表的 content_type 字段及 oss_bucket 表的 mime_mismatch_policy 字段
+
+-- 1. 新增检测出的MIME类型字段（可空，无法检测或早期的对象没有记录）
+ALTER TABLE oss_obj
+    ADD COLUMN content_type VARCHAR(100) NULL;
+
+-- 2. 新增MIME类型不符时的策略字段（可空，为空时使用配置中的默认值）
+ALTER TABLE oss_bucket
+    ADD COLUMN mime_mismatch_policy VARCHAR(20) NULL;
+
+-- 3. 更新字段注释
+COMMENT ON COLUMN oss_obj.content_type IS '检测出的MIME类型';
+COMMENT ON COLUMN oss_bucket.mime_mismatch_policy IS 'MIME类型不符时的策略(reject/downgrade)';
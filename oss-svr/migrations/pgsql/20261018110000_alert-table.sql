@@ -0,0 +1,8 @@
+-- 新增 oss_bucket 表的 access 字段
+
+-- 1. 新增访问策略字段（可空，为空时为公共读）
+ALTER TABLE oss_bucket
+    ADD COLUMN access VARCHAR(20) NULL;
+
+-- 2. 更新字段注释
+COMMENT ON COLUMN oss_bucket.access IS '访问策略(public-read/private/authenticated)';
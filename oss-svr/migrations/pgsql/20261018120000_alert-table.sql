@@ -0,0 +1,35 @@
+-- 新增 oss_bucket_acl 表（存储桶的访问控制）
+
+-- 1. 新建表
+CREATE TABLE oss_bucket_acl
+(
+    _id               INT8        NOT NULL,
+    bucket_id         INT8        NOT NULL,
+    grantee_type      VARCHAR(10) NOT NULL,
+    grantee           VARCHAR(50) NOT NULL,
+    permission        VARCHAR(10) NOT NULL,
+    _creator_id       INT8        NOT NULL,
+    _create_timestamp INT8        NOT NULL,
+    _updator_id       INT8        NOT NULL,
+    _update_timestamp INT8        NOT NULL,
+    CONSTRAINT PK_OSS_BUCKET_ACL PRIMARY KEY (_id),
+    CONSTRAINT AK_GRANTEE_OSS_BUCKET_ACL UNIQUE (bucket_id, grantee_type, grantee)
+);
+
+-- 2. 添加外键约束
+ALTER TABLE oss_bucket_acl
+    ADD CONSTRAINT fk_acl_bucket_id__from__oss_bucket
+        FOREIGN KEY (bucket_id) REFERENCES oss_bucket (_id)
+            ON DELETE RESTRICT ON UPDATE RESTRICT;
+
+-- 3. 添加表及字段注释
+COMMENT ON TABLE oss_bucket_acl IS '存储桶的访问控制';
+COMMENT ON COLUMN oss_bucket_acl._id IS 'ID';
+COMMENT ON COLUMN oss_bucket_acl.bucket_id IS '桶ID';
+COMMENT ON COLUMN oss_bucket_acl.grantee_type IS '授权对象的类型(user/role)';
+COMMENT ON COLUMN oss_bucket_acl.grantee IS '授权对象(用户ID或角色名称)';
+COMMENT ON COLUMN oss_bucket_acl.permission IS '权限(read/write/admin)';
+COMMENT ON COLUMN oss_bucket_acl._creator_id IS '创建人的用户ID';
+COMMENT ON COLUMN oss_bucket_acl._create_timestamp IS '建立时间戳';
+COMMENT ON COLUMN oss_bucket_acl._updator_id IS '修改人的用户ID';
+COMMENT ON COLUMN oss_bucket_acl._update_timestamp IS '修改时间戳';
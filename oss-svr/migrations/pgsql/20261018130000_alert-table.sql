@@ -0,0 +1,24 @@
+-- 新增 oss_bucket 表的 thumbnail_sizes 字段、oss_obj 表的 source_id 及 derivation 字段、oss_obj_ref 表的 thumbnail_urls 字段
+
+-- 1. 新增缩略图的尺寸字段（可空，为空时不生成缩略图）
+ALTER TABLE oss_bucket
+    ADD COLUMN thumbnail_sizes VARCHAR(200) NULL;
+
+-- 2. 新增派生对象的来源对象ID及处理参数字段（可空，上传的对象为空）
+ALTER TABLE oss_obj
+    ADD COLUMN source_id INT8 NULL,
+    ADD COLUMN derivation VARCHAR(200) NULL;
+
+-- 3. 同一来源对象的相同处理参数只生成一个派生对象
+ALTER TABLE oss_obj
+    ADD CONSTRAINT AK_SOURCE_AND_DERIVATION_OSS_OBJ UNIQUE (source_id, derivation);
+
+-- 4. 新增缩略图URL字段（可空，不是可处理的图片或存储桶未设置缩略图的尺寸时为空）
+ALTER TABLE oss_obj_ref
+    ADD COLUMN thumbnail_urls VARCHAR(2000) NULL;
+
+-- 5. 更新字段注释
+COMMENT ON COLUMN oss_bucket.thumbnail_sizes IS '缩略图的尺寸(多个用逗号分隔，如200x200,400x300)';
+COMMENT ON COLUMN oss_obj.source_id IS '派生对象的来源对象ID';
+COMMENT ON COLUMN oss_obj.derivation IS '派生对象的处理参数';
+COMMENT ON COLUMN oss_obj_ref.thumbnail_urls IS '缩略图URL(多个用逗号分隔)';
@@ -0,0 +1,8 @@
+-- 新增 oss_bucket 表的 exif_policy 字段
+
+-- 1. 新增上传图片时处理EXIF等元数据的策略字段（可空，为空时按原样保存）
+ALTER TABLE oss_bucket
+    ADD COLUMN exif_policy VARCHAR(20) NULL;
+
+-- 2. 更新字段注释
+COMMENT ON COLUMN oss_bucket.exif_policy IS '上传图片时处理EXIF等元数据的策略(keep: 按原样保存，orient: 应用方向，strip: 应用方向并去除元数据)';
@@ -0,0 +1,8 @@
+-- 新增 oss_bucket 表的 watermark 字段
+
+-- 1. 新增预览图片时添加的水印字段（可空，为空时不添加水印）
+ALTER TABLE oss_bucket
+    ADD COLUMN watermark VARCHAR(500) NULL;
+
+-- 2. 更新字段注释
+COMMENT ON COLUMN oss_bucket.watermark IS '预览图片时添加的水印(格式与图片处理的watermark操作的参数相同)';
@@ -1,5 +1,8 @@
 use bytesize::ByteSize;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use strum::EnumString;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -19,6 +22,233 @@ pub struct OssConfig {
     /// 下载缓冲区大小
     #[serde(default = "download_buffer_size_default")]
     pub download_buffer_size: ByteSize,
+    /// 上传临时目录(存放分片上传会话的分片)
+    #[serde(default = "upload_temp_dir_default")]
+    pub upload_temp_dir: String,
+    /// 存储后端
+    #[serde(default = "StorageBackendConfig::default")]
+    pub backend: StorageBackendConfig,
+    /// 清理任务
+    #[serde(default = "ReaperConfig::default")]
+    pub reaper: ReaperConfig,
+    /// 扩展名对应的MIME类型，优先于内置的扩展名数据库，如 `md = "text/markdown"`
+    #[serde(default)]
+    pub mime_types: HashMap<String, String>,
+    /// 默认可预览的MIME类型，支持 `image/*` 形式的通配，存储桶未设置可预览类型时使用
+    #[serde(default = "previewable_types_default")]
+    pub previewable_types: Vec<String>,
+    /// 上传时检测出的MIME类型与扩展名不符时的默认策略，存储桶未设置策略时使用
+    #[serde(default)]
+    pub mime_mismatch_policy: MimeMismatchPolicy,
+    /// 预签名URL
+    #[serde(default = "PresignConfig::default")]
+    pub presign: PresignConfig,
+    /// 访问控制
+    #[serde(default = "AclConfig::default")]
+    pub acl: AclConfig,
+    /// 图片处理
+    #[serde(default = "ImageConfig::default")]
+    pub image: ImageConfig,
+    /// 渲染预览
+    #[serde(default = "RenderConfig::default")]
+    pub render: RenderConfig,
+}
+
+/// # 图片处理配置
+///
+/// 对应配置文件中的 `[oss.image]` 段，预览图片时可通过 `x-oss-process` 参数缩放、裁剪、旋转、添加水印及转换格式
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ImageConfig {
+    /// 处理后的图片的缓存目录
+    #[serde(default = "image_cache_dir_default")]
+    pub cache_dir: String,
+    /// 可以处理的原图的最大大小，避免解码过大的图片占用过多内存
+    #[serde(default = "image_max_source_size_default")]
+    pub max_source_size: ByteSize,
+    /// 原图及处理后的图片的最大宽度和高度
+    #[serde(default = "image_max_side_default")]
+    pub max_side: u32,
+    /// 文字水印的字体文件(TTF/OTF)，相对路径基于应用目录，未配置时不能添加文字水印
+    #[serde(default)]
+    pub watermark_font: Option<String>,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            cache_dir: image_cache_dir_default(),
+            max_source_size: image_max_source_size_default(),
+            max_side: image_max_side_default(),
+            watermark_font: None,
+        }
+    }
+}
+
+/// # 渲染预览配置
+///
+/// 对应配置文件中的 `[oss.render]` 段，渲染预览时将Markdown渲染为净化后的HTML，源代码语法高亮，CSV/TSV显示为分页的表格
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct RenderConfig {
+    /// 可以渲染的文件的最大大小，渲染时需要将整个文件读入内存
+    #[serde(default = "render_max_source_size_default")]
+    pub max_source_size: ByteSize,
+    /// CSV/TSV表格每页的行数(不包括表头)
+    #[serde(default = "render_page_size_default")]
+    pub page_size: usize,
+    /// 源代码语法高亮的主题
+    #[serde(default = "render_theme_default")]
+    pub theme: String,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            max_source_size: render_max_source_size_default(),
+            page_size: render_page_size_default(),
+            theme: render_theme_default(),
+        }
+    }
+}
+
+/// # 访问控制配置
+///
+/// 对应配置文件中的 `[oss.acl]` 段，启用后上传文件、存储桶及对象引用的增删改查、级联删除等接口按存储桶的访问控制校验权限
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct AclConfig {
+    /// 是否启用，未启用时不校验权限
+    #[serde(default)]
+    pub enabled: bool,
+    /// 传递当前用户角色的请求头，多个角色用逗号分隔
+    #[serde(default = "acl_roles_header_default")]
+    pub roles_header: String,
+    /// 超级管理员的角色，拥有所有存储桶的全部权限，用于运维
+    #[serde(default = "acl_superuser_role_default")]
+    pub superuser_role: String,
+}
+
+impl Default for AclConfig {
+    fn default() -> Self {
+        AclConfig {
+            enabled: false,
+            roles_header: acl_roles_header_default(),
+            superuser_role: acl_superuser_role_default(),
+        }
+    }
+}
+
+/// # 预签名URL配置
+///
+/// 对应配置文件中的 `[oss.presign]` 段，预签名URL以HMAC-SHA256签名，在有效期内无需其它认证即可访问
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct PresignConfig {
+    /// 签名密钥，未配置时不能生成预签名URL，带签名的请求都会被拒绝
+    pub secret: Option<String>,
+    /// 默认有效期
+    #[serde(default = "presign_default_expires_default", with = "humantime_serde")]
+    pub default_expires: Duration,
+    /// 最长有效期
+    #[serde(default = "presign_max_expires_default", with = "humantime_serde")]
+    pub max_expires: Duration,
+    /// 是否信任代理服务器设置的 `X-Forwarded-For`、`X-Real-IP` 请求头，用于获取限制IP时的客户端IP
+    #[serde(default)]
+    pub trust_proxy_headers: bool,
+}
+
+impl Default for PresignConfig {
+    fn default() -> Self {
+        PresignConfig {
+            secret: None,
+            default_expires: presign_default_expires_default(),
+            max_expires: presign_max_expires_default(),
+            trust_proxy_headers: false,
+        }
+    }
+}
+
+/// # MIME类型不符时的策略
+///
+/// 上传时根据文件头部的特征字节检测出的MIME类型与扩展名对应的MIME类型不符时(如扩展名为 `.jpg` 的HTML页面)的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, EnumString)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum MimeMismatchPolicy {
+    /// 拒绝上传
+    Reject,
+    /// 降级为 `application/octet-stream`，只能作为附件下载，不能预览
+    #[default]
+    Downgrade,
+}
+
+/// # 清理任务配置
+///
+/// 对应配置文件中的 `[oss.reaper]` 段，定时清理过期的未完成对象及孤立对象
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReaperConfig {
+    /// 是否启用
+    #[serde(default = "reaper_enabled_default")]
+    pub enabled: bool,
+    /// 执行间隔
+    #[serde(default = "reaper_interval_default", with = "humantime_serde")]
+    pub interval: Duration,
+    /// 宽限期，未完成对象创建后超过宽限期仍未完成才会被清理，分片上传会话和tus上传超过宽限期没有接收内容才会被清理
+    #[serde(default = "reaper_grace_period_default", with = "humantime_serde")]
+    pub grace_period: Duration,
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        ReaperConfig {
+            enabled: reaper_enabled_default(),
+            interval: reaper_interval_default(),
+            grace_period: reaper_grace_period_default(),
+        }
+    }
+}
+
+/// # 存储后端配置
+///
+/// 对应配置文件中的 `[oss.backend]` 段，通过 `type` 选择存储后端
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StorageBackendConfig {
+    /// 本地磁盘(默认)，文件存放在 `file-root-dir` 目录下
+    #[default]
+    Local,
+    /// 内存，进程退出即丢失，仅用于测试
+    Memory,
+    /// S3兼容的远程存储(如AWS S3、MinIO等)
+    S3(S3BackendConfig),
+}
+
+/// # S3存储后端配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct S3BackendConfig {
+    /// 服务地址，如 `http://127.0.0.1:9000`
+    pub endpoint: String,
+    /// 区域
+    #[serde(default = "s3_region_default")]
+    pub region: String,
+    /// 存储桶名称(S3中的bucket，与oss的桶无关，所有oss的桶都存放在此存储桶中)
+    pub bucket: String,
+    /// Access Key ID
+    pub access_key_id: String,
+    /// Secret Access Key
+    pub secret_access_key: String,
+    /// 是否允许使用http协议访问
+    #[serde(default = "s3_allow_http_default")]
+    pub allow_http: bool,
+    /// 是否使用虚拟主机风格的请求(`bucket.endpoint`)，MinIO等一般使用路径风格(`endpoint/bucket`)
+    #[serde(default)]
+    pub virtual_hosted_style: bool,
+    /// 分片上传时每个分片的大小(S3要求除最后一个分片外不能小于5MiB)
+    #[serde(default = "s3_part_size_default")]
+    pub part_size: ByteSize,
 }
 
 impl Default for OssConfig {
@@ -29,6 +259,16 @@ impl Default for OssConfig {
             upload_file_limit_size: upload_file_limit_size_default(),
             upload_buffer_size: upload_buffer_size_default(),
             download_buffer_size: download_buffer_size_default(),
+            upload_temp_dir: upload_temp_dir_default(),
+            backend: StorageBackendConfig::default(),
+            reaper: ReaperConfig::default(),
+            mime_types: HashMap::new(),
+            previewable_types: previewable_types_default(),
+            mime_mismatch_policy: MimeMismatchPolicy::default(),
+            presign: PresignConfig::default(),
+            acl: AclConfig::default(),
+            image: ImageConfig::default(),
+            render: RenderConfig::default(),
         }
     }
 }
@@ -51,3 +291,84 @@ fn upload_buffer_size_default() -> ByteSize {
 fn download_buffer_size_default() -> ByteSize {
     ByteSize::mib(1)
 }
+
+fn upload_temp_dir_default() -> String {
+    "upload-temp".to_string()
+}
+
+fn previewable_types_default() -> Vec<String> {
+    [
+        "image/*",
+        "audio/*",
+        "video/*",
+        "application/pdf",
+        "text/plain",
+        "text/markdown",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn reaper_enabled_default() -> bool {
+    true
+}
+
+fn reaper_interval_default() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn reaper_grace_period_default() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
+
+fn presign_default_expires_default() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn presign_max_expires_default() -> Duration {
+    Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+fn acl_roles_header_default() -> String {
+    "x-user-roles".to_string()
+}
+
+fn acl_superuser_role_default() -> String {
+    "oss-admin".to_string()
+}
+
+fn image_cache_dir_default() -> String {
+    "image-cache".to_string()
+}
+
+fn image_max_source_size_default() -> ByteSize {
+    ByteSize::mib(20)
+}
+
+fn image_max_side_default() -> u32 {
+    8192
+}
+
+fn render_max_source_size_default() -> ByteSize {
+    ByteSize::mib(5)
+}
+
+fn render_page_size_default() -> usize {
+    100
+}
+
+fn render_theme_default() -> String {
+    "InspiredGitHub".to_string()
+}
+
+fn s3_region_default() -> String {
+    "us-east-1".to_string()
+}
+
+fn s3_allow_http_default() -> bool {
+    true
+}
+
+fn s3_part_size_default() -> ByteSize {
+    ByteSize::mib(8)
+}
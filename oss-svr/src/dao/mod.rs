@@ -1,7 +1,9 @@
+mod oss_bucket_acl_dao;
 mod oss_bucket_dao;
 mod oss_obj_dao;
 mod oss_obj_ref_dao;
 
+pub use oss_bucket_acl_dao::*;
 pub use oss_bucket_dao::*;
 pub use oss_obj_dao::*;
 pub use oss_obj_ref_dao::*;
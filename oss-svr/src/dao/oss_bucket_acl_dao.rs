@@ -0,0 +1,63 @@
+use robotech::macros::dao;
+use sea_orm::{ColumnTrait, Condition};
+
+/// 存储桶的访问控制
+#[dao(
+    unique_keys: [
+        ("bucket_id,grantee_type,grantee", "存储桶的授权对象"),
+    ],
+    foreign_keys: [
+        ("bucket_id", "oss_bucket", "桶")
+    ],
+    like_columns: [
+        Column::Grantee
+    ],
+)]
+pub struct OssBucketAclDao;
+
+impl OssBucketAclDao {
+    /// # 获取授予用户或角色的存储桶权限
+    ///
+    /// ## 参数
+    /// * `bucket_id` - 存储桶ID
+    /// * `user_id` - 用户ID，未登录时为None
+    /// * `roles` - 用户拥有的角色
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回授予该用户及其角色的记录
+    pub async fn find_by_bucket_id_and_grantees<C>(
+        bucket_id: u64,
+        user_id: Option<u64>,
+        roles: &[String],
+        db: &C,
+    ) -> Result<Vec<Model>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        let mut grantees = Condition::any();
+        if let Some(user_id) = user_id {
+            grantees = grantees.add(
+                Condition::all()
+                    .add(Column::GranteeType.eq("user"))
+                    .add(Column::Grantee.eq(user_id.to_string())),
+            );
+        }
+        if !roles.is_empty() {
+            grantees = grantees.add(
+                Condition::all()
+                    .add(Column::GranteeType.eq("role"))
+                    .add(Column::Grantee.is_in(roles.iter().cloned())),
+            );
+        }
+        if grantees.is_empty() {
+            return Ok(vec![]);
+        }
+        Entity::find()
+            .filter(Column::BucketId.eq(bucket_id as i64))
+            .filter(grantees)
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+}
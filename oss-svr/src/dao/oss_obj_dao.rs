@@ -1,6 +1,6 @@
 use crate::model::oss_obj_ref::{Column as OssObjRefColumn, Entity as OssObjRefEntity};
 use robotech::macros::dao;
-use sea_orm::{ColumnTrait, QuerySelect, QueryTrait};
+use sea_orm::{ColumnTrait, Condition, QueryOrder, QuerySelect, QueryTrait};
 
 #[dao(
     unique_keys: [
@@ -18,7 +18,7 @@ pub struct OssObjDao;
 impl OssObjDao {
     /// # 获取孤立没有关联对象引用的记录
     ///
-    /// 此函数负责获取那些在 `oss_obj_ref` 表中没有关联记录的 `oss_obj` 记录。
+    /// 此函数负责获取那些在 `oss_obj_ref` 表中没有关联记录的 `oss_obj` 记录，以及来源对象已不存在的派生对象。
     /// 这有助于清理孤立的数据，释放存储空间。
     ///
     /// ## 参数
@@ -31,17 +31,113 @@ impl OssObjDao {
         C: ConnectionTrait,
     {
         // 使用子查询删除没有关联记录的oss_obj记录
+        // 未完成的对象还没有关联记录，由清理任务在宽限期后清理
+        // 派生对象不会被引用，随来源对象一起删除
         Entity::find()
-            .filter(
-                Column::Id.not_in_subquery(
-                    OssObjRefEntity::find()
-                        .select_only()
-                        .column(OssObjRefColumn::ObjId)
-                        .into_query(),
-                ),
-            )
+            .filter(Column::IsCompleted.eq(true))
+            .filter(Self::orphaned_condition())
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+
+    /// # 获取指定时间之前更新的孤立记录
+    ///
+    /// 与 `find_orphaned` 相同，但只返回更新时间早于指定时间戳的记录，避免清理刚完成还未新增对象引用的记录
+    ///
+    /// ## 参数
+    /// * `before_timestamp` - 时间戳(毫秒)
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回查询到的记录列表
+    pub async fn find_orphaned_before<C>(
+        before_timestamp: i64,
+        db: &C,
+    ) -> Result<Vec<Model>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::find()
+            .filter(Column::IsCompleted.eq(true))
+            .filter(Column::UpdateTimestamp.lt(before_timestamp))
+            .filter(Self::orphaned_condition())
             .all(db)
             .await
             .map_err(|e| DaoError::parse_db_err(e))
     }
+
+    /// # 分批获取指定时间之前创建的未完成记录
+    ///
+    /// 客户端断开连接或进程异常退出时，未完成(`is_completed = false`)的记录会一直保留，需要定时清理；
+    /// 按ID排序，只返回ID大于 `after_id` 的前 `limit` 条记录，避免一次加载过多的记录
+    ///
+    /// ## 参数
+    /// * `before_timestamp` - 时间戳(毫秒)
+    /// * `after_id` - 上一批最后一条记录的ID，第一批为0
+    /// * `limit` - 每批的最大记录数
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回查询到的记录列表
+    pub async fn find_incomplete_before<C>(
+        before_timestamp: i64,
+        after_id: u64,
+        limit: u64,
+        db: &C,
+    ) -> Result<Vec<Model>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::find()
+            .filter(Column::IsCompleted.eq(false))
+            .filter(Column::CreateTimestamp.lt(before_timestamp))
+            .filter(Column::Id.gt(after_id as i64))
+            .order_by_asc(Column::Id)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+
+    /// # 获取对象的派生对象
+    ///
+    /// ## 参数
+    /// * `source_id` - 来源对象ID
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回查询到的记录列表
+    pub async fn find_by_source_id<C>(source_id: u64, db: &C) -> Result<Vec<Model>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::find()
+            .filter(Column::SourceId.eq(source_id as i64))
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+
+    /// 孤立记录的条件：没有关联对象引用的上传对象，或来源对象已不存在的派生对象(生成派生对象后新增来源对象的事务回滚了)
+    fn orphaned_condition() -> Condition {
+        Condition::any()
+            .add(
+                Condition::all().add(Column::SourceId.is_null()).add(
+                    Column::Id.not_in_subquery(
+                        OssObjRefEntity::find()
+                            .select_only()
+                            .column(OssObjRefColumn::ObjId)
+                            .into_query(),
+                    ),
+                ),
+            )
+            .add(
+                Condition::all().add(Column::SourceId.is_not_null()).add(
+                    Column::SourceId.not_in_subquery(
+                        Entity::find().select_only().column(Column::Id).into_query(),
+                    ),
+                ),
+            )
+    }
 }
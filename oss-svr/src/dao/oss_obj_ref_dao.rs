@@ -1,5 +1,6 @@
 use crate::model::{oss_bucket, oss_obj};
 use robotech::macros::dao;
+use sea_orm::{ColumnTrait, QueryOrder};
 
 /// 对象引用
 #[dao(
@@ -21,3 +22,53 @@ use robotech::macros::dao;
     ]
 )]
 pub struct OssObjRefDao;
+
+impl OssObjRefDao {
+    /// # 根据ID列表获取记录及引用的对象
+    ///
+    /// ## 参数
+    /// * `ids` - ID列表
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回查询到的记录及引用的对象，按ID排序
+    pub async fn find_also_obj_by_ids<C>(
+        ids: &[u64],
+        db: &C,
+    ) -> Result<Vec<(Model, Option<oss_obj::Model>)>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::find()
+            .filter(Column::Id.is_in(ids.iter().map(|id| *id as i64)))
+            .find_also_related(oss_obj::Entity)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+
+    /// # 根据存储桶ID获取记录及引用的对象
+    ///
+    /// ## 参数
+    /// * `bucket_id` - 存储桶ID
+    /// * `db` - 数据库连接 trait 对象
+    ///
+    /// ## 返回值
+    /// 返回查询到的记录及引用的对象，按ID排序
+    pub async fn find_also_obj_by_bucket_id<C>(
+        bucket_id: u64,
+        db: &C,
+    ) -> Result<Vec<(Model, Option<oss_obj::Model>)>, DaoError>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::find()
+            .filter(Column::BucketId.eq(bucket_id as i64))
+            .find_also_related(oss_obj::Entity)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| DaoError::parse_db_err(e))
+    }
+}
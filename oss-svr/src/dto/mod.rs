@@ -1,3 +1,7 @@
+pub mod oss_bucket_acl_dto;
 pub mod oss_bucket_dto;
+pub mod oss_file_dto;
 pub mod oss_obj_dto;
 pub mod oss_obj_ref_dto;
+pub mod oss_presign_dto;
+pub mod oss_upload_session_dto;
@@ -0,0 +1,13 @@
+use robotech::macros::crud_dto;
+
+#[crud_dto]
+pub struct OssBucketAclDto {
+    /// 存储桶ID
+    pub bucket_id: u64,
+    /// 授权对象的类型(`user`: 用户，`role`: 角色)
+    pub grantee_type: String,
+    /// 授权对象(用户ID或角色名称)
+    pub grantee: String,
+    /// 权限(`read`: 下载，`write`: 上传及修改、删除文件，`admin`: 管理存储桶及其访问控制)
+    pub permission: String,
+}
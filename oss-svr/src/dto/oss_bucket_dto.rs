@@ -6,4 +6,16 @@ pub struct OssBucketDto {
     pub name: String,
     /// 备注
     pub remark: Option<String>,
+    /// 可预览的MIME类型(多个用逗号分隔，支持 `image/*` 形式的通配)，未设置时使用配置中的默认值
+    pub previewable_types: Option<String>,
+    /// 上传时检测出的MIME类型与扩展名不符时的策略(`reject`: 拒绝上传，`downgrade`: 降级为 `application/octet-stream`)，未设置时使用配置中的默认值
+    pub mime_mismatch_policy: Option<String>,
+    /// 访问策略(`public-read`: 公共读，`private`: 私有，`authenticated`: 登录用户可读)，未设置时为公共读
+    pub access: Option<String>,
+    /// 缩略图的尺寸(多个用逗号分隔，如 `200x200,400x300`，只有一个数字时为正方形)，上传可处理的图片时在后台按此生成缩略图，未设置时不生成
+    pub thumbnail_sizes: Option<String>,
+    /// 上传图片时处理EXIF等元数据的策略(`keep`: 按原样保存，`orient`: 按EXIF中的方向旋转图片，`strip`: 应用方向并去除包括GPS位置在内的元数据)，未设置时按原样保存
+    pub exif_policy: Option<String>,
+    /// 预览图片时添加的水印，格式与图片处理的 `watermark` 操作的参数相同(如 `text_5ZOB54mM,g_se,t_60` 或 `image_<对象引用ID>,P_20`)，文件的上传者及拥有 `write` 权限的用户下载时不添加，未设置时不添加水印
+    pub watermark: Option<String>,
 }
@@ -0,0 +1,45 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// # 秒传文件
+#[derive(ToSchema, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssFileInstantDto {
+    /// 文件名
+    pub name: String,
+    /// 文件大小
+    pub size: u64,
+    /// 文件Hash(SHA256)
+    pub hash: String,
+}
+
+/// # 下载文件的查询参数
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OssFileDownloadQueryDto {
+    /// 保存的文件名，未提供时使用上传时的文件名
+    pub filename: Option<String>,
+    /// 图片处理参数，只对预览图片有效，如 `image/resize,w_200/format,webp`
+    #[serde(rename = "x-oss-process")]
+    pub process: Option<String>,
+}
+
+/// # 渲染预览文件的查询参数
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OssFileRenderQueryDto {
+    /// 表格的页码，从1开始，只对CSV/TSV有效，未提供时为第1页
+    pub page: Option<usize>,
+}
+
+/// # 打包下载文件
+///
+/// `objRefIds` 与 `bucket` 二选一，都提供时只打包指定的对象引用
+#[derive(ToSchema, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssFileArchiveDto {
+    /// 要打包的对象引用ID列表
+    pub obj_ref_ids: Option<Vec<u64>>,
+    /// 要打包的存储桶名称，打包存储桶中所有的文件
+    pub bucket: Option<String>,
+    /// 保存的压缩包文件名，未提供时使用存储桶名称或 `archive.zip`
+    pub filename: Option<String>,
+}
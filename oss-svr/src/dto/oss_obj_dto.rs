@@ -8,6 +8,12 @@ pub struct OssObjDto {
     pub size: Option<u64>,
     /// Hash
     pub hash: Option<String>,
+    /// 检测出的MIME类型
+    pub content_type: Option<String>,
+    /// 派生对象的来源对象ID
+    pub source_id: Option<u64>,
+    /// 派生对象的处理参数
+    pub derivation: Option<String>,
     /// 是否完成
     pub is_completed: bool,
 }
@@ -14,4 +14,8 @@ pub struct OssObjRefDto {
     pub download_url: String,
     /// 预览URL
     pub preview_url: Option<String>,
+    /// MIME类型
+    pub content_type: Option<String>,
+    /// 缩略图URL(多个用逗号分隔)
+    pub thumbnail_urls: Option<String>,
 }
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// # 生成预签名URL
+#[derive(ToSchema, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssPresignDto {
+    /// 允许的请求方法(`GET`: 下载或预览，`POST`: 表单上传，`PUT`: 以原始请求体上传)，`GET` 同时允许 `HEAD`
+    pub method: String,
+    /// 请求路径，如 `/oss/file/download/123.jpg`、`/oss/file/upload/bucket`，可以是百分号编码的
+    pub path: String,
+    /// 有效期(秒)，未提供时使用配置中的默认有效期
+    pub expires_in: Option<u64>,
+    /// 限制只允许此IP访问
+    pub ip: Option<String>,
+    /// 限制请求体的最大长度(`Content-Length`)，用于限制上传文件的大小
+    pub max_content_length: Option<u64>,
+}
+
+/// # 预签名URL的查询参数
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OssPresignQueryDto {
+    /// 允许的请求方法
+    #[serde(rename = "x-oss-method")]
+    pub method: Option<String>,
+    /// 过期时间戳(秒)
+    #[serde(rename = "x-oss-expires")]
+    pub expires: Option<u64>,
+    /// 签发者的用户ID，通过预签名URL上传时作为创建者
+    #[serde(rename = "x-oss-user-id")]
+    pub user_id: Option<u64>,
+    /// 限制的IP
+    #[serde(rename = "x-oss-ip")]
+    pub ip: Option<String>,
+    /// 限制的请求体的最大长度
+    #[serde(rename = "x-oss-max-content-length")]
+    pub max_content_length: Option<u64>,
+    /// 签名(HMAC-SHA256的十六进制字符串)
+    #[serde(rename = "x-oss-signature")]
+    pub signature: Option<String>,
+}
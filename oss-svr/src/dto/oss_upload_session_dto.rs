@@ -0,0 +1,14 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// # 新建上传会话
+#[derive(ToSchema, Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssUploadSessionInitDto {
+    /// 文件名
+    pub name: String,
+    /// 文件大小
+    pub size: u64,
+    /// 文件Hash(SHA256)，提供时完成上传后会校验文件内容
+    pub hash: Option<String>,
+}
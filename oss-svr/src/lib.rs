@@ -2,6 +2,7 @@ pub mod app;
 pub mod dao;
 pub mod dto;
 pub mod model;
+pub mod storage;
 pub mod svc;
 pub mod vo;
 pub mod web;
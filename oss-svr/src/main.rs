@@ -3,6 +3,8 @@ use clap::Parser;
 use idworker::init_id_worker;
 use tracing::debug;
 use oss_svr::app::{set_app_config, AppConfig};
+use oss_svr::storage::init_storage_backend;
+use oss_svr::svc::init_reaper;
 use robotech;
 use robotech::app::{build_app_cfg, wait_app_exit};
 use robotech::cfg::watch_cfg_file;
@@ -119,7 +121,9 @@ async fn main() -> anyhow::Result<()> {
 /// 3. 根据配置中的数据库设置执行数据库迁移以确保数据库结构是最新的。
 /// 4. 初始化ID生成器，可能用于生成全局唯一ID。
 /// 5. 建立与数据库的连接。
-/// 6. 使用提供的或默认的端口号启动Web服务器，并处理任何给定的旧进程ID。
+/// 6. 根据配置初始化存储后端。
+/// 7. 启动定时清理未完成对象及孤立对象的任务。
+/// 8. 使用提供的或默认的端口号启动Web服务器，并处理任何给定的旧进程ID。
 ///
 /// ## Errors
 /// 如果在升级数据库版本时遇到问题，将打印错误信息并终止程序执行。
@@ -144,7 +148,7 @@ async fn apply_app_config(
         web_server: web_server_config,
         db: db_conn_config,
         id_worker: id_worker_config,
-        ..
+        oss: oss_config,
     } = app_config.clone();
     set_app_config(app_config)?;
 
@@ -158,6 +162,12 @@ async fn apply_app_config(
     // 初始化数据库连接
     init_db_conn(db_conn_config.clone()).await?;
 
+    // 初始化存储后端
+    init_storage_backend(&oss_config)?;
+
+    // 启动清理任务
+    init_reaper(&oss_config.reaper)?;
+
     // 启动Web服务器
     start_web_server(web_server_config, port, old_pid).await?;
 
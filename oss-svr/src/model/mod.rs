@@ -3,5 +3,6 @@
 pub mod prelude;
 
 pub mod oss_bucket;
+pub mod oss_bucket_acl;
 pub mod oss_obj;
 pub mod oss_obj_ref;
@@ -11,6 +11,12 @@ pub struct Model {
     #[sea_orm(unique)]
     pub name: String,
     pub remark: Option<String>,
+    pub previewable_types: Option<String>,
+    pub mime_mismatch_policy: Option<String>,
+    pub access: Option<String>,
+    pub thumbnail_sizes: Option<String>,
+    pub exif_policy: Option<String>,
+    pub watermark: Option<String>,
     #[sea_orm(column_name = "_creator_id")]
     pub creator_id: i64,
     #[sea_orm(column_name = "_create_timestamp")]
@@ -23,10 +29,18 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::oss_bucket_acl::Entity")]
+    OssBucketAcl,
     #[sea_orm(has_many = "super::oss_obj_ref::Entity")]
     OssObjRef,
 }
 
+impl Related<super::oss_bucket_acl::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OssBucketAcl.def()
+    }
+}
+
 impl Related<super::oss_obj_ref::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::OssObjRef.def()
@@ -13,6 +13,9 @@ pub struct Model {
     pub path: String,
     pub size: Option<i64>,
     pub hash: Option<String>,
+    pub content_type: Option<String>,
+    pub source_id: Option<i64>,
+    pub derivation: Option<String>,
     #[sea_orm(column_name = "_creator_id")]
     pub creator_id: i64,
     #[sea_orm(column_name = "_create_timestamp")]
@@ -23,6 +23,8 @@ pub struct Model {
     #[sea_orm(unique)]
     pub download_url: String,
     pub preview_url: Option<String>,
+    pub content_type: Option<String>,
+    pub thumbnail_urls: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
 pub use super::oss_bucket::Entity as OssBucket;
+pub use super::oss_bucket_acl::Entity as OssBucketAcl;
 pub use super::oss_obj::Entity as OssObj;
 pub use super::oss_obj_ref::Entity as OssObjRef;
@@ -0,0 +1,255 @@
+use crate::storage::{ByteStream, StorageBackend, StorageMeta};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use std::io;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::trace;
+
+/// # 本地磁盘存储后端
+///
+/// 对象存放在 `root_dir` 目录下，相对路径会拼接在 `root_dir` 之后，绝对路径则直接使用(兼容旧数据)
+#[derive(Debug)]
+pub struct LocalStorageBackend {
+    /// 文件根目录
+    root_dir: PathBuf,
+    /// 上传缓冲区大小
+    upload_buffer_size: u64,
+    /// 下载缓冲区大小
+    download_buffer_size: u64,
+}
+
+impl LocalStorageBackend {
+    pub fn new(root_dir: PathBuf, upload_buffer_size: u64, download_buffer_size: u64) -> Self {
+        Self {
+            root_dir,
+            upload_buffer_size,
+            download_buffer_size,
+        }
+    }
+
+    /// 获取对象在本地磁盘上的完整路径
+    fn full_path(&self, path: &str) -> PathBuf {
+        // 如果path是绝对路径，join会直接返回path
+        self.root_dir.join(path)
+    }
+
+    async fn write_stream(
+        file: &mut File,
+        mut stream: ByteStream<'_>,
+        buffer_size: u64,
+    ) -> io::Result<u64> {
+        let mut written: u64 = 0;
+        let mut buffer = Vec::with_capacity(buffer_size as usize);
+        // 分块写入，而非一次性读取
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            let chunk_size = chunk.len() as u64;
+            written += chunk_size;
+            if chunk_size >= buffer_size && !buffer.is_empty() {
+                trace!("切片大小 >= 缓存大小，先写入缓存中的数据，避免打乱写入顺序");
+                file.write_all(&buffer).await?;
+                buffer.clear();
+            }
+            if chunk_size == buffer_size {
+                trace!("切片大小 == 缓存大小，直接写入盘(没有用到缓存)");
+                file.write_all(&chunk).await?;
+            } else if chunk_size > buffer_size {
+                trace!(
+                    "切片大小 > 缓存大小，chunk按缓存大小分片处理，避免单次写入过大(这里也没有用到缓存)"
+                );
+                let mut chunk_start: usize = 0;
+                while chunk_start < chunk_size as usize {
+                    let chunk_end = (chunk_start + buffer_size as usize).min(chunk_size as usize);
+                    let slice = &chunk[chunk_start..chunk_end];
+                    file.write_all(slice).await?;
+                    chunk_start = chunk_end;
+                }
+            } else {
+                trace!("切片大小 < 缓存大小，攒够缓存满才写一次盘");
+                // 如果加入切片后缓存未超出缓存大小，则将切片添加入缓存中，否则将缓存中的数据写入盘，并清空缓存，再将切片写入缓存中
+                if buffer.len() as u64 + chunk_size <= buffer_size {
+                    buffer.extend_from_slice(&chunk);
+                } else {
+                    file.write_all(&buffer).await?;
+                    buffer.clear();
+                    buffer.extend_from_slice(&chunk);
+                }
+            }
+        }
+        // 写入剩余的缓存部分
+        if !buffer.is_empty() {
+            file.write_all(&buffer).await?;
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorageBackend {
+    async fn put(&self, path: &str, stream: ByteStream<'_>) -> io::Result<u64> {
+        let full_path = self.full_path(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = File::create(&full_path).await?;
+        match Self::write_stream(&mut file, stream, self.upload_buffer_size).await {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                // 写入失败，删除已写入的部分
+                drop(file);
+                fs::remove_file(&full_path).await.ok();
+                Err(e)
+            }
+        }
+    }
+
+    async fn get(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream<'static>> {
+        let buffer_size = self.download_buffer_size as usize;
+        let mut file = File::open(self.full_path(path)).await?;
+        Ok(if let Some((start, end)) = range {
+            file.seek(SeekFrom::Start(start)).await?;
+            // 用 Take 限制只读 chunk_size 字节，再包成流
+            let limited = file.take(end - start + 1);
+            ReaderStream::with_capacity(limited, buffer_size).boxed()
+        } else {
+            ReaderStream::with_capacity(file, buffer_size).boxed()
+        })
+    }
+
+    async fn delete(&self, path: &str) -> io::Result<()> {
+        fs::remove_file(self.full_path(path)).await
+    }
+
+    async fn stat(&self, path: &str) -> io::Result<StorageMeta> {
+        let metadata = fs::metadata(self.full_path(path)).await?;
+        Ok(StorageMeta {
+            size: metadata.len(),
+            last_modified: metadata.modified().ok(),
+        })
+    }
+
+    async fn exists(&self, path: &str) -> io::Result<bool> {
+        fs::try_exists(self.full_path(path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use futures_util::{TryStreamExt, stream};
+    use tempfile::TempDir;
+
+    /// 缓冲区设置得很小，以便覆盖小于、等于、大于缓冲区大小的切片
+    const BUFFER_SIZE: u64 = 4;
+
+    fn new_backend() -> (TempDir, LocalStorageBackend) {
+        let root_dir = TempDir::new().unwrap();
+        let backend = LocalStorageBackend::new(root_dir.path().to_path_buf(), BUFFER_SIZE, 3);
+        (root_dir, backend)
+    }
+
+    fn to_stream(chunks: &[&'static [u8]]) -> ByteStream<'static> {
+        let chunks: Vec<io::Result<Bytes>> = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::from_static(chunk)))
+            .collect();
+        stream::iter(chunks).boxed()
+    }
+
+    async fn read_all(
+        backend: &LocalStorageBackend,
+        path: &str,
+        range: Option<(u64, u64)>,
+    ) -> Vec<u8> {
+        let chunks: Vec<Bytes> = backend
+            .get(path, range)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        chunks.concat()
+    }
+
+    #[tokio::test]
+    async fn put_mixed_chunks_keeps_order() {
+        let (_root_dir, backend) = new_backend();
+        // 小切片进入缓存后，紧跟等于及大于缓存大小的切片，不能先于缓存中的数据写入
+        let chunks: &[&'static [u8]] = &[
+            b"ab",
+            b"cdef",
+            b"g",
+            b"hijklmnopq",
+            b"r",
+            b"s",
+            b"tuvw",
+            b"xyz",
+        ];
+        let written = backend.put("a/b/c.txt", to_stream(chunks)).await.unwrap();
+        assert_eq!(written, 26);
+        assert_eq!(
+            read_all(&backend, "a/b/c.txt", None).await,
+            b"abcdefghijklmnopqrstuvwxyz"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_range() {
+        let (_root_dir, backend) = new_backend();
+        backend
+            .put("range.txt", to_stream(&[b"0123456789"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_all(&backend, "range.txt", Some((2, 7))).await,
+            b"234567"
+        );
+        assert_eq!(read_all(&backend, "range.txt", Some((9, 9))).await, b"9");
+    }
+
+    #[tokio::test]
+    async fn put_error_removes_partial_file() {
+        let (_root_dir, backend) = new_backend();
+        let chunks: Vec<io::Result<Bytes>> = vec![
+            Ok(Bytes::from_static(b"abcdefgh")),
+            Err(io::Error::other("中断")),
+        ];
+        assert!(
+            backend
+                .put("broken.txt", stream::iter(chunks).boxed())
+                .await
+                .is_err()
+        );
+        assert!(!backend.exists("broken.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stat_and_delete() {
+        let (_root_dir, backend) = new_backend();
+        backend
+            .put("stat.txt", to_stream(&[b"hello", b" ", b"world"]))
+            .await
+            .unwrap();
+        let meta = backend.stat("stat.txt").await.unwrap();
+        assert_eq!(meta.size, 11);
+        assert!(meta.last_modified.is_some());
+
+        backend.delete("stat.txt").await.unwrap();
+        assert!(!backend.exists("stat.txt").await.unwrap());
+        assert_eq!(
+            backend.stat("stat.txt").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            backend.delete("stat.txt").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::storage::{ByteStream, StorageBackend, StorageMeta};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures_util::{StreamExt, stream};
+use std::collections::HashMap;
+use std::io;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// # 内存存储后端
+///
+/// 对象内容保存在内存中，进程退出即丢失，仅用于测试
+#[derive(Debug, Default)]
+pub struct MemoryStorageBackend {
+    objects: RwLock<HashMap<String, MemoryObject>>,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryObject {
+    data: Bytes,
+    last_modified: SystemTime,
+}
+
+impl MemoryStorageBackend {
+    fn get_object(&self, path: &str) -> io::Result<MemoryObject> {
+        let read_lock = self
+            .objects
+            .read()
+            .map_err(|_| io::Error::other("读取内存存储失败"))?;
+        read_lock
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for MemoryStorageBackend {
+    async fn put(&self, path: &str, mut stream: ByteStream<'_>) -> io::Result<u64> {
+        let mut data = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+        let written = data.len() as u64;
+        let mut write_lock = self
+            .objects
+            .write()
+            .map_err(|_| io::Error::other("写入内存存储失败"))?;
+        write_lock.insert(
+            path.to_string(),
+            MemoryObject {
+                data: data.freeze(),
+                last_modified: SystemTime::now(),
+            },
+        );
+        Ok(written)
+    }
+
+    async fn get(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream<'static>> {
+        let object = self.get_object(path)?;
+        let data = match range {
+            Some((start, end)) => {
+                let end = (end as usize + 1).min(object.data.len());
+                let start = (start as usize).min(end);
+                object.data.slice(start..end)
+            }
+            None => object.data,
+        };
+        Ok(stream::once(async move { Ok(data) }).boxed())
+    }
+
+    async fn delete(&self, path: &str) -> io::Result<()> {
+        let mut write_lock = self
+            .objects
+            .write()
+            .map_err(|_| io::Error::other("写入内存存储失败"))?;
+        write_lock
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+    }
+
+    async fn stat(&self, path: &str) -> io::Result<StorageMeta> {
+        let object = self.get_object(path)?;
+        Ok(StorageMeta {
+            size: object.data.len() as u64,
+            last_modified: Some(object.last_modified),
+        })
+    }
+
+    async fn exists(&self, path: &str) -> io::Result<bool> {
+        match self.get_object(path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+
+    fn to_stream(chunks: &[&'static [u8]]) -> ByteStream<'static> {
+        let chunks: Vec<io::Result<Bytes>> = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::from_static(chunk)))
+            .collect();
+        stream::iter(chunks).boxed()
+    }
+
+    async fn read_all(
+        backend: &MemoryStorageBackend,
+        path: &str,
+        range: Option<(u64, u64)>,
+    ) -> Vec<u8> {
+        let chunks: Vec<Bytes> = backend
+            .get(path, range)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        chunks.concat()
+    }
+
+    #[tokio::test]
+    async fn put_and_get() {
+        let backend = MemoryStorageBackend::default();
+        let written = backend
+            .put("a/b.txt", to_stream(&[b"ab", b"cdefghij", b"k"]))
+            .await
+            .unwrap();
+        assert_eq!(written, 11);
+        assert_eq!(read_all(&backend, "a/b.txt", None).await, b"abcdefghijk");
+        assert_eq!(read_all(&backend, "a/b.txt", Some((2, 5))).await, b"cdef");
+        // 结束位置超出对象大小时读到末尾
+        assert_eq!(read_all(&backend, "a/b.txt", Some((8, 100))).await, b"ijk");
+    }
+
+    #[tokio::test]
+    async fn put_error_keeps_nothing() {
+        let backend = MemoryStorageBackend::default();
+        let chunks: Vec<io::Result<Bytes>> = vec![
+            Ok(Bytes::from_static(b"abc")),
+            Err(io::Error::other("中断")),
+        ];
+        assert!(
+            backend
+                .put("broken.txt", stream::iter(chunks).boxed())
+                .await
+                .is_err()
+        );
+        assert!(!backend.exists("broken.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stat_and_delete() {
+        let backend = MemoryStorageBackend::default();
+        backend
+            .put("stat.txt", to_stream(&[b"hello"]))
+            .await
+            .unwrap();
+        let meta = backend.stat("stat.txt").await.unwrap();
+        assert_eq!(meta.size, 5);
+        assert!(meta.last_modified.is_some());
+
+        backend.delete("stat.txt").await.unwrap();
+        assert!(!backend.exists("stat.txt").await.unwrap());
+        assert_eq!(
+            backend.stat("stat.txt").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            backend.delete("stat.txt").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,9 @@
+mod local_storage_backend;
+mod memory_storage_backend;
+mod s3_storage_backend;
+mod storage_backend;
+
+pub use local_storage_backend::*;
+pub use memory_storage_backend::*;
+pub use s3_storage_backend::*;
+pub use storage_backend::*;
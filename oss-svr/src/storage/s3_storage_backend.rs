@@ -0,0 +1,138 @@
+use crate::app::S3BackendConfig;
+use crate::storage::{ByteStream, StorageBackend, StorageMeta};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::{GetOptions, GetRange, ObjectStore, WriteMultipart};
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+
+/// 分片上传时同时进行中的分片数量
+const MAX_CONCURRENCY: usize = 4;
+
+/// # S3兼容的远程存储后端
+///
+/// 对象内容存放在S3兼容服务(如AWS S3、MinIO等)的存储桶中，`oss_obj.path` 即为对象的key
+pub struct S3StorageBackend {
+    store: AmazonS3,
+    /// 服务地址
+    endpoint: String,
+    /// 存储桶名称
+    bucket: String,
+    /// 分片上传时每个分片的大小
+    part_size: usize,
+}
+
+impl S3StorageBackend {
+    pub fn new(config: &S3BackendConfig) -> anyhow::Result<Self> {
+        let store = AmazonS3Builder::new()
+            .with_endpoint(&config.endpoint)
+            .with_region(&config.region)
+            .with_bucket_name(&config.bucket)
+            .with_access_key_id(&config.access_key_id)
+            .with_secret_access_key(&config.secret_access_key)
+            .with_allow_http(config.allow_http)
+            .with_virtual_hosted_style_request(config.virtual_hosted_style)
+            .build()?;
+        Ok(Self {
+            store,
+            endpoint: config.endpoint.clone(),
+            bucket: config.bucket.clone(),
+            part_size: config.part_size.as_u64() as usize,
+        })
+    }
+
+    /// 将object_store的错误转换为io错误，保留NotFound语义
+    fn map_err(e: object_store::Error) -> io::Error {
+        match e {
+            object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, e),
+            _ => io::Error::other(e),
+        }
+    }
+}
+
+/// 不输出访问凭证
+impl Debug for S3StorageBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3StorageBackend")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("part_size", &self.part_size)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3StorageBackend {
+    async fn put(&self, path: &str, mut stream: ByteStream<'_>) -> io::Result<u64> {
+        let upload = self
+            .store
+            .put_multipart(&ObjectPath::from(path))
+            .await
+            .map_err(Self::map_err)?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, self.part_size);
+        let mut written: u64 = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    // 中止分片上传，已上传的分片由S3服务端丢弃
+                    writer.abort().await.ok();
+                    return Err(e);
+                }
+            };
+            // 控制同时上传的分片数量，避免接收速度大于上传速度时占用过多内存
+            if let Err(e) = writer.wait_for_capacity(MAX_CONCURRENCY).await {
+                writer.abort().await.ok();
+                return Err(Self::map_err(e));
+            }
+            written += chunk.len() as u64;
+            writer.write(&chunk);
+        }
+        writer.finish().await.map_err(Self::map_err)?;
+        Ok(written)
+    }
+
+    async fn get(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream<'static>> {
+        let options = GetOptions {
+            range: range.map(|(start, end)| GetRange::Bounded(start..end + 1)),
+            ..Default::default()
+        };
+        let result = self
+            .store
+            .get_opts(&ObjectPath::from(path), options)
+            .await
+            .map_err(Self::map_err)?;
+        Ok(result.into_stream().map(|r| r.map_err(Self::map_err)).boxed())
+    }
+
+    async fn delete(&self, path: &str) -> io::Result<()> {
+        // S3删除不存在的对象不会报错，先确认对象存在，保持与其它存储后端一致的NotFound语义
+        self.stat(path).await?;
+        self.store
+            .delete(&ObjectPath::from(path))
+            .await
+            .map_err(Self::map_err)
+    }
+
+    async fn stat(&self, path: &str) -> io::Result<StorageMeta> {
+        let meta = self
+            .store
+            .head(&ObjectPath::from(path))
+            .await
+            .map_err(Self::map_err)?;
+        Ok(StorageMeta {
+            size: meta.size,
+            last_modified: Some(meta.last_modified.into()),
+        })
+    }
+
+    async fn exists(&self, path: &str) -> io::Result<bool> {
+        match self.stat(path).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
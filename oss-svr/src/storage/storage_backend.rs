@@ -0,0 +1,99 @@
+use crate::app::{OssConfig, StorageBackendConfig};
+use crate::storage::{LocalStorageBackend, MemoryStorageBackend, S3StorageBackend};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use robotech::env::{APP_ENV, EnvError};
+use std::fmt::Debug;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tracing::info;
+
+static STORAGE_BACKEND: RwLock<Option<Arc<dyn StorageBackend>>> = RwLock::new(None);
+
+/// 字节流，存储后端读写对象内容时使用
+pub type ByteStream<'a> = BoxStream<'a, io::Result<Bytes>>;
+
+/// # 存储对象的元数据
+#[derive(Debug, Clone)]
+pub struct StorageMeta {
+    /// 对象大小(字节)
+    pub size: u64,
+    /// 最后修改时间(后端不支持时为None)
+    pub last_modified: Option<SystemTime>,
+}
+
+/// # 存储后端
+///
+/// 负责对象内容的存取，`oss_obj.path` 即为对象在存储后端中的路径。
+/// 上传、去重等业务逻辑只通过此 trait 读写对象内容，不关心内容实际存放在哪里。
+#[async_trait]
+pub trait StorageBackend: Send + Sync + Debug {
+    /// # 写入对象
+    ///
+    /// 将字节流写入指定路径，如果写入过程中流返回错误，需清理已写入的部分再返回该错误
+    ///
+    /// ## 返回值
+    /// 返回写入的字节数
+    async fn put(&self, path: &str, stream: ByteStream<'_>) -> io::Result<u64>;
+
+    /// # 读取对象
+    ///
+    /// ## 参数
+    /// * `path` - 对象路径
+    /// * `range` - 读取范围(起始位置, 结束位置)，两端都包含，为None时读取整个对象
+    async fn get(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream<'static>>;
+
+    /// # 删除对象
+    ///
+    /// 对象不存在时返回 `io::ErrorKind::NotFound` 错误
+    async fn delete(&self, path: &str) -> io::Result<()>;
+
+    /// # 获取对象的元数据
+    ///
+    /// 对象不存在时返回 `io::ErrorKind::NotFound` 错误
+    async fn stat(&self, path: &str) -> io::Result<StorageMeta>;
+
+    /// # 判断对象是否存在
+    async fn exists(&self, path: &str) -> io::Result<bool>;
+}
+
+/// 获取存储后端
+pub fn get_storage_backend() -> io::Result<Arc<dyn StorageBackend>> {
+    let read_lock = STORAGE_BACKEND
+        .read()
+        .map_err(|_| io::Error::other("获取存储后端失败"))?;
+    read_lock
+        .clone()
+        .ok_or_else(|| io::Error::other("存储后端未初始化"))
+}
+
+/// # 初始化存储后端
+///
+/// 根据 `[oss.backend]` 配置创建存储后端，重新加载配置时会替换掉旧的存储后端
+pub fn init_storage_backend(oss_config: &OssConfig) -> anyhow::Result<()> {
+    let backend: Arc<dyn StorageBackend> = match &oss_config.backend {
+        StorageBackendConfig::Local => {
+            let root_dir = APP_ENV
+                .get()
+                .ok_or(EnvError::GetAppEnv())?
+                .app_dir
+                .join(&oss_config.file_root_dir);
+            Arc::new(LocalStorageBackend::new(
+                root_dir,
+                oss_config.upload_buffer_size.as_u64(),
+                oss_config.download_buffer_size.as_u64(),
+            ))
+        }
+        StorageBackendConfig::Memory => Arc::new(MemoryStorageBackend::default()),
+        StorageBackendConfig::S3(s3_config) => Arc::new(S3StorageBackend::new(s3_config)?),
+    };
+    info!("初始化存储后端: {:?}", backend);
+
+    let mut write_lock = STORAGE_BACKEND
+        .write()
+        .map_err(|_| io::Error::other("设置存储后端失败"))?;
+    *write_lock = Some(backend);
+    Ok(())
+}
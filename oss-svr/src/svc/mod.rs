@@ -1,9 +1,31 @@
+mod oss_archive_svc;
+mod oss_bucket_acl_svc;
 mod oss_bucket_svc;
+mod oss_exif_svc;
 mod oss_file_svc;
+mod oss_image_svc;
+mod oss_mime_svc;
 mod oss_obj_ref_svc;
 mod oss_obj_svc;
+mod oss_presign_svc;
+mod oss_reaper_svc;
+mod oss_render_svc;
+mod oss_thumbnail_svc;
+mod oss_tus_svc;
+mod oss_upload_session_svc;
 
+pub use oss_archive_svc::*;
+pub use oss_bucket_acl_svc::*;
 pub use oss_bucket_svc::*;
+pub use oss_exif_svc::*;
 pub use oss_file_svc::*;
+pub use oss_image_svc::*;
+pub use oss_mime_svc::*;
 pub use oss_obj_ref_svc::*;
 pub use oss_obj_svc::*;
+pub use oss_presign_svc::*;
+pub use oss_reaper_svc::*;
+pub use oss_render_svc::*;
+pub use oss_thumbnail_svc::*;
+pub use oss_tus_svc::*;
+pub use oss_upload_session_svc::*;
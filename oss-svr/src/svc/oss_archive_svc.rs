@@ -0,0 +1,424 @@
+use crate::dao::OssObjRefDao;
+use crate::dto::oss_file_dto::OssFileArchiveDto;
+use crate::model::{oss_obj, oss_obj_ref};
+use crate::storage::get_storage_backend;
+use crate::svc::{OssAccessor, OssBucketAclSvc, OssBucketSvc, OssMimeSvc, OssPermission};
+use crate::svc::{OssImageProcess, OssImageSvc};
+use anyhow::anyhow;
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use axum::body::Body;
+use bytes::Bytes;
+use chrono::{Local, TimeZone};
+use futures_util::io::AsyncWriteExt;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use robotech::macros::db_unwrap;
+use robotech::svc::SvcError;
+use sea_orm::ConnectionTrait;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+use tokio::io::DuplexStream;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error};
+
+/// 写入压缩包与读取压缩包之间的缓冲区大小
+const ARCHIVE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 需要压缩的MIME类型(除 `text/*` 外)，其它类型(图片、音视频、压缩包等)大多已经压缩过，直接存储
+const DEFLATE_TYPES: [&str; 5] = [
+    "application/json",
+    "application/xml",
+    "application/javascript",
+    "application/x-sh",
+    "image/svg+xml",
+];
+
+/// # 打包下载服务
+///
+/// 将多个对象引用或整个存储桶的文件打包为ZIP，边读取存储后端边生成压缩包，不在内存或磁盘中缓存整个压缩包
+pub struct OssArchiveSvc;
+
+impl OssArchiveSvc {
+    /// # 打包文件
+    ///
+    /// 只查询要打包的文件，此时还未读取文件内容；压缩包在返回的 `Body` 被读取时才生成，
+    /// 读取或写入某个文件失败时中止响应，客户端会得到不完整的压缩包；
+    /// 存储桶设置了水印时，除文件的上传者及拥有存储桶 `write` 权限的用户外，打包的图片都添加水印
+    ///
+    /// ## 参数
+    /// * `dto` - 要打包的对象引用ID列表或存储桶名称
+    /// * `accessor` - 访问者，用于按存储桶的访问策略判断是否可以下载
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(OssFileArchive)` - 返回压缩包的文件名及内容
+    /// * `Err(SvcError)` - 打包失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果存储桶或某个对象引用不存在，返回 NotFound 错误
+    /// * 指定对象引用ID时，如果无权下载其中某个文件，视为不存在；打包整个存储桶时跳过无权下载的文件
+    #[db_unwrap]
+    pub async fn archive<C>(
+        dto: OssFileArchiveDto,
+        accessor: &OssAccessor,
+        db: Option<&C>,
+    ) -> Result<OssFileArchive, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let (file_name, obj_refs) = match (dto.obj_ref_ids, dto.bucket) {
+            (Some(obj_ref_ids), _) => {
+                let obj_refs = OssObjRefDao::find_also_obj_by_ids(&obj_ref_ids, db).await?;
+                // 对象引用可能分布在多个存储桶中，每个存储桶只判断一次
+                let mut readable_buckets = HashMap::new();
+                for (obj_ref, _) in &obj_refs {
+                    let bucket_id = obj_ref.bucket_id as u64;
+                    if !readable_buckets.contains_key(&bucket_id) {
+                        let bucket_vo =
+                            OssBucketSvc::get_by_id(bucket_id, Some(db))
+                                .await?
+                                .extra
+                                .ok_or(SvcError::NotFound(format!("bucket id: {}", bucket_id)))?;
+                        let readable =
+                            OssBucketSvc::can_read_bucket(&bucket_vo, accessor, Some(db)).await?;
+                        readable_buckets.insert(bucket_id, readable);
+                    }
+                }
+                let obj_refs: Vec<_> = obj_refs
+                    .into_iter()
+                    .filter(|(obj_ref, _)| {
+                        readable_buckets[&(obj_ref.bucket_id as u64)]
+                            || Self::is_creator(obj_ref, accessor)
+                    })
+                    .collect();
+                let found_ids: HashSet<u64> = obj_refs
+                    .iter()
+                    .map(|(obj_ref, _)| obj_ref.id as u64)
+                    .collect();
+                let not_found_ids: Vec<String> = obj_ref_ids
+                    .iter()
+                    .filter(|id| !found_ids.contains(id))
+                    .map(|id| id.to_string())
+                    .collect();
+                if !not_found_ids.is_empty() {
+                    return Err(SvcError::NotFound(format!(
+                        "ids: {}",
+                        not_found_ids.join(",")
+                    )));
+                }
+                ("archive".to_string(), obj_refs)
+            }
+            (None, Some(bucket)) => {
+                let bucket_vo = OssBucketSvc::get_by_name(&bucket, Some(db))
+                    .await?
+                    .extra
+                    .ok_or(SvcError::NotFound(format!("bucket: {}", bucket)))?;
+                let obj_refs = OssObjRefDao::find_also_obj_by_bucket_id(bucket_vo.id, db).await?;
+                let readable =
+                    OssBucketSvc::can_read_bucket(&bucket_vo, accessor, Some(db)).await?;
+                let obj_refs = obj_refs
+                    .into_iter()
+                    .filter(|(obj_ref, _)| readable || Self::is_creator(obj_ref, accessor))
+                    .collect();
+                (bucket, obj_refs)
+            }
+            (None, None) => {
+                return Err(SvcError::Runtime(anyhow!("未提供要打包的对象引用或存储桶")));
+            }
+        };
+
+        let mut entry_names = HashSet::new();
+        let mut entries = Vec::with_capacity(obj_refs.len());
+        // 每个存储桶的水印只加载一次
+        let mut watermarks = HashMap::new();
+        for (obj_ref, obj) in obj_refs {
+            let obj = obj.ok_or(SvcError::NotFound(format!("obj id: {}", obj_ref.obj_id)))?;
+            let content_type = match &obj_ref.content_type {
+                Some(content_type) => content_type.clone(),
+                None => OssMimeSvc::get_content_type(&obj_ref.ext)?,
+            };
+            let watermark = if OssImageSvc::is_processable(&content_type)
+                && !Self::is_creator(&obj_ref, accessor)
+            {
+                Self::get_watermark(obj_ref.bucket_id as u64, accessor, &mut watermarks, db).await?
+            } else {
+                None
+            };
+            entries.push(Self::to_entry(
+                obj_ref,
+                obj,
+                content_type,
+                watermark,
+                &mut entry_names,
+            ));
+        }
+
+        let file_name = dto
+            .filename
+            .filter(|file_name| !file_name.trim().is_empty())
+            .unwrap_or_else(|| format!("{}.zip", file_name));
+        let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_SIZE);
+        let write_task = tokio::spawn(Self::write_archive(writer, entries));
+        // 压缩包写完后再检查写入的结果，写入失败时以错误结束响应
+        let write_result = stream::once(async move {
+            match write_task.await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => {
+                    error!("打包失败: {}", e);
+                    Some(Err(e))
+                }
+                Err(e) => {
+                    error!("打包任务异常: {}", e);
+                    Some(Err(io::Error::other(e)))
+                }
+            }
+        })
+        .filter_map(|result| async move { result });
+        let body = Body::from_stream(ReaderStream::new(reader).chain(write_result));
+        Ok(OssFileArchive { file_name, body })
+    }
+
+    /// 访问者是否是文件的上传者，上传者可以下载自己上传的文件
+    fn is_creator(obj_ref: &oss_obj_ref::Model, accessor: &OssAccessor) -> bool {
+        accessor.user_id == Some(obj_ref.creator_id as u64)
+    }
+
+    /// # 获取存储桶的水印
+    ///
+    /// 存储桶未设置水印或访问者拥有存储桶的 `write` 权限时返回None，加载的水印按存储桶ID缓存
+    async fn get_watermark<C>(
+        bucket_id: u64,
+        accessor: &OssAccessor,
+        watermarks: &mut HashMap<u64, Option<Arc<ArchiveWatermark>>>,
+        db: &C,
+    ) -> Result<Option<Arc<ArchiveWatermark>>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(watermark) = watermarks.get(&bucket_id) {
+            return Ok(watermark.clone());
+        }
+        let bucket_vo = OssBucketSvc::get_by_id(bucket_id, Some(db))
+            .await?
+            .extra
+            .ok_or(SvcError::NotFound(format!("bucket id: {}", bucket_id)))?;
+        let watermark = match &bucket_vo.watermark {
+            Some(watermark)
+                if accessor.user_id.is_none()
+                    || !OssBucketAclSvc::has_permission(
+                        &bucket_vo,
+                        accessor,
+                        OssPermission::Write,
+                        Some(db),
+                    )
+                    .await? =>
+            {
+                let (process, images) =
+                    OssImageSvc::load_watermark(watermark, bucket_id, accessor).await?;
+                Some(Arc::new(ArchiveWatermark { process, images }))
+            }
+            _ => None,
+        };
+        watermarks.insert(bucket_id, watermark.clone());
+        Ok(watermark)
+    }
+
+    /// 根据对象引用生成压缩包中的条目，条目名称为对象引用的名称，重名时加上序号
+    fn to_entry(
+        obj_ref: oss_obj_ref::Model,
+        obj: oss_obj::Model,
+        content_type: String,
+        watermark: Option<Arc<ArchiveWatermark>>,
+        entry_names: &mut HashSet<String>,
+    ) -> ArchiveEntry {
+        let essence = OssMimeSvc::get_essence(&content_type);
+        let compression =
+            if essence.starts_with("text/") || DEFLATE_TYPES.contains(&essence.as_str()) {
+                Compression::Deflate
+            } else {
+                Compression::Stored
+            };
+        let modified = obj_ref.update_timestamp.max(obj.update_timestamp);
+        ArchiveEntry {
+            name: Self::get_unique_entry_name(&obj_ref.name, entry_names),
+            path: obj.path,
+            compression,
+            modified,
+            size: obj.size.unwrap_or_default() as u64,
+            content_type,
+            watermark,
+        }
+    }
+
+    /// # 获取不重复的条目名称
+    ///
+    /// 名称中的路径分隔符替换为 `_`，避免解压时写到目标目录之外；
+    /// 与已有的名称重复(不区分大小写)时在扩展名前加上序号，如 `a.txt`、`a (1).txt`、`a (2).txt`
+    fn get_unique_entry_name(name: &str, entry_names: &mut HashSet<String>) -> String {
+        let name = name.replace(['/', '\\'], "_");
+        let name = if matches!(name.trim(), "" | "." | "..") {
+            "unnamed".to_string()
+        } else {
+            name
+        };
+        let (stem, ext) = match name.rfind('.') {
+            Some(index) if index > 0 => name.split_at(index),
+            _ => (name.as_str(), ""),
+        };
+        let mut entry_name = name.clone();
+        let mut index = 1;
+        while !entry_names.insert(entry_name.to_lowercase()) {
+            entry_name = format!("{} ({}){}", stem, index, ext);
+            index += 1;
+        }
+        entry_name
+    }
+
+    /// 依次读取每个文件写入压缩包，写完后关闭压缩包
+    async fn write_archive(writer: DuplexStream, entries: Vec<ArchiveEntry>) -> io::Result<()> {
+        let storage_backend = get_storage_backend()?;
+        let mut zip_writer = ZipFileWriter::with_tokio(writer);
+        for entry in entries {
+            debug!("写入压缩包条目: {} <- {}", entry.name, entry.path);
+            let mut builder = ZipEntryBuilder::new(entry.name.into(), entry.compression);
+            if let Some(modified) = Local.timestamp_millis_opt(entry.modified).single() {
+                builder = builder.last_modification_date(ZipDateTime::from_chrono(&modified));
+            }
+            let mut entry_writer = zip_writer
+                .write_entry_stream(builder)
+                .await
+                .map_err(io::Error::other)?;
+            match &entry.watermark {
+                Some(watermark) => {
+                    let data = Self::watermark_image(
+                        &entry.path,
+                        entry.size,
+                        &entry.content_type,
+                        watermark,
+                    )
+                    .await
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                    entry_writer.write_all(&data).await?;
+                }
+                None => {
+                    let mut stream = storage_backend.get(&entry.path, None).await?;
+                    while let Some(chunk) = stream.try_next().await? {
+                        entry_writer.write_all(&chunk).await?;
+                    }
+                }
+            }
+            entry_writer.close().await.map_err(io::Error::other)?;
+        }
+        zip_writer.close().await.map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// 读取图片并添加水印，输出格式与原图相同
+    async fn watermark_image(
+        path: &str,
+        size: u64,
+        content_type: &str,
+        watermark: &ArchiveWatermark,
+    ) -> Result<Vec<u8>, SvcError> {
+        let source_data = OssImageSvc::read_source(path, size).await?;
+        let format = OssImageSvc::get_format(&watermark.process, content_type);
+        OssImageSvc::process_data(
+            source_data,
+            watermark.process.clone(),
+            format,
+            watermark.images.clone(),
+        )
+        .await
+    }
+}
+
+/// # 压缩包中的条目
+struct ArchiveEntry {
+    /// 条目名称
+    name: String,
+    /// 对象在存储后端中的路径
+    path: String,
+    /// 压缩方式
+    compression: Compression,
+    /// 最后修改时间戳(毫秒)
+    modified: i64,
+    /// 文件大小
+    size: u64,
+    /// MIME类型
+    content_type: String,
+    /// 要添加的水印，不添加水印时为None
+    watermark: Option<Arc<ArchiveWatermark>>,
+}
+
+/// # 打包时添加的存储桶的水印
+struct ArchiveWatermark {
+    /// 只有水印操作的处理参数
+    process: OssImageProcess,
+    /// 以对象引用ID为键的水印图片
+    images: HashMap<u64, Bytes>,
+}
+
+/// # 打包的压缩包
+pub struct OssFileArchive {
+    /// 压缩包文件名
+    pub file_name: String,
+    /// 压缩包内容，读取时才生成
+    pub body: Body,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_names(names: &[&str]) -> Vec<String> {
+        let mut entry_names = HashSet::new();
+        names
+            .iter()
+            .map(|name| OssArchiveSvc::get_unique_entry_name(name, &mut entry_names))
+            .collect()
+    }
+
+    #[test]
+    fn unique_entry_name_repeated() {
+        assert_eq!(
+            unique_names(&["a.txt", "a.txt", "A.TXT", "b.txt"]),
+            ["a.txt", "a (1).txt", "A (2).txt", "b.txt"]
+        );
+        // 序号在最后一个扩展名前
+        assert_eq!(
+            unique_names(&["a.tar.gz", "a.tar.gz"]),
+            ["a.tar.gz", "a.tar (1).gz"]
+        );
+        // 加上序号后与已有的名称重复时继续增加序号
+        assert_eq!(
+            unique_names(&["a (1).txt", "a.txt", "a.txt"]),
+            ["a (1).txt", "a.txt", "a (2).txt"]
+        );
+    }
+
+    #[test]
+    fn unique_entry_name_without_extension() {
+        assert_eq!(
+            unique_names(&["README", "README", "readme"]),
+            ["README", "README (1)", "readme (2)"]
+        );
+        // 以 `.` 开头的名称整个作为主名
+        assert_eq!(
+            unique_names(&[".gitignore", ".gitignore"]),
+            [".gitignore", ".gitignore (1)"]
+        );
+    }
+
+    #[test]
+    fn unique_entry_name_sanitized() {
+        assert_eq!(
+            unique_names(&["../etc/passwd", "a\\b.txt", "dir/a.txt"]),
+            [".._etc_passwd", "a_b.txt", "dir_a.txt"]
+        );
+        assert_eq!(
+            unique_names(&["", ".", "..", " "]),
+            ["unnamed", "unnamed (1)", "unnamed (2)", "unnamed (3)"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::app::get_app_config;
+use crate::svc::OssAccessor;
+use crate::vo::OssBucketVo;
+use axum::http::HeaderMap;
+use robotech::macros::svc;
+use std::str::FromStr;
+use strum::{Display, EnumString};
+use tracing::warn;
+
+/// # 存储桶的权限
+///
+/// 高级的权限包含低级的权限，即 `admin` > `write` > `read`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OssPermission {
+    /// 下载、预览文件，查看对象引用
+    Read,
+    /// 上传文件，新增、修改、删除对象引用
+    Write,
+    /// 修改、删除(包括级联删除)存储桶，管理存储桶的访问控制
+    Admin,
+}
+
+#[svc]
+pub struct OssBucketAclSvc;
+
+impl OssBucketAclSvc {
+    /// # 是否启用访问控制
+    pub fn is_enabled() -> Result<bool, SvcError> {
+        Ok(get_app_config()?.oss.acl.enabled)
+    }
+
+    /// # 从请求头中解析当前用户的角色
+    ///
+    /// 角色所在的请求头由配置中的 `roles-header` 指定，多个角色用逗号分隔，没有该请求头时返回空列表
+    pub fn get_roles(headers: &HeaderMap) -> Result<Vec<String>, SvcError> {
+        let roles_header = get_app_config()?.oss.acl.roles_header;
+        let roles = headers
+            .get(roles_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|role| !role.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(roles)
+    }
+
+    /// # 判断访问者是否是超级管理员
+    pub fn is_superuser(accessor: &OssAccessor) -> Result<bool, SvcError> {
+        let superuser_role = get_app_config()?.oss.acl.superuser_role;
+        Ok(accessor.roles.contains(&superuser_role))
+    }
+
+    /// # 获取访问者对存储桶的权限
+    ///
+    /// 超级管理员及存储桶的创建者拥有 `admin` 权限，否则取授予该用户及其角色的最高权限，
+    /// 无效的授权记录会被忽略
+    ///
+    /// ## 参数
+    /// * `bucket_vo` - 存储桶
+    /// * `accessor` - 访问者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Option<OssPermission>)` - 返回访问者的权限，没有权限时返回None
+    /// * `Err(SvcError)` - 查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn get_permission<C>(
+        bucket_vo: &OssBucketVo,
+        accessor: &OssAccessor,
+        db: Option<&C>,
+    ) -> Result<Option<OssPermission>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        if Self::is_superuser(accessor)? || accessor.user_id == Some(bucket_vo.creator_id) {
+            return Ok(Some(OssPermission::Admin));
+        }
+        let models = OssBucketAclDao::find_by_bucket_id_and_grantees(
+            bucket_vo.id,
+            accessor.user_id,
+            &accessor.roles,
+            db,
+        )
+        .await?;
+        let permission = models
+            .iter()
+            .filter_map(|model| match OssPermission::from_str(&model.permission) {
+                Ok(permission) => Some(permission),
+                Err(_) => {
+                    warn!("忽略无效的存储桶权限<{}>: {}", model.id, model.permission);
+                    None
+                }
+            })
+            .max();
+        Ok(permission)
+    }
+
+    /// # 判断访问者是否拥有存储桶的权限
+    ///
+    /// ## 参数
+    /// * `bucket_vo` - 存储桶
+    /// * `accessor` - 访问者
+    /// * `permission` - 需要的权限
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否拥有该权限(或更高的权限)
+    /// * `Err(SvcError)` - 查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn has_permission<C>(
+        bucket_vo: &OssBucketVo,
+        accessor: &OssAccessor,
+        permission: OssPermission,
+        db: Option<&C>,
+    ) -> Result<bool, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let granted = Self::get_permission(bucket_vo, accessor, Some(db)).await?;
+        Ok(granted.is_some_and(|granted| granted >= permission))
+    }
+
+    /// # 根据bucket_id删除访问控制记录
+    ///
+    /// ## 参数
+    /// * `bucket_id` - 要删除符合bucket_id为此值的所有记录
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<()>)` - 删除成功
+    /// * `Err(SvcError)` - 删除失败，可能是数据库错误
+    #[db_unwrap(transaction_required)]
+    #[log_call]
+    pub async fn del_by_bucket_id<C>(
+        bucket_id: u64,
+        #[skip_log] db: Option<&C>,
+    ) -> Result<Ro<()>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let query_dto = OssBucketAclQueryDto::builder().bucket_id(bucket_id).build();
+        Self::del_by_query_dto(query_dto, Some(db)).await
+    }
+}
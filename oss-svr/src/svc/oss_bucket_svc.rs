@@ -1,6 +1,36 @@
 use crate::svc::OssObjRefSvc;
 use crate::svc::OssObjSvc;
+use crate::svc::{OssBucketAclSvc, OssPermission};
+use anyhow::anyhow;
 use robotech::macros::svc;
+use std::str::FromStr;
+use strum::{Display, EnumString};
+
+/// # 存储桶的访问策略
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OssBucketAccess {
+    /// 公共读，任何人都可以下载(默认)
+    #[default]
+    PublicRead,
+    /// 私有，只有存储桶的创建者、文件的上传者及(启用访问控制时)拥有 `read` 权限的用户可以下载
+    Private,
+    /// 登录用户可以下载
+    Authenticated,
+}
+
+/// # 访问者
+///
+/// 用于判断是否有权访问存储桶及其中的文件
+#[derive(Debug, Clone, Default)]
+pub struct OssAccessor {
+    /// 当前用户ID，未登录时为None，通过预签名URL访问时为签发者的用户ID
+    pub user_id: Option<u64>,
+    /// 当前用户的角色，通过预签名URL访问时为空
+    pub roles: Vec<String>,
+    /// 是否通过预签名URL访问(签名已校验通过)
+    pub presigned: bool,
+}
 
 #[svc]
 pub struct OssBucketSvc;
@@ -28,8 +58,188 @@ impl OssBucketSvc {
         C: ConnectionTrait,
     {
         OssObjRefSvc::del_by_bucket_id(id, Some(db)).await?;
+        OssBucketAclSvc::del_by_bucket_id(id, Some(db)).await?;
         OssObjSvc::delete_orphaned(Some(db)).await?;
         let ro = Self::del_by_id(id, Some(db)).await?;
         Ok(ro)
     }
+
+    /// # 根据名称获取存储桶
+    ///
+    /// 通过提供的名称从数据库中查询相应的存储桶，如果找到则返回封装了Vo的Ro对象，否则返回对象的extra为None
+    ///
+    /// ## 参数
+    /// * `name` - 存储桶名称
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<Vo>)` - 查询成功，如果记录存在，返回封装了Vo的Ro对象，如果不存在则返回对象的extra为None
+    /// * `Err(SvcError)` - 查询失败，可能是数据库错误
+    #[db_unwrap]
+    #[log_call]
+    pub async fn get_by_name<C>(
+        name: &str,
+        #[skip_log] db: Option<&C>,
+    ) -> Result<Ro<OssBucketVo>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let query_dto = OssBucketQueryDto::builder()
+            .name(name.to_string())
+            .build();
+        Self::get_by_query_dto(query_dto, Some(db)).await
+    }
+
+    /// # 查询访问者可以查看的存储桶列表
+    ///
+    /// 未启用访问控制或访问者为超级管理员时返回全部符合条件的存储桶，
+    /// 否则只返回访问者拥有 `read` 权限的存储桶(包括自己创建的存储桶)
+    ///
+    /// ## 参数
+    /// * `query_dto` - 查询条件
+    /// * `accessor` - 访问者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<Vec<Vo>>)` - 查询成功，返回封装了Vo列表的Ro对象
+    /// * `Err(SvcError)` - 查询失败，可能是数据库错误
+    #[db_unwrap]
+    #[log_call]
+    pub async fn list_readable<C>(
+        query_dto: OssBucketQueryDto,
+        accessor: &OssAccessor,
+        #[skip_log] db: Option<&C>,
+    ) -> Result<Ro<Vec<OssBucketVo>>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let mut ro = Self::list_by_query_dto(query_dto, Some(db)).await?;
+        if !OssBucketAclSvc::is_enabled()? || OssBucketAclSvc::is_superuser(accessor)? {
+            return Ok(ro);
+        }
+        let mut bucket_vos = vec![];
+        for bucket_vo in ro.extra.take().unwrap_or_default() {
+            if OssBucketAclSvc::has_permission(&bucket_vo, accessor, OssPermission::Read, Some(db))
+                .await?
+            {
+                bucket_vos.push(bucket_vo);
+            }
+        }
+        ro.extra = Some(bucket_vos);
+        Ok(ro)
+    }
+
+    /// # 获取存储桶的访问策略
+    ///
+    /// 未设置时为公共读，设置的值无效时返回错误，避免私有的存储桶因配置错误而被公开访问
+    pub fn get_access(bucket_vo: &OssBucketVo) -> Result<OssBucketAccess, SvcError> {
+        match &bucket_vo.access {
+            Some(access) => OssBucketAccess::from_str(access.trim()).map_err(|_| {
+                SvcError::Runtime(anyhow!(
+                    "存储桶<{}>的访问策略无效: {}",
+                    bucket_vo.name,
+                    access
+                ))
+            }),
+            None => Ok(OssBucketAccess::default()),
+        }
+    }
+
+    /// # 判断访问者是否可以下载存储桶中的文件
+    ///
+    /// 文件的上传者可以下载自己上传的文件，其他人按存储桶判断(见 `can_read_bucket`)
+    ///
+    /// ## 参数
+    /// * `bucket_vo` - 存储桶
+    /// * `obj_ref_creator_id` - 文件的上传者(对象引用的创建者)ID
+    /// * `accessor` - 访问者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否可以下载
+    /// * `Err(SvcError)` - 存储桶的访问策略无效或查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn can_read<C>(
+        bucket_vo: &OssBucketVo,
+        obj_ref_creator_id: u64,
+        accessor: &OssAccessor,
+        db: Option<&C>,
+    ) -> Result<bool, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        if accessor.user_id == Some(obj_ref_creator_id) {
+            return Ok(true);
+        }
+        Self::can_read_bucket(bucket_vo, accessor, Some(db)).await
+    }
+
+    /// # 判断访问者是否可以下载存储桶中的所有文件
+    ///
+    /// 按存储桶的访问策略判断，通过预签名URL访问时以签发者作为当前用户判断:
+    /// - 公共读: 任何人都可以下载
+    /// - 登录用户: 有当前用户ID时可以下载
+    /// - 私有: 当前用户是存储桶的创建者时可以下载；启用访问控制时，拥有存储桶 `read` 权限的用户也可以下载
+    ///
+    /// ## 参数
+    /// * `bucket_vo` - 存储桶
+    /// * `accessor` - 访问者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否可以下载
+    /// * `Err(SvcError)` - 存储桶的访问策略无效或查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn can_read_bucket<C>(
+        bucket_vo: &OssBucketVo,
+        accessor: &OssAccessor,
+        db: Option<&C>,
+    ) -> Result<bool, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let can_read = match Self::get_access(bucket_vo)? {
+            OssBucketAccess::PublicRead => true,
+            OssBucketAccess::Authenticated => accessor.user_id.is_some(),
+            OssBucketAccess::Private if OssBucketAclSvc::is_enabled()? => {
+                OssBucketAclSvc::has_permission(bucket_vo, accessor, OssPermission::Read, Some(db))
+                    .await?
+            }
+            OssBucketAccess::Private => accessor.user_id == Some(bucket_vo.creator_id),
+        };
+        Ok(can_read)
+    }
+
+    /// # 判断访问者是否为存储桶中文件的特权用户
+    ///
+    /// 文件的上传者及拥有存储桶 `write` 权限的用户(包括超级管理员及存储桶的创建者)为特权用户，
+    /// 下载时不添加存储桶的默认水印；通过预签名URL访问时按签发者判断
+    ///
+    /// ## 参数
+    /// * `bucket_vo` - 存储桶
+    /// * `obj_ref_creator_id` - 文件的上传者(对象引用的创建者)ID
+    /// * `accessor` - 访问者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否为特权用户
+    /// * `Err(SvcError)` - 查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn is_privileged<C>(
+        bucket_vo: &OssBucketVo,
+        obj_ref_creator_id: u64,
+        accessor: &OssAccessor,
+        db: Option<&C>,
+    ) -> Result<bool, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        if accessor.user_id.is_none() {
+            return Ok(false);
+        }
+        if accessor.user_id == Some(obj_ref_creator_id) {
+            return Ok(true);
+        }
+        OssBucketAclSvc::has_permission(bucket_vo, accessor, OssPermission::Write, Some(db)).await
+    }
 }
@@ -0,0 +1,354 @@
+use crate::app::get_app_config;
+use crate::svc::{OssBucketSvc, OssFileDownload, OssImageSvc, OssMimeSvc};
+use anyhow::anyhow;
+use bytes::Bytes;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, Limits};
+use robotech::svc::SvcError;
+use sea_orm::DatabaseTransaction;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::str::FromStr;
+use strum::{Display, EnumString};
+use tracing::{debug, warn};
+
+/// 应用方向重新编码JPEG时的质量
+const ORIENT_QUALITY: u8 = 95;
+
+/// 可以去除元数据及应用方向的图片格式
+const SUPPORTED_FORMATS: [ImageFormat; 3] =
+    [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+/// PNG中要去除的块: EXIF、文本及修改时间(保留iCCP等颜色相关的块)
+const PNG_STRIPPED_CHUNKS: [&[u8; 4]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+/// WebP中要去除的块
+const WEBP_STRIPPED_CHUNKS: [&[u8; 4]; 2] = [b"EXIF", b"XMP "];
+
+/// # 上传图片时处理EXIF等元数据的策略
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OssExifPolicy {
+    /// 按原样保存(默认)
+    #[default]
+    Keep,
+    /// 按EXIF中的方向旋转图片，需要旋转时重新编码，重新编码后只保留ICC颜色配置
+    Orient,
+    /// 应用方向后去除EXIF、XMP、IPTC、注释等元数据(包括GPS位置)，不需要旋转时无损去除
+    Strip,
+}
+
+/// # EXIF服务
+///
+/// 手机拍摄的照片通常带有GPS位置等EXIF信息，存储桶可设置在上传时去除元数据或应用方向，
+/// 处理在计算hash及写入存储后端之前进行；只处理JPEG、PNG、WebP，其它格式按原样保存
+pub struct OssExifSvc;
+
+impl OssExifSvc {
+    /// # 获取存储桶设置的EXIF策略
+    ///
+    /// 未设置时按原样保存，设置的值无效时返回错误，避免因配置错误而泄露位置信息
+    ///
+    /// ## 参数
+    /// * `exif_policy` - 存储桶中设置的策略
+    ///
+    /// ## 返回值
+    /// * `Ok(OssExifPolicy)` - 返回策略
+    /// * `Err(SvcError)` - 存储桶中设置的策略无效，返回错误信息
+    pub fn get_policy(exif_policy: &Option<String>) -> Result<OssExifPolicy, SvcError> {
+        match exif_policy {
+            Some(exif_policy) => OssExifPolicy::from_str(exif_policy.trim())
+                .map_err(|_| SvcError::Runtime(anyhow!("无效的EXIF策略: {}", exif_policy))),
+            None => Ok(OssExifPolicy::default()),
+        }
+    }
+
+    /// # 根据存储桶ID获取EXIF策略
+    ///
+    /// 用于只记录了存储桶ID的分片上传及tus上传
+    pub async fn get_bucket_policy(bucket_id: u64) -> Result<OssExifPolicy, SvcError> {
+        let bucket_vo = OssBucketSvc::get_by_id::<DatabaseTransaction>(bucket_id, None)
+            .await?
+            .extra
+            .ok_or(SvcError::NotFound(format!("bucket id: {}", bucket_id)))?;
+        Self::get_policy(&bucket_vo.exif_policy)
+    }
+
+    /// # 判断该类型的文件在上传时是否会被处理
+    ///
+    /// 设置了去除元数据或应用方向的存储桶不能秒传这些类型的文件，避免引用未处理的对象
+    pub fn is_affected(exif_policy: OssExifPolicy, content_type: &Option<String>) -> bool {
+        exif_policy != OssExifPolicy::Keep
+            && content_type.as_deref().is_some_and(|content_type| {
+                ImageFormat::from_mime_type(OssMimeSvc::get_essence(content_type))
+                    .is_some_and(|format| SUPPORTED_FORMATS.contains(&format))
+            })
+    }
+
+    /// # 根据文件头部判断是否为可处理的图片格式
+    ///
+    /// 上传时只有可处理的图片才需要接收到内存中处理，其它文件直接写入存储后端
+    pub fn is_processable(head: &[u8]) -> bool {
+        image::guess_format(head).is_ok_and(|format| SUPPORTED_FORMATS.contains(&format))
+    }
+
+    /// # 按策略处理上传的图片
+    ///
+    /// 解码及编码都比较耗时，在阻塞线程中执行；不是支持的格式或无法解析时按原样保存
+    ///
+    /// ## 参数
+    /// * `exif_policy` - 存储桶的EXIF策略
+    /// * `data` - 上传的文件内容
+    ///
+    /// ## 返回值
+    /// * `Ok(Some(Vec<u8>))` - 返回处理后的内容
+    /// * `Ok(None)` - 不需要处理(不是支持的格式、没有元数据或方向不需要旋转)
+    /// * `Err(SvcError)` - 处理任务异常，返回错误信息
+    pub async fn process(
+        exif_policy: OssExifPolicy,
+        data: Bytes,
+    ) -> Result<Option<Vec<u8>>, SvcError> {
+        if exif_policy == OssExifPolicy::Keep {
+            return Ok(None);
+        }
+        let max_side = get_app_config()?.oss.image.max_side;
+        tokio::task::spawn_blocking(move || Self::process_image(exif_policy, &data, max_side))
+            .await
+            .map_err(|e| SvcError::Runtime(anyhow!("EXIF处理任务异常: {}", e)))
+    }
+
+    /// # 读取文件的EXIF信息
+    ///
+    /// 只返回主图像的字段(不包括内嵌缩略图的字段及厂商私有的MakerNote)，字段值为带单位的可读文本
+    ///
+    /// ## 参数
+    /// * `download` - 要读取的文件
+    ///
+    /// ## 返回值
+    /// * `Ok(BTreeMap<String, String>)` - 返回字段名及字段值，没有EXIF信息时为空
+    /// * `Err(SvcError)` - 文件超过可处理的最大大小、读取失败或EXIF格式错误，返回错误信息
+    pub async fn read(download: &OssFileDownload) -> Result<BTreeMap<String, String>, SvcError> {
+        let data = OssImageSvc::read_source(&download.path, download.file_size).await?;
+        let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(data)) {
+            Ok(exif) => exif,
+            Err(exif::Error::NotFound(_)) | Err(exif::Error::NotSupported(_)) => {
+                return Ok(BTreeMap::new());
+            }
+            Err(e) => return Err(SvcError::Runtime(anyhow!("读取EXIF失败: {}", e))),
+        };
+        let fields = exif
+            .fields()
+            .filter(|field| field.ifd_num == exif::In::PRIMARY && field.tag != exif::Tag::MakerNote)
+            .map(|field| {
+                (
+                    field.tag.to_string(),
+                    field.display_value().with_unit(&exif).to_string(),
+                )
+            })
+            .collect();
+        Ok(fields)
+    }
+
+    /// 处理图片，先应用方向，不需要旋转(或无法解码)时再按策略无损去除元数据
+    fn process_image(exif_policy: OssExifPolicy, data: &[u8], max_side: u32) -> Option<Vec<u8>> {
+        let format = image::guess_format(data)
+            .ok()
+            .filter(|format| SUPPORTED_FORMATS.contains(format))?;
+        match Self::orient(data, format, max_side) {
+            Ok(Some(oriented)) => return Some(oriented),
+            Ok(None) => {}
+            Err(e) => warn!("应用图片的方向失败: {}", e),
+        }
+        if exif_policy != OssExifPolicy::Strip {
+            return None;
+        }
+        let stripped = match format {
+            ImageFormat::Jpeg => Self::strip_jpeg(data),
+            ImageFormat::Png => Self::strip_png(data),
+            _ => Self::strip_webp(data),
+        };
+        match stripped {
+            Some(stripped) if stripped.len() == data.len() => None,
+            Some(stripped) => {
+                debug!("去除元数据: {} -> {}", data.len(), stripped.len());
+                Some(stripped)
+            }
+            None => {
+                warn!("无法解析图片的结构，按原样保存");
+                None
+            }
+        }
+    }
+
+    /// 按EXIF中的方向旋转图片并重新编码，不需要旋转时返回None
+    fn orient(
+        data: &[u8],
+        format: ImageFormat,
+        max_side: u32,
+    ) -> Result<Option<Vec<u8>>, SvcError> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(max_side);
+        limits.max_image_height = Some(max_side);
+        let mut reader = ImageReader::new(Cursor::new(data));
+        reader.set_format(format);
+        reader.limits(limits);
+        let mut decoder = reader
+            .into_decoder()
+            .map_err(|e| SvcError::Runtime(anyhow!("解码图片失败: {}", e)))?;
+        let orientation = decoder
+            .orientation()
+            .map_err(|e| SvcError::Runtime(anyhow!("读取图片的方向失败: {}", e)))?;
+        if orientation == Orientation::NoTransforms {
+            return Ok(None);
+        }
+        let icc_profile = decoder.icc_profile().ok().flatten();
+        let mut image = DynamicImage::from_decoder(decoder)
+            .map_err(|e| SvcError::Runtime(anyhow!("解码图片失败: {}", e)))?;
+        image.apply_orientation(orientation);
+        debug!("应用图片的方向: {:?}", orientation);
+
+        let mut oriented = Vec::new();
+        let result = match format {
+            ImageFormat::Jpeg => {
+                let mut encoder = JpegEncoder::new_with_quality(&mut oriented, ORIENT_QUALITY);
+                Self::set_icc_profile(&mut encoder, icc_profile);
+                // JPEG不支持透明通道
+                DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)
+            }
+            ImageFormat::Png => {
+                let mut encoder = PngEncoder::new(&mut oriented);
+                Self::set_icc_profile(&mut encoder, icc_profile);
+                image.write_with_encoder(encoder)
+            }
+            _ => {
+                let mut encoder = WebPEncoder::new_lossless(&mut oriented);
+                Self::set_icc_profile(&mut encoder, icc_profile);
+                DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder)
+            }
+        };
+        result.map_err(|e| SvcError::Runtime(anyhow!("编码图片失败: {}", e)))?;
+        Ok(Some(oriented))
+    }
+
+    /// 重新编码时保留原图的ICC颜色配置，编码器不支持时忽略
+    fn set_icc_profile(encoder: &mut impl ImageEncoder, icc_profile: Option<Vec<u8>>) {
+        if let Some(icc_profile) = icc_profile
+            && let Err(e) = encoder.set_icc_profile(icc_profile)
+        {
+            debug!("忽略ICC颜色配置: {}", e);
+        }
+    }
+
+    /// 无损去除JPEG中的元数据段，图像数据(SOS之后)原样保留，结构错误时返回None
+    fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        let mut stripped = Vec::with_capacity(data.len());
+        stripped.extend_from_slice(&data[..2]);
+        let mut pos = 2;
+        loop {
+            if *data.get(pos)? != 0xFF {
+                return None;
+            }
+            // 标记前可以有多个填充的0xFF
+            while *data.get(pos + 1)? == 0xFF {
+                pos += 1;
+            }
+            let marker = data[pos + 1];
+            match marker {
+                // SOS之后是图像数据，EOI为结束
+                0xDA | 0xD9 => {
+                    stripped.extend_from_slice(&data[pos..]);
+                    return Some(stripped);
+                }
+                // 没有长度的标记
+                0x01 | 0xD0..=0xD7 => {
+                    stripped.extend_from_slice(&data[pos..pos + 2]);
+                    pos += 2;
+                }
+                _ => {
+                    let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]);
+                    let end = pos + 2 + length as usize;
+                    if length < 2 || end > data.len() {
+                        return None;
+                    }
+                    // 去除APP1(EXIF、XMP)、APP13(IPTC)、COM(注释)，以及除APP14(Adobe颜色变换)外的APP3~APP15，
+                    // 保留APP0(JFIF)及APP2(ICC颜色配置)
+                    if !matches!(marker, 0xE1 | 0xE3..=0xED | 0xEF | 0xFE) {
+                        stripped.extend_from_slice(&data[pos..end]);
+                    }
+                    pos = end;
+                }
+            }
+        }
+    }
+
+    /// 无损去除PNG中的元数据块，结构错误时返回None
+    fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+        const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        if !data.starts_with(&SIGNATURE) {
+            return None;
+        }
+        let mut stripped = Vec::with_capacity(data.len());
+        stripped.extend_from_slice(&SIGNATURE);
+        let mut pos = SIGNATURE.len();
+        while pos < data.len() {
+            let length = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+            let chunk_type = data.get(pos + 4..pos + 8)?;
+            // 长度、类型、数据及CRC
+            let end = pos + 12 + length;
+            if end > data.len() {
+                return None;
+            }
+            if !PNG_STRIPPED_CHUNKS
+                .iter()
+                .any(|stripped_chunk| chunk_type == *stripped_chunk)
+            {
+                stripped.extend_from_slice(&data[pos..end]);
+            }
+            pos = end;
+            if chunk_type == b"IEND" {
+                break;
+            }
+        }
+        Some(stripped)
+    }
+
+    /// 无损去除WebP中的EXIF及XMP块，同时清除VP8X中的对应标志并修正RIFF的大小，结构错误时返回None
+    fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+            return None;
+        }
+        let mut stripped = Vec::with_capacity(data.len());
+        stripped.extend_from_slice(&data[..12]);
+        let mut pos = 12;
+        while pos + 8 <= data.len() {
+            let fourcc = &data[pos..pos + 4];
+            let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+            // 块的数据按偶数字节对齐
+            let end = pos + 8 + size + size % 2;
+            if end > data.len() {
+                return None;
+            }
+            if fourcc == b"VP8X" && size >= 1 {
+                let flags_pos = stripped.len() + 8;
+                stripped.extend_from_slice(&data[pos..end]);
+                // 清除EXIF(0x08)及XMP(0x04)标志
+                stripped[flags_pos] &= !(0x08 | 0x04);
+            } else if !WEBP_STRIPPED_CHUNKS
+                .iter()
+                .any(|stripped_chunk| fourcc == *stripped_chunk)
+            {
+                stripped.extend_from_slice(&data[pos..end]);
+            }
+            pos = end;
+        }
+        let riff_size = u32::try_from(stripped.len() - 8).ok()?;
+        stripped[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        Some(stripped)
+    }
+}
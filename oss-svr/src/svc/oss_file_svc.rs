@@ -1,49 +1,58 @@
-use crate::app::{AppConfig, OssConfig, get_app_config};
+use crate::app::{AppConfig, MimeMismatchPolicy, OssConfig, get_app_config};
 use crate::dao::OssObjRefDao;
-use crate::dto::oss_bucket_dto::OssBucketQueryDto;
+use crate::dto::oss_file_dto::OssFileInstantDto;
 use crate::dto::oss_obj_dto::{OssObjAddDto, OssObjModifyDto};
-use crate::dto::oss_obj_ref_dto::{OssObjRefAddDto, OssObjRefModifyDto};
-use crate::svc::OssBucketSvc;
+use crate::dto::oss_obj_ref_dto::OssObjRefAddDto;
+use crate::svc::{OssAccessor, OssBucketSvc, OssImageSvc, OssThumbnailSvc, OssThumbnailTask};
+use crate::svc::{OssExifPolicy, OssExifSvc};
+use crate::svc::{OCTET_STREAM, OssMimeSvc, SNIFF_SIZE};
 use crate::svc::OssObjRefSvc;
 use crate::svc::OssObjSvc;
-use crate::vo::OssObjRefVo;
+use crate::vo::{OssBucketVo, OssObjRefVo};
+use crate::storage::get_storage_backend;
 use anyhow::anyhow;
 use axum::body::Body;
 use axum::extract::Multipart;
-use axum::extract::multipart::Field;
 use axum::http::{HeaderMap, HeaderValue, header};
-use chrono::{Local, TimeZone};
+use bytes::Bytes;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use futures_util::{Stream, StreamExt, TryStreamExt, future, stream};
 use idworker::get_id_worker;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, info, warn};
 use robotech::dao::begin_transaction;
 use robotech::env::{APP_ENV, EnvError};
 use robotech::ro::Ro;
 use robotech::svc::SvcError;
 use robotech::macros::db_unwrap;
-use sea_orm::ConnectionTrait;
+use sea_orm::{ConnectionTrait, DatabaseTransaction};
 use sha2::Digest;
-use std::io::SeekFrom;
-use tokio::fs;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-use tokio_util::io::ReaderStream;
+use std::io;
+use std::path::PathBuf;
 use wheel_rs::file_utils::get_file_ext;
 use wheel_rs::time_utils::now_ts;
 
+/// 一个Range请求中允许的最大范围数，避免请求大量细碎的范围
+const MAX_RANGES: usize = 64;
+
 pub struct OssFileSvc;
 
 impl OssFileSvc {
     /// # 上传文件到指定的存储桶中
     ///
-    /// 该函数负责将临时文件上传到系统中，并根据文件的哈希值和大小判断是否已存在相同文件。
-    /// 如果文件已存在，则直接创建引用关系；否则将文件移动到指定位置并创建新的对象记录。
+    /// 该函数负责将上传的文件写入存储后端，并根据文件的哈希值和大小判断是否已存在相同文件。
+    /// 如果文件已存在，则直接创建引用关系；否则写入文件并创建新的对象记录。
+    ///
+    /// 文件的哈希值和大小可以通过请求头提供，也可以通过表单字段提供，表单字段不限制顺序:
+    /// - 在`file`字段之前提供时，可以用于秒传判断，并在接收过程中校验
+    /// - 在`file`字段之后提供时，接收完文件后再校验，不一致时删除已写入的文件
     ///
     /// ## 参数
     /// * `bucket` - 存储桶名称
-    /// * `file_name` - 原始文件名
-    /// * `file_size` - 文件大小（字节）
-    /// * `hash` - 文件哈希值
-    /// * `temp_file` - 包含文件内容的临时文件
+    /// * `hash_provided` - 请求头中提供的文件哈希值
+    /// * `file_size_provided` - 请求头中提供的文件大小
+    /// * `multipart` - 上传的表单数据
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
     ///
     /// ## 返回值
     /// * `Ok(Ro<OssObjRefVo>)` - 上传成功，返回包含文件引用信息的结果对象
@@ -51,10 +60,21 @@ impl OssFileSvc {
     ///
     /// ## 错误处理
     /// * 如果存储桶不存在，返回警告信息
+    /// * 如果多处提供的哈希值或文件大小不一致，返回验证错误
     /// * 如果文件操作或数据库操作失败，返回相应错误
-    #[db_unwrap(transaction_required)]
+    ///
+    /// ## 事务
+    /// 接收文件可能持续很长时间，不能在整个上传过程中占用一个事务，因此分为几个短事务:
+    /// 1. 预留一个未完成(`is_completed = false`)的对象
+    /// 2. 在事务之外接收文件并写入存储后端
+    /// 3. 在一个事务中完成对象(去重)并新增对象引用
+    ///
+    /// 接收失败时删除预留的对象，删除失败而遗留的未完成对象可根据 `is_completed` 清理
+    #[db_unwrap]
     pub async fn upload<C>(
         bucket: &str,
+        mut hash_provided: Option<String>,
+        mut file_size_provided: Option<u64>,
         mut multipart: Multipart,
         current_user_id: u64,
         db: Option<&C>,
@@ -63,239 +83,795 @@ impl OssFileSvc {
         C: ConnectionTrait,
     {
         // 获取存储桶
-        let one_bucket = OssBucketSvc::get_by_query_dto(
-            OssBucketQueryDto::builder()
-                .name(bucket.to_string())
-                .build(),
-            Some(db),
-        )
-        .await?;
-        let one_bucket = match one_bucket.extra {
+        let one_bucket = match OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra {
             Some(bucket) => bucket,
             None => return Ok(Ro::warn(format!("未找到存储桶<{}>", bucket))),
         };
 
-        let mut hash_provided = None;
-        let mut file_size_provided = None;
-        // XXX 注意: 前端上传文件时，file参数必须放在最后
+        // 接收到的文件，接收完所有字段后再完成对象和新增对象引用
+        let mut received_file = None;
+        if let Err(e) = Self::receive_fields(
+            &one_bucket,
+            &mut hash_provided,
+            &mut file_size_provided,
+            &mut multipart,
+            &mut received_file,
+            current_user_id,
+            db,
+        )
+        .await
+        {
+            // 接收文件之后的字段出错时，删除已写入的文件及预留的对象
+            if let Some(ReceivedFile::Written { obj_id, .. }) = &received_file {
+                Self::discard_obj(*obj_id).await;
+            }
+            return Err(e);
+        }
+
+        let received_file = match received_file {
+            Some(received_file) => received_file,
+            None => Err(validator::ValidationError::new("上传文件为空"))?,
+        };
+        // 校验在文件之后提供的文件大小和hash(按接收到的原始内容校验)
+        // 秒传的对象由文件之前提供的hash和size查出，之后提供的值已在合并时校验过是否一致
+        if let ReceivedFile::Written {
+            obj_id, received, ..
+        } = &received_file
+            && let Err(e) = Self::check_provided(
+                &hash_provided,
+                &file_size_provided,
+                &received.original_hash,
+                received.original_size,
+            )
+        {
+            Self::discard_obj(*obj_id).await;
+            return Err(e);
+        }
+        let obj_ref_ro =
+            Self::finish_received_file(one_bucket.id, received_file, current_user_id, db).await?;
+        Ok(obj_ref_ro.msg("上传成功".to_string()))
+    }
+
+    /// # 接收上传表单的所有字段
+    ///
+    /// 合并表单字段中提供的文件hash和大小，并接收`file`字段的文件
+    async fn receive_fields<C>(
+        one_bucket: &OssBucketVo,
+        hash_provided: &mut Option<String>,
+        file_size_provided: &mut Option<u64>,
+        multipart: &mut Multipart,
+        received_file: &mut Option<ReceivedFile>,
+        current_user_id: u64,
+        db: &C,
+    ) -> Result<(), SvcError>
+    where
+        C: ConnectionTrait,
+    {
         while let Some(field) = multipart.next_field().await? {
             match field.name() {
                 Some("hash") => {
-                    hash_provided = Some(field.text().await?);
+                    let hash = field.text().await?;
+                    Self::merge_provided(
+                        hash_provided,
+                        hash.to_lowercase(),
+                        "提供的文件hash不一致",
+                    )?;
                 }
                 Some("size") => {
-                    file_size_provided = Some(
-                        field
-                            .text()
-                            .await?
-                            .parse::<u64>()
-                            .map_err(|_| validator::ValidationError::new("文件大小格式错误"))?,
-                    );
+                    let file_size = field
+                        .text()
+                        .await?
+                        .parse::<u64>()
+                        .map_err(|_| validator::ValidationError::new("文件大小格式错误"))?;
+                    Self::merge_provided(
+                        file_size_provided,
+                        file_size,
+                        "提供的文件大小不一致",
+                    )?;
                 }
                 Some("file") => {
+                    if received_file.is_some() {
+                        Err(validator::ValidationError::new("只能上传一个文件"))?;
+                    }
                     let file_name = field
                         .file_name()
-                        .ok_or_else(|| validator::ValidationError::new("上传文件没有文件名"))?;
-
-                    // 根据hash和size判断，如果对象已存在，则直接返回对象信息
-                    let obj_vo = if let (Some(hash_provided), Some(file_size_provided)) =
-                        (&hash_provided, &file_size_provided)
-                    {
-                        OssObjSvc::get_by_hash_and_size(
-                            &hash_provided,
-                            &file_size_provided,
-                            Some(db),
-                        )
-                        .await?
-                        .extra
-                    } else {
-                        None
-                    };
-
-                    let now = now_ts()?;
-                    let ext = get_file_ext(file_name);
-
-                    // 判断对象是否存在
-                    let (obj_exists, obj_id, new_file_path) = if let Some(obj_vo) = obj_vo {
-                        info!("对象已存在，直接返回对象信息");
-                        // 如果已经上传过该文件，则直接返回之前的对象ID和存放路径
-                        (true, obj_vo.id, obj_vo.path)
-                    } else {
-                        // 如果未上传过该文件，则新增对象，并返回新对象ID和新文件的存放路径
-                        let obj_id = get_id_worker()?.next_id()?;
-                        // 根据当前时间，创建yyyy/MM/dd/HH的目录，并将文件存入此目录中
-                        let datetime = Local.timestamp_opt((now / 1000) as i64, 0).unwrap();
-                        let oss_config = get_app_config()?.oss;
-
-                        let date_path = datetime.format(&oss_config.file_dir_format).to_string();
-
-                        let storage_dir = APP_ENV
-                            .get()
-                            .ok_or(EnvError::GetAppEnv())?
-                            .app_dir
-                            .join(&oss_config.file_root_dir)
-                            .join(bucket.to_string())
-                            .join(&date_path);
-                        fs::create_dir_all(&storage_dir).await?;
-                        let new_file_path = storage_dir
-                            .join(obj_id.to_string())
-                            .as_path()
-                            .to_str()
-                            .ok_or_else(|| {
-                                std::io::Error::new(std::io::ErrorKind::Other, "Invalid file path")
-                            })?
-                            .to_string();
-
-                        // 新增对象
-                        let is_completed = false;
-                        let oss_obj_add_dto = OssObjAddDto::builder()
-                            .id(obj_id)
-                            .path(new_file_path.to_string())
-                            .is_completed(is_completed)
-                            ._current_user_id(current_user_id)
-                            .build();
-
-                        debug!("新增对象: {:?}", oss_obj_add_dto);
-                        let add_ro = OssObjSvc::add(oss_obj_add_dto, Some(db)).await?;
-                        if let Some(obj_vo) = add_ro.extra {
-                            (false, obj_vo.id, new_file_path)
-                        } else {
-                            return Err(SvcError::Runtime(anyhow!("新增对象失败")));
-                        }
-                    };
+                        .ok_or_else(|| validator::ValidationError::new("上传文件没有文件名"))?
+                        .to_string();
 
-                    // 新增对象引用
-                    let obj_ref_id = get_id_worker()?.next_id()?;
-                    let (obj_ref_name, preview_url) = if Self::is_previewable(&ext)
-                        && let Some(ext) = &ext
-                    {
-                        let obj_ref_name = format!("{}.{}", obj_ref_id, ext);
-                        let preview_url = Some(format!("/oss/file/preview/{}", obj_ref_name));
-                        (obj_ref_name, preview_url)
-                    } else {
-                        let obj_ref_name = obj_ref_id.to_string();
-                        (obj_ref_name, None)
-                    };
-                    let download_url = format!("/oss/file/download/{}", obj_ref_name);
-                    let oss_obj_ref_add_dto = OssObjRefAddDto::builder()
-                        .id(obj_ref_id)
-                        .name(file_name.to_string())
-                        .bucket_id(one_bucket.id)
-                        .obj_id(obj_id)
-                        .ext(ext)
-                        .download_url(download_url)
-                        .preview_url(preview_url)
-                        ._current_user_id(current_user_id)
-                        .build();
-                    debug!("新增对象引用: {:?}", oss_obj_ref_add_dto);
-                    let obj_ref_ro = OssObjRefSvc::add(oss_obj_ref_add_dto, Some(db)).await?;
-
-                    if !obj_exists {
-                        let (file_size_computed, hash_computed) = Self::receive_and_write(
-                            &hash_provided,
-                            &file_size_provided,
+                    *received_file = Some(
+                        Self::receive_file(
+                            one_bucket,
+                            file_name,
+                            hash_provided,
+                            file_size_provided,
                             field,
-                            &new_file_path,
+                            current_user_id,
+                            db,
                         )
-                        .await?;
+                        .await?,
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 
-                        // 写完文件时最后再检查一次文件大小和hash是否已经存在
-                        let oss_obj_vo = OssObjSvc::get_by_hash_and_size(
-                            &hash_computed,
-                            &file_size_computed,
-                            Some(db),
-                        )
-                        .await?
-                        .extra;
-                        if let Some(oss_obj_vo) = oss_obj_vo {
-                            warn!(
-                                "在上传完成后发现文件已存在，删除上传文件，引用的对象指向已存在的对象"
-                            );
-                            fs::remove_file(new_file_path).await?;
-                            OssObjRefSvc::modify(
-                                OssObjRefModifyDto::builder()
-                                    .id(obj_ref_id)
-                                    .obj_id(oss_obj_vo.id)
-                                    ._current_user_id(current_user_id)
-                                    .build(),
-                                Some(db),
-                            )
-                            .await?;
-                        } else {
-                            // 文件已上传完成，修改对象信息的hash、文件大小、是否完成
-                            let is_completed = true;
-                            OssObjSvc::modify(
-                                OssObjModifyDto::builder()
-                                    .id(obj_id)
-                                    .hash(Some(hash_computed))
-                                    .size(Some(file_size_computed))
-                                    .is_completed(is_completed)
-                                    ._current_user_id(current_user_id)
-                                    .build(),
-                                Some(db),
-                            )
-                            .await?;
+    /// # 接收上传的文件
+    ///
+    /// 根据提供的hash和size判断对象是否已存在，已存在时不再接收文件内容，
+    /// 否则新增未完成的对象，并将文件内容写入存储后端；
+    /// 已存在的对象在上传时会按存储桶的EXIF策略处理时，不能直接引用，需要接收文件内容
+    async fn receive_file<C, S, E>(
+        one_bucket: &OssBucketVo,
+        file_name: String,
+        hash_provided: &Option<String>,
+        file_size_provided: &Option<u64>,
+        stream: S,
+        current_user_id: u64,
+        db: &C,
+    ) -> Result<ReceivedFile, SvcError>
+    where
+        C: ConnectionTrait,
+        S: Stream<Item = Result<Bytes, E>> + Send,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let exif_policy = OssExifSvc::get_policy(&one_bucket.exif_policy)?;
+        // 根据hash和size判断，如果对象已存在，则直接返回对象信息
+        let obj_vo = if let (Some(hash_provided), Some(file_size_provided)) =
+            (hash_provided, file_size_provided)
+        {
+            OssObjSvc::get_by_hash_and_size(hash_provided, file_size_provided, Some(db))
+                .await?
+                .extra
+                .filter(|obj_vo| !OssExifSvc::is_affected(exif_policy, &obj_vo.content_type))
+        } else {
+            None
+        };
+
+        // 判断对象是否存在
+        if let Some(obj_vo) = obj_vo {
+            info!("对象已存在，直接返回对象信息");
+            // 如果已经上传过该文件，则直接引用之前的对象
+            return Ok(ReceivedFile::Existing {
+                file_name,
+                obj_id: obj_vo.id,
+            });
+        }
+
+        // 如果未上传过该文件，则新增对象，接收完文件后再完成对象
+        let (obj_id, new_file_path) =
+            Self::add_incomplete_obj(&one_bucket.name, current_user_id, Some(db)).await?;
+        let received = match Self::receive_and_write(
+            hash_provided,
+            file_size_provided,
+            stream,
+            &new_file_path,
+            exif_policy,
+        )
+        .await
+        {
+            Ok(received) => received,
+            Err(e) => {
+                Self::discard_obj(obj_id).await;
+                return Err(e);
+            }
+        };
+        Ok(ReceivedFile::Written {
+            file_name,
+            obj_id,
+            path: new_file_path,
+            received,
+        })
+    }
+
+    /// # 完成接收到的文件
+    ///
+    /// 已存在的对象直接新增对象引用；新写入的文件在一个短事务中完成对象(可能因去重而引用已存在的对象)并新增对象引用，
+    /// 完成失败时删除已写入的文件及预留的对象；新增对象引用提交后再在后台生成缩略图
+    async fn finish_received_file<C>(
+        bucket_id: u64,
+        received_file: ReceivedFile,
+        current_user_id: u64,
+        db: &C,
+    ) -> Result<Ro<OssObjRefVo>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let (obj_ref_ro, thumbnail_task) = match received_file {
+            ReceivedFile::Existing { file_name, obj_id } => {
+                Self::add_obj_ref(bucket_id, obj_id, &file_name, current_user_id, Some(db)).await?
+            }
+            ReceivedFile::Written {
+                file_name,
+                obj_id,
+                path,
+                received,
+            } => {
+                let result = Self::finish_upload::<DatabaseTransaction>(
+                    bucket_id,
+                    obj_id,
+                    &path,
+                    received,
+                    &file_name,
+                    current_user_id,
+                    None,
+                )
+                .await;
+                if result.is_err() {
+                    Self::discard_obj(obj_id).await;
+                }
+                result?
+            }
+        };
+        OssThumbnailSvc::spawn_generate(thumbnail_task);
+        Ok(obj_ref_ro)
+    }
+
+    /// # 丢弃预留的对象
+    ///
+    /// 接收或完成失败时删除已写入的文件及预留的未完成对象，删除失败时只记录日志，
+    /// 遗留的未完成对象可根据 `is_completed` 清理
+    async fn discard_obj(obj_id: u64) {
+        if let Err(e) = OssObjSvc::del_with_file::<DatabaseTransaction>(obj_id, None).await {
+            warn!("删除未完成的对象<{}>失败，留待清理: {}", obj_id, e);
+        }
+    }
+
+    /// # 合并多处提供的值
+    ///
+    /// 文件的hash和大小可能同时在请求头和表单字段中提供，如果不一致则返回验证错误
+    fn merge_provided<T: PartialEq>(
+        provided: &mut Option<T>,
+        value: T,
+        mismatch_message: &'static str,
+    ) -> Result<(), SvcError> {
+        match provided {
+            Some(provided) if *provided != value => {
+                Err(validator::ValidationError::new(mismatch_message))?
+            }
+            Some(_) => Ok(()),
+            None => {
+                *provided = Some(value);
+                Ok(())
+            }
+        }
+    }
+
+    /// # 校验提供的文件大小和hash
+    ///
+    /// 用于校验接收完文件后才提供的文件大小和hash
+    fn check_provided(
+        hash_provided: &Option<String>,
+        file_size_provided: &Option<u64>,
+        hash_computed: &str,
+        file_size_computed: u64,
+    ) -> Result<(), SvcError> {
+        if let Some(file_size_provided) = file_size_provided
+            && *file_size_provided != file_size_computed
+        {
+            return Err(SvcError::Runtime(anyhow!(
+                "上传文件大小错误，请重新上传: {file_size_provided}->{file_size_computed}"
+            )));
+        }
+        if let Some(hash_provided) = hash_provided
+            && hash_provided != hash_computed
+        {
+            return Err(SvcError::Runtime(anyhow!(
+                "上传文件内容校验错误，请重新上传: {hash_provided}->{hash_computed}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// # 以原始请求体上传文件到指定的存储桶中
+    ///
+    /// 与 `upload` 相同，但文件内容为原始字节流，不需要multipart编码，
+    /// 文件名由调用方提供，文件的hash和大小只能预先提供
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `file_name` - 原始文件名
+    /// * `hash_provided` - 提供的文件哈希值
+    /// * `file_size_provided` - 提供的文件大小
+    /// * `stream` - 文件内容的字节流
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssObjRefVo>)` - 上传成功，返回包含文件引用信息的结果对象
+    /// * `Err(SvcError)` - 上传失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果存储桶不存在，返回警告信息
+    /// * 如果文件名为空，返回验证错误
+    /// * 如果文件操作或数据库操作失败，返回相应错误
+    ///
+    /// ## 事务
+    /// 与 `upload` 相同，接收文件时不占用事务
+    #[db_unwrap]
+    pub async fn upload_raw<C, S, E>(
+        bucket: &str,
+        file_name: &str,
+        hash_provided: Option<String>,
+        file_size_provided: Option<u64>,
+        stream: S,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<OssObjRefVo>, SvcError>
+    where
+        C: ConnectionTrait,
+        S: Stream<Item = Result<Bytes, E>> + Send,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        if file_name.trim().is_empty() {
+            Err(validator::ValidationError::new("上传文件没有文件名"))?;
+        }
+
+        // 获取存储桶
+        let one_bucket = match OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra {
+            Some(bucket) => bucket,
+            None => return Ok(Ro::warn(format!("未找到存储桶<{}>", bucket))),
+        };
+
+        let received_file = Self::receive_file(
+            &one_bucket,
+            file_name.to_string(),
+            &hash_provided,
+            &file_size_provided,
+            stream,
+            current_user_id,
+            db,
+        )
+        .await?;
+        let obj_ref_ro =
+            Self::finish_received_file(one_bucket.id, received_file, current_user_id, db).await?;
+        Ok(obj_ref_ro.msg("上传成功".to_string()))
+    }
+
+    /// # 批量上传文件到指定的存储桶中
+    ///
+    /// 一次请求上传多个文件，每个`file`字段之前的`hash`、`size`字段为该文件的哈希值和大小。
+    /// 每个文件单独完成，某个文件上传失败不影响其它文件
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `multipart` - 上传的表单数据
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<Vec<Ro<OssObjRefVo>>>)` - 按上传顺序返回每个文件的上传结果，有文件上传失败时返回警告
+    /// * `Err(SvcError)` - 读取表单数据失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果存储桶不存在，返回警告信息
+    /// * 如果单个文件上传失败，该文件的上传结果为警告信息
+    #[db_unwrap]
+    pub async fn upload_batch<C>(
+        bucket: &str,
+        mut multipart: Multipart,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<Vec<Ro<OssObjRefVo>>>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        // 获取存储桶
+        if OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra.is_none() {
+            return Ok(Ro::warn(format!("未找到存储桶<{}>", bucket)));
+        }
+
+        let mut results = vec![];
+        let mut hash_provided = None;
+        let mut file_size_provided = None;
+        while let Some(field) = multipart.next_field().await? {
+            match field.name() {
+                Some("hash") => {
+                    hash_provided = Some(field.text().await?.to_lowercase());
+                }
+                Some("size") => {
+                    file_size_provided = Some(field.text().await?);
+                }
+                Some("file") => {
+                    // 哈希值和文件大小只对紧随其后的文件有效
+                    let hash_provided = hash_provided.take();
+                    let file_size_provided = match file_size_provided.take() {
+                        Some(file_size) => match file_size.parse::<u64>() {
+                            Ok(file_size) => Some(file_size),
+                            Err(_) => {
+                                results.push(Ro::warn(format!("文件大小格式错误: {}", file_size)));
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+                    let file_name = match field.file_name() {
+                        Some(file_name) => file_name.to_string(),
+                        None => {
+                            results.push(Ro::warn("上传文件没有文件名".to_string()));
+                            continue;
                         }
-                    }
-                    return Ok(obj_ref_ro.msg("上传成功".to_string()));
+                    };
+                    let result = Self::upload_raw(
+                        bucket,
+                        &file_name,
+                        hash_provided,
+                        file_size_provided,
+                        field,
+                        current_user_id,
+                        Some(db),
+                    )
+                    .await;
+                    results.push(match result {
+                        Ok(obj_ref_ro) => obj_ref_ro,
+                        Err(e) => {
+                            warn!("批量上传文件<{}>失败: {}", file_name, e);
+                            Ro::warn(format!("上传文件<{}>失败: {}", file_name, e))
+                        }
+                    });
                 }
                 _ => {}
             }
         }
-        Err(validator::ValidationError::new("上传文件为空"))?
+
+        if results.is_empty() {
+            Err(validator::ValidationError::new("上传文件为空"))?;
+        }
+        let ro = if results.iter().all(|ro| ro.extra.is_some()) {
+            Ro::success("上传成功".to_string())
+        } else {
+            Ro::warn("部分文件上传失败".to_string())
+        };
+        Ok(ro.extra(Some(results)))
+    }
+
+    /// # 秒传文件
+    ///
+    /// 客户端先计算文件的hash，只提交文件名、文件大小和hash，不传输文件内容。
+    /// 如果已存在相同hash和大小的对象，则直接新增对象引用并返回；否则返回警告信息，客户端需再正常上传文件。
+    /// 已存在的对象在上传时会按存储桶的EXIF策略处理时，也需要再正常上传文件
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `dto` - 文件名、文件大小和文件hash
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssObjRefVo>)` - 秒传成功时返回包含文件引用信息的结果对象，对象不存在时返回不含文件引用信息的警告
+    /// * `Err(SvcError)` - 秒传失败，返回错误信息
+    ///
+    /// ## 事务
+    /// 只新增一条对象引用，不需要事务，新增后即可在后台生成缩略图
+    #[db_unwrap]
+    pub async fn instant_upload<C>(
+        bucket: &str,
+        dto: OssFileInstantDto,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<OssObjRefVo>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        // 获取存储桶
+        let one_bucket = match OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra {
+            Some(bucket) => bucket,
+            None => return Ok(Ro::warn(format!("未找到存储桶<{}>", bucket))),
+        };
+
+        // 计算出的hash为小写的十六进制字符串
+        let hash = dto.hash.to_lowercase();
+        let exif_policy = OssExifSvc::get_policy(&one_bucket.exif_policy)?;
+        let obj_vo = match OssObjSvc::get_by_hash_and_size(&hash, &dto.size, Some(db))
+            .await?
+            .extra
+            .filter(|obj_vo| !OssExifSvc::is_affected(exif_policy, &obj_vo.content_type))
+        {
+            Some(obj_vo) => obj_vo,
+            None => return Ok(Ro::warn("需要上传文件".to_string())),
+        };
+
+        info!("对象已存在，秒传成功");
+        let (obj_ref_ro, thumbnail_task) = Self::add_obj_ref(
+            one_bucket.id,
+            obj_vo.id,
+            &dto.name,
+            current_user_id,
+            Some(db),
+        )
+        .await?;
+        OssThumbnailSvc::spawn_generate(thumbnail_task);
+        Ok(obj_ref_ro.msg("秒传成功".to_string()))
+    }
+
+    /// # 新增未完成的对象
+    ///
+    /// 根据当前时间生成对象在存储后端中的路径，并新增一条未完成(`is_completed = false`)的对象记录，
+    /// 对象内容写入存储后端后需调用 `complete_obj` 完成对象
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok((u64, String))` - 新增成功，返回对象ID和对象在存储后端中的路径
+    /// * `Err(SvcError)` - 新增失败，返回错误信息
+    #[db_unwrap]
+    pub async fn add_incomplete_obj<C>(
+        bucket: &str,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<(u64, String), SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let obj_id = get_id_worker()?.next_id()?;
+        // 根据当前时间，创建yyyy/MM/dd/HH的目录，并将文件存入此目录中
+        let now = now_ts()?;
+        let datetime = Local.timestamp_opt((now / 1000) as i64, 0).unwrap();
+        let oss_config = get_app_config()?.oss;
+
+        let date_path = datetime.format(&oss_config.file_dir_format).to_string();
+        // 对象在存储后端中的路径
+        let new_file_path = format!("{}/{}/{}", bucket, date_path, obj_id);
+
+        // 新增对象
+        let is_completed = false;
+        let oss_obj_add_dto = OssObjAddDto::builder()
+            .id(obj_id)
+            .path(new_file_path.to_string())
+            .is_completed(is_completed)
+            ._current_user_id(current_user_id)
+            .build();
+
+        debug!("新增对象: {:?}", oss_obj_add_dto);
+        let add_ro = OssObjSvc::add(oss_obj_add_dto, Some(db)).await?;
+        if let Some(obj_vo) = add_ro.extra {
+            Ok((obj_vo.id, new_file_path))
+        } else {
+            Err(SvcError::Runtime(anyhow!("新增对象失败")))
+        }
+    }
+
+    /// # 完成对象
+    ///
+    /// 对象内容写入存储后端后调用，最后再检查一次文件大小和hash是否已经存在:
+    /// - 如果已存在，则删除刚写入的内容及未完成的对象记录，返回已存在的对象ID
+    /// - 如果不存在，则修改对象信息的hash、文件大小、检测出的MIME类型，并标记为已完成
+    ///
+    /// ## 参数
+    /// * `obj_id` - 未完成的对象ID
+    /// * `path` - 对象在存储后端中的路径
+    /// * `received` - 接收文件时计算出的文件大小、hash及检测出的MIME类型
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(u64)` - 返回最终被引用的对象ID
+    /// * `Err(SvcError)` - 操作失败，返回错误信息
+    #[db_unwrap]
+    pub async fn complete_obj<C>(
+        obj_id: u64,
+        path: &str,
+        received: OssFileReceived,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<u64, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let OssFileReceived {
+            size,
+            hash,
+            content_type,
+            ..
+        } = received;
+        let oss_obj_vo = OssObjSvc::get_by_hash_and_size(&hash, &size, Some(db))
+            .await?
+            .extra;
+        if let Some(oss_obj_vo) = oss_obj_vo {
+            warn!("在上传完成后发现文件已存在，删除上传文件，引用的对象指向已存在的对象");
+            OssObjSvc::del_with_file(obj_id, Some(db)).await?;
+            Ok(oss_obj_vo.id)
+        } else {
+            // 文件已上传完成，修改对象信息的hash、文件大小、MIME类型、是否完成
+            let is_completed = true;
+            OssObjSvc::modify(
+                OssObjModifyDto::builder()
+                    .id(obj_id)
+                    .hash(Some(hash))
+                    .size(Some(size))
+                    .content_type(content_type)
+                    .is_completed(is_completed)
+                    ._current_user_id(current_user_id)
+                    .build(),
+                Some(db),
+            )
+            .await?;
+            debug!("对象已完成: {}", path);
+            Ok(obj_id)
+        }
+    }
+
+    /// # 完成上传
+    ///
+    /// 对象内容写入存储后端后，在一个事务中完成对象(去重)并新增对象引用；
+    /// 返回待生成的缩略图，调用方需在事务提交后调用 `OssThumbnailSvc::spawn_generate` 生成
+    ///
+    /// ## 参数
+    /// * `bucket_id` - 存储桶ID
+    /// * `obj_id` - 未完成的对象ID
+    /// * `path` - 对象在存储后端中的路径
+    /// * `received` - 接收文件时计算出的文件大小、hash及检测出的MIME类型
+    /// * `file_name` - 原始文件名
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok((Ro<OssObjRefVo>, Option<OssThumbnailTask>))` - 完成成功，返回包含文件引用信息的结果对象及待生成的缩略图
+    /// * `Err(SvcError)` - 完成失败，返回错误信息
+    #[db_unwrap(transaction_required)]
+    pub async fn finish_upload<C>(
+        bucket_id: u64,
+        obj_id: u64,
+        path: &str,
+        received: OssFileReceived,
+        file_name: &str,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<(Ro<OssObjRefVo>, Option<OssThumbnailTask>), SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let obj_id = Self::complete_obj(obj_id, path, received, current_user_id, Some(db)).await?;
+        Self::add_obj_ref(bucket_id, obj_id, file_name, current_user_id, Some(db)).await
+    }
+
+    /// # 新增对象引用
+    ///
+    /// 根据文件扩展名确定MIME类型，生成下载URL和预览URL(MIME类型在存储桶的可预览白名单中时)，并新增对象引用记录；
+    /// 上传时检测出的MIME类型与扩展名不符时，根据存储桶的策略拒绝或降级为 `application/octet-stream`；
+    /// 可预览的图片在存储桶设置了缩略图的尺寸时生成缩略图URL，并返回待生成的缩略图；
+    /// 可能在事务中调用，所以不在这里生成，调用方需在新增对象引用提交后调用 `OssThumbnailSvc::spawn_generate` 生成
+    ///
+    /// ## 参数
+    /// * `bucket_id` - 存储桶ID
+    /// * `obj_id` - 引用的对象ID
+    /// * `file_name` - 原始文件名
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok((Ro<OssObjRefVo>, Option<OssThumbnailTask>))` - 新增成功，返回包含文件引用信息的结果对象及待生成的缩略图
+    /// * `Err(SvcError)` - 新增失败，返回错误信息
+    #[db_unwrap]
+    pub async fn add_obj_ref<C>(
+        bucket_id: u64,
+        obj_id: u64,
+        file_name: &str,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<(Ro<OssObjRefVo>, Option<OssThumbnailTask>), SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let bucket_vo = OssBucketSvc::get_by_id(bucket_id, Some(db))
+            .await?
+            .extra
+            .ok_or(SvcError::NotFound(format!("bucket id: {}", bucket_id)))?;
+        let obj_vo = OssObjSvc::get_by_id(obj_id, Some(db))
+            .await?
+            .extra
+            .ok_or(SvcError::NotFound(format!("obj id: {}", obj_id)))?;
+        let ext = get_file_ext(file_name);
+        let mut content_type = OssMimeSvc::get_content_type(&ext)?;
+        if let Some(sniffed_content_type) = &obj_vo.content_type
+            && !OssMimeSvc::is_consistent(&content_type, sniffed_content_type)
+        {
+            match OssMimeSvc::get_mismatch_policy(&bucket_vo.mime_mismatch_policy)? {
+                MimeMismatchPolicy::Reject => {
+                    return Err(SvcError::Runtime(anyhow!(
+                        "文件内容与扩展名不符: 扩展名对应的类型为<{}>，检测出的类型为<{}>",
+                        content_type,
+                        sniffed_content_type
+                    )));
+                }
+                MimeMismatchPolicy::Downgrade => {
+                    warn!(
+                        "文件内容与扩展名不符，降级为{}: 扩展名对应的类型为<{}>，检测出的类型为<{}>",
+                        OCTET_STREAM, content_type, sniffed_content_type
+                    );
+                    content_type = OCTET_STREAM.to_string();
+                }
+            }
+        }
+        let obj_ref_id = get_id_worker()?.next_id()?;
+        let (obj_ref_name, preview_url) =
+            if OssMimeSvc::is_previewable(&content_type, &bucket_vo.previewable_types)?
+                && let Some(ext) = &ext
+            {
+                let obj_ref_name = format!("{}.{}", obj_ref_id, ext);
+                let preview_url = Some(format!("/oss/file/preview/{}", obj_ref_name));
+                (obj_ref_name, preview_url)
+            } else {
+                let obj_ref_name = obj_ref_id.to_string();
+                (obj_ref_name, None)
+            };
+        let download_url = format!("/oss/file/download/{}", obj_ref_name);
+        let (derivations, thumbnail_urls) = match &preview_url {
+            Some(preview_url) if OssImageSvc::is_processable(&content_type) => {
+                let derivations = OssThumbnailSvc::get_derivations(&bucket_vo.thumbnail_sizes)?;
+                let thumbnail_urls = OssThumbnailSvc::get_thumbnail_urls(preview_url, &derivations);
+                (derivations, thumbnail_urls)
+            }
+            _ => (vec![], None),
+        };
+        let oss_obj_ref_add_dto = OssObjRefAddDto::builder()
+            .id(obj_ref_id)
+            .name(file_name.to_string())
+            .bucket_id(bucket_id)
+            .obj_id(obj_id)
+            .ext(ext)
+            .download_url(download_url)
+            .preview_url(preview_url)
+            .content_type(Some(content_type.clone()))
+            .thumbnail_urls(thumbnail_urls)
+            ._current_user_id(current_user_id)
+            .build();
+        debug!("新增对象引用: {:?}", oss_obj_ref_add_dto);
+        let ro = OssObjRefSvc::add(oss_obj_ref_add_dto, Some(db)).await?;
+        let thumbnail_task = (!derivations.is_empty()).then(|| OssThumbnailTask {
+            obj_vo,
+            content_type,
+            derivations,
+            current_user_id,
+        });
+        Ok((ro, thumbnail_task))
+    }
+
+    /// # 获取上传临时目录
+    ///
+    /// 分片上传会话、tus上传等未完成的上传内容暂存在此目录中
+    pub fn upload_temp_dir() -> Result<PathBuf, SvcError> {
+        let upload_temp_dir = get_app_config()?.oss.upload_temp_dir;
+        Ok(APP_ENV
+            .get()
+            .ok_or(EnvError::GetAppEnv())?
+            .app_dir
+            .join(upload_temp_dir))
     }
 
     /// # 下载文件
     ///
-    /// 该函数负责根据对象引用ID下载文件内容，支持断点续传功能，可以指定下载文件的特定范围
+    /// 该函数负责根据对象引用ID获取要下载的文件信息，根据条件请求头判断文件是否未修改，并根据Range请求头解析要下载的范围，
+    /// 此时还未读取文件内容，需再调用 `read_content` 读取；存储桶设置了水印且访问者不是特权用户时，返回要添加的水印
     ///
     /// ## 参数
+    /// * `headers` - 请求头
     /// * `obj_ref_id` - 对象引用ID
     /// * `ext` - 文件扩展名
-    /// * `start` - 下载起始位置（可选）
-    /// * `end` - 下载结束位置（可选）
+    /// * `accessor` - 访问者，用于按存储桶的访问策略判断是否可以下载，及是否添加水印
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
     ///
     /// ## 返回值
-    /// * `Ok((String, u64, u64, Vec<u8>, Option<u64>, Option<u64>))` - 下载成功，返回元组包含：
-    ///   - 文件名
-    ///   - 文件总大小
-    ///   - 实际读取长度
-    ///   - 文件内容数据
-    ///   - 起始位置
-    ///   - 结束位置
-    /// * `Err(SvcError)` - 下载失败，返回错误信息
+    /// * `Ok(OssFileDownload)` - 返回要下载的文件信息
+    /// * `Err(SvcError)` - 获取失败，返回错误信息
     ///
     /// ## 错误处理
-    /// * 如果对象引用不存在或扩展名不匹配，返回 NotFound 错误
-    /// * 如果文件读取过程中发生错误，返回相应错误
+    /// * 如果对象引用不存在、扩展名不匹配或无权下载，返回 NotFound 错误(不暴露私有文件是否存在)
     #[db_unwrap]
     pub async fn download<C>(
         headers: HeaderMap,
         obj_ref_id: u64,
-        mut ext: Option<String>,
+        ext: Option<String>,
+        accessor: &OssAccessor,
         db: Option<&C>,
-    ) -> Result<
-        (
-            String,
-            Option<String>,
-            u64,
-            u64,
-            Body,
-            Option<u64>,
-            Option<u64>,
-        ),
-        SvcError,
-    >
+    ) -> Result<OssFileDownload, SvcError>
     where
         C: ConnectionTrait,
     {
-        // 解析Range头：格式为 "bytes=start-end" 或 "bytes=start-"
-        let (start, mut end) = match headers.get(header::RANGE) {
-            Some(range) => Self::parse_range(range)?,
-            // 如果没有Range头，则返回整个文件
-            None => (None, None),
-        };
-
         let one = OssObjRefDao::get_by_id_also_related(obj_ref_id, db).await?;
         let (obj_ref_model, _, obj_model) =
             one.ok_or(SvcError::NotFound(format!("id: {}", obj_ref_id)))?;
@@ -303,196 +879,619 @@ impl OssFileSvc {
         if &ext != &obj_ref_model.ext {
             return Err(SvcError::NotFound(format!("id: {}", obj_ref_id)));
         }
-        ext = obj_ref_model.ext;
-
-        // 读取文件指定范围内容
-        let mut file = File::open(&obj_model.path).await?;
-        let file_size = file.metadata().await?.len();
-        let (chunk_size, body) = if let Some(start_pos) = start {
-            if start_pos > file_size - 1 {
-                Err(validator::ValidationError::new("起始位置超过了文件末尾"))?;
+        // 按存储桶的访问策略判断是否可以下载
+        let bucket_vo = OssBucketSvc::get_by_id(obj_ref_model.bucket_id as u64, Some(db))
+            .await?
+            .extra
+            .ok_or(SvcError::NotFound(format!("bucket id: {}", obj_ref_model.bucket_id)))?;
+        let obj_ref_creator_id = obj_ref_model.creator_id as u64;
+        if !OssBucketSvc::can_read(&bucket_vo, obj_ref_creator_id, accessor, Some(db)).await? {
+            return Err(SvcError::NotFound(format!("id: {}", obj_ref_id)));
+        }
+        // 文件的上传者及拥有存储桶写权限的用户下载时不添加水印
+        let watermark = match &bucket_vo.watermark {
+            Some(watermark)
+                if !OssBucketSvc::is_privileged(
+                    &bucket_vo,
+                    obj_ref_creator_id,
+                    accessor,
+                    Some(db),
+                )
+                .await? =>
+            {
+                Some(watermark.clone())
             }
-            // 如果没有指定结束位置，则默认为文件末尾
-            let end_pos = end.unwrap_or({
-                let end_pos = file_size - 1;
-                end = Some(end_pos);
-                end_pos
-            });
-            if end_pos > file_size - 1 {
-                Err(validator::ValidationError::new("结束位置超过了文件末尾"))?;
+            _ => None,
+        };
+
+        // 已完成的对象记录了文件大小，不需要再访问存储后端
+        let file_size = match obj_model.size {
+            Some(size) => size as u64,
+            None => get_storage_backend()?.stat(&obj_model.path).await?.size,
+        };
+        // 以文件的hash作为强ETag，以对象和对象引用中较晚的更新时间作为最后修改时间(精确到秒)
+        let etag = obj_model.hash.as_ref().map(|hash| format!("\"{}\"", hash));
+        let last_modified =
+            (obj_model.update_timestamp.max(obj_ref_model.update_timestamp) / 1000) as u64;
+        let not_modified = Self::is_not_modified(&headers, &etag, last_modified);
+        // 解析Range头，如果没有Range头或If-Range不匹配，则返回整个文件
+        let ranges = match headers.get(header::RANGE) {
+            Some(range) if Self::is_if_range_matched(&headers, &etag, last_modified) => {
+                Self::parse_range(range, file_size)
             }
+            _ => OssFileRanges::Full,
+        };
 
-            let chunk_size = end_pos - start_pos + 1;
-            file.seek(SeekFrom::Start(start_pos)).await?;
+        // 新增对象引用时已确定MIME类型，早期的对象引用没有记录时再根据扩展名确定
+        let content_type = match obj_ref_model.content_type {
+            Some(content_type) => content_type,
+            None => OssMimeSvc::get_content_type(&obj_ref_model.ext)?,
+        };
 
-            // 用 Take 限制只读 chunk_size 字节，再包成流
-            let limited = file.take(chunk_size);
-            let buffer_size = get_app_config()?.oss.download_buffer_size.as_u64() as usize;
-            let stream = ReaderStream::with_capacity(limited, buffer_size);
-            let body = Body::from_stream(stream);
-            (chunk_size, body)
+        Ok(OssFileDownload {
+            obj_id: obj_model.id as u64,
+            file_name: obj_ref_model.name,
+            ext: obj_ref_model.ext,
+            content_type,
+            previewable: obj_ref_model.preview_url.is_some(),
+            path: obj_model.path,
+            file_size,
+            etag,
+            last_modified: Self::format_http_date(last_modified),
+            not_modified,
+            ranges,
+            bucket_id: bucket_vo.id,
+            watermark,
+        })
+    }
+
+    /// # 判断文件是否未修改
+    ///
+    /// 按 RFC 7232 判断条件请求，未修改时应返回304状态码:
+    /// - 有 `If-None-Match` 头时，只要有一个ETag匹配(弱比较)或为 `*` 即为未修改
+    /// - 否则有 `If-Modified-Since` 头时，最后修改时间不晚于该时间即为未修改
+    pub fn is_not_modified(headers: &HeaderMap, etag: &Option<String>, last_modified: u64) -> bool {
+        // If-None-Match 优先于 If-Modified-Since
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+            return if_none_match.split(',').map(str::trim).any(|tag| {
+                tag == "*" || etag.as_deref() == Some(tag.trim_start_matches("W/"))
+            });
+        }
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|if_modified_since| if_modified_since.to_str().ok())
+            .and_then(Self::parse_http_date)
+            .is_some_and(|if_modified_since| last_modified <= if_modified_since)
+    }
+
+    /// # 判断If-Range是否匹配
+    ///
+    /// 没有 `If-Range` 头时视为匹配；ETag使用强比较，弱ETag不匹配；日期须与最后修改时间完全相同。
+    /// 不匹配时说明客户端已缓存的部分内容已过期，应忽略Range头返回整个文件
+    fn is_if_range_matched(headers: &HeaderMap, etag: &Option<String>, last_modified: u64) -> bool {
+        let Some(if_range) = headers.get(header::IF_RANGE) else {
+            return true;
+        };
+        let Ok(if_range) = if_range.to_str() else {
+            return false;
+        };
+        let if_range = if_range.trim();
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            etag.as_deref() == Some(if_range)
         } else {
-            // 将文件转为异步流
-            let read_stream = ReaderStream::new(file);
-            let body = Body::from_stream(read_stream);
-            (file_size, body)
+            Self::parse_http_date(if_range) == Some(last_modified)
+        }
+    }
+
+    /// # 格式化HTTP日期
+    ///
+    /// 将秒级时间戳格式化为 RFC 7231 的HTTP日期格式，如 `Sun, 06 Nov 1994 08:49:37 GMT`
+    fn format_http_date(timestamp: u64) -> String {
+        Utc.timestamp_opt(timestamp as i64, 0)
+            .single()
+            .unwrap_or_default()
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string()
+    }
+
+    /// # 解析HTTP日期
+    ///
+    /// 返回秒级时间戳，格式不正确时返回None
+    pub fn parse_http_date(http_date: &str) -> Option<u64> {
+        DateTime::parse_from_rfc2822(http_date.trim())
+            .ok()
+            .and_then(|datetime| u64::try_from(datetime.timestamp()).ok())
+    }
+
+    /// # 读取下载文件的内容
+    ///
+    /// 根据解析出的范围从存储后端读取文件内容:
+    /// - 没有范围时读取整个文件
+    /// - 单个范围时读取该范围的内容
+    /// - 多个范围时按 `multipart/byteranges` 格式依次读取各个范围的内容
+    ///
+    /// ## 参数
+    /// * `download` - 要下载的文件信息
+    /// * `content_type` - 文件的内容类型，用于 `multipart/byteranges` 中每个部分的 `Content-Type`
+    /// * `with_body` - 是否读取内容，为false时(HEAD请求)不打开文件，只返回响应头所需的信息，内容为空
+    pub async fn read_content(
+        download: &OssFileDownload,
+        content_type: &str,
+        with_body: bool,
+    ) -> Result<OssFileContent, SvcError> {
+        let storage_backend = get_storage_backend()?;
+        let ranges = match &download.ranges {
+            OssFileRanges::Full => {
+                if !with_body {
+                    return Ok(OssFileContent::Full(Body::empty()));
+                }
+                let stream = storage_backend.get(&download.path, None).await?;
+                return Ok(OssFileContent::Full(Body::from_stream(stream)));
+            }
+            OssFileRanges::NotSatisfiable => return Ok(OssFileContent::NotSatisfiable),
+            OssFileRanges::Satisfiable(ranges) => ranges,
         };
+        if let [(start, end)] = ranges.as_slice() {
+            let body = if with_body {
+                let stream = storage_backend
+                    .get(&download.path, Some((*start, *end)))
+                    .await?;
+                Body::from_stream(stream)
+            } else {
+                Body::empty()
+            };
+            return Ok(OssFileContent::Partial {
+                start: *start,
+                end: *end,
+                body,
+            });
+        }
 
-        Ok((
-            obj_ref_model.name,
-            ext,
-            file_size,
-            chunk_size,
-            body,
-            start,
-            end,
-        ))
+        // 多个范围，每个部分之前是分隔符及该部分的头
+        let boundary = format!("oss-byteranges-{}", get_id_worker()?.next_id()?);
+        let parts: Vec<(Bytes, (u64, u64))> = ranges
+            .iter()
+            .map(|&(start, end)| {
+                let part_head = format!(
+                    "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    boundary, content_type, start, end, download.file_size
+                );
+                (Bytes::from(part_head), (start, end))
+            })
+            .collect();
+        let tail = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+        let content_length = parts
+            .iter()
+            .map(|(part_head, (start, end))| part_head.len() as u64 + end - start + 1)
+            .sum::<u64>()
+            + tail.len() as u64;
+        if !with_body {
+            return Ok(OssFileContent::Multipart {
+                boundary,
+                content_length,
+                body: Body::empty(),
+            });
+        }
+
+        let path = download.path.clone();
+        let stream = stream::iter(parts)
+            .then(move |(part_head, range)| {
+                let storage_backend = storage_backend.clone();
+                let path = path.clone();
+                async move {
+                    let part_body = storage_backend.get(&path, Some(range)).await?;
+                    Ok::<_, io::Error>(stream::once(future::ready(Ok(part_head))).chain(part_body))
+                }
+            })
+            .try_flatten()
+            .chain(stream::once(future::ready(Ok(tail))));
+        Ok(OssFileContent::Multipart {
+            boundary,
+            content_length,
+            body: Body::from_stream(stream),
+        })
     }
 
-    async fn receive_and_write(
+    /// # 接收上传的字节流并写入存储后端
+    ///
+    /// 接收过程中计算文件大小和hash，并保留文件头部用于检测真实的MIME类型，超出上传大小限制时中止写入，
+    /// 写入完成后如果与提供的文件大小或hash不一致，则删除已写入的对象
+    ///
+    /// 存储桶设置了EXIF策略时，先根据文件头部判断是否为可处理的图片，是图片时将文件内容接收到内存中，
+    /// 按策略处理后再写入，提供的文件大小和hash按接收到的原始内容校验，记录的文件大小和hash为写入的内容；
+    /// 不是可处理的图片或超过图片可处理的最大大小时按原样写入
+    ///
+    /// ## 返回值
+    /// 返回计算出的文件大小、hash及检测出的MIME类型
+    pub async fn receive_and_write<S, E>(
         hash_provided: &Option<String>,
         file_size_provided: &Option<u64>,
-        mut field: Field<'_>,
+        stream: S,
         new_file_path: &str,
-    ) -> Result<(u64, String), SvcError> {
+        exif_policy: OssExifPolicy,
+    ) -> Result<OssFileReceived, SvcError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
         // 如果对象不存在，则开始接收 chunk
         let AppConfig { oss, .. } = get_app_config().expect("app config not found");
         let OssConfig {
             upload_file_limit_size,
-            upload_buffer_size,
+            image,
             ..
         } = oss;
 
-        let buffer_size = upload_buffer_size.as_u64();
         let mut file_size_computed: u64 = 0;
-        let mut file = File::create(new_file_path).await?;
         let mut hasher = sha2::Sha256::new();
-        let mut buffer = Vec::with_capacity(buffer_size as usize);
-        // 分块写入，而非 .bytes() 一次性读取
-        while let Some(chunk) = field.chunk().await? {
-            let chunk_size = chunk.len() as u64;
-            file_size_computed += chunk_size;
+        let mut head: Vec<u8> = Vec::with_capacity(SNIFF_SIZE);
+        let stream = stream.map(|chunk| {
+            let chunk = chunk.map_err(io::Error::other)?;
+            file_size_computed += chunk.len() as u64;
             if file_size_computed > upload_file_limit_size.as_u64() {
-                fs::remove_file(new_file_path).await?;
-                return Err(SvcError::Runtime(anyhow!(
+                return Err(io::Error::other(format!(
                     "上传文件大小超出限制: {upload_file_limit_size}"
                 )));
             }
             hasher.update(&chunk);
-            if chunk_size == buffer_size {
-                trace!("切片大小 == 缓存大小，直接写入盘(没有用到缓存)");
-                file.write_all(&chunk).await?;
-            } else if chunk_size > buffer_size {
-                trace!(
-                    "切片大小 > 缓存大小，chunk按缓存大小分片处理，避免单次写入过大(这里也没有用到缓存)"
-                );
-                let mut chunk_start: usize = 0;
-                while chunk_start < chunk_size as usize {
-                    let chunk_end = (chunk_start + buffer_size as usize).min(chunk_size as usize);
-                    let slice = &chunk[chunk_start..chunk_end];
-                    file.write_all(slice).await?;
-                    chunk_start = chunk_end;
+            if head.len() < SNIFF_SIZE {
+                let len = (SNIFF_SIZE - head.len()).min(chunk.len());
+                head.extend_from_slice(&chunk[..len]);
+            }
+            Ok(chunk)
+        });
+        let storage_backend = get_storage_backend()?;
+        // 按EXIF策略处理后的内容，未处理时为None
+        let processed = if exif_policy == OssExifPolicy::Keep {
+            storage_backend.put(new_file_path, stream.boxed()).await?;
+            None
+        } else {
+            let mut stream = stream.boxed();
+            let mut data = Vec::new();
+            // 先读取文件头部，只有可处理的图片才接收到内存中，其它文件直接写入
+            while data.len() < SNIFF_SIZE
+                && let Some(chunk) = stream.try_next().await?
+            {
+                data.extend_from_slice(&chunk);
+            }
+            let max_source_size = image.max_source_size.as_u64();
+            let processable = OssExifSvc::is_processable(&data);
+            if processable {
+                while data.len() as u64 <= max_source_size
+                    && let Some(chunk) = stream.try_next().await?
+                {
+                    data.extend_from_slice(&chunk);
+                }
+            }
+            if !processable || data.len() as u64 > max_source_size {
+                if processable {
+                    warn!(
+                        "上传文件超过图片可处理的最大大小，按原样保存: {}",
+                        new_file_path
+                    );
+                }
+                let body = stream::once(future::ready(Ok(Bytes::from(data)))).chain(stream);
+                storage_backend.put(new_file_path, body.boxed()).await?;
+                None
+            } else {
+                drop(stream);
+                let data = Bytes::from(data);
+                let processed = OssExifSvc::process(exif_policy, data.clone())
+                    .await?
+                    .map(Bytes::from);
+                let body = processed.clone().unwrap_or(data);
+                storage_backend
+                    .put(
+                        new_file_path,
+                        stream::once(future::ready(Ok::<_, io::Error>(body))).boxed(),
+                    )
+                    .await?;
+                processed
+            }
+        };
+
+        let hash_computed = hex::encode(hasher.finalize());
+        if let Err(e) = Self::check_provided(
+            hash_provided,
+            file_size_provided,
+            &hash_computed,
+            file_size_computed,
+        ) {
+            storage_backend.delete(new_file_path).await?;
+            return Err(e);
+        }
+        let (size, hash) = match processed {
+            Some(processed) => (
+                processed.len() as u64,
+                hex::encode(sha2::Sha256::digest(&processed)),
+            ),
+            None => (file_size_computed, hash_computed.clone()),
+        };
+        Ok(OssFileReceived {
+            size,
+            hash,
+            content_type: OssMimeSvc::sniff(&head),
+            original_size: file_size_computed,
+            original_hash: hash_computed,
+        })
+    }
+
+    /// # 解析Range请求头
+    ///
+    /// 按 RFC 7233 解析，支持以下格式，多个范围之间以逗号分隔:
+    /// - `bytes=500-999` - 第500到第999个字节
+    /// - `bytes=500-` - 从第500个字节到文件末尾
+    /// - `bytes=-500` - 最后500个字节
+    ///
+    /// 格式不正确、不是字节单位或范围过多时忽略Range头，返回整个文件；
+    /// 所有范围都超出文件末尾时返回范围不可满足；重叠或相邻的范围会被合并
+    fn parse_range(range: &HeaderValue, file_size: u64) -> OssFileRanges {
+        let Some(range_specs) = range.to_str().ok().and_then(|range| {
+            let (unit, range_specs) = range.split_once('=')?;
+            unit.trim()
+                .eq_ignore_ascii_case("bytes")
+                .then_some(range_specs)
+        }) else {
+            return OssFileRanges::Full;
+        };
+
+        let mut ranges = vec![];
+        for range_spec in range_specs.split(',') {
+            let range_spec = range_spec.trim();
+            if range_spec.is_empty() {
+                continue;
+            }
+            let Some((first, last)) = range_spec.split_once('-') else {
+                return OssFileRanges::Full;
+            };
+            let (first, last) = (first.trim(), last.trim());
+            let range = if first.is_empty() {
+                // 后缀范围: 最后n个字节
+                let Ok(suffix_length) = last.parse::<u64>() else {
+                    return OssFileRanges::Full;
+                };
+                if suffix_length == 0 || file_size == 0 {
+                    None
+                } else {
+                    Some((file_size.saturating_sub(suffix_length), file_size - 1))
                 }
             } else {
-                trace!("切片大小 < 缓存大小，攒够缓存满才写一次盘");
-                let buffer_length = buffer.len() as u64; // 缓存实际大小
-                let total_size = buffer_length + chunk_size; // 总大小 = 缓存大小 + 切片大小
-                let chunk_remain = total_size - buffer_length; // 切片剩余大小
-                // 如果切片剩余大小 <= 0，则将切片添加入缓存中，否则将缓存中的数据写入盘，并清空缓存，再将剩余切片写入缓存中
-                if chunk_remain <= 0 {
-                    buffer.extend_from_slice(&chunk);
+                let Ok(first) = first.parse::<u64>() else {
+                    return OssFileRanges::Full;
+                };
+                let last = if last.is_empty() {
+                    None
                 } else {
-                    file.write_all(&buffer).await?;
-                    buffer.clear();
-                    buffer.extend_from_slice(&chunk);
+                    match last.parse::<u64>() {
+                        Ok(last) if last >= first => Some(last),
+                        _ => return OssFileRanges::Full,
+                    }
+                };
+                if first >= file_size {
+                    None
+                } else {
+                    // 结束位置超过文件末尾时，截止到文件末尾
+                    let last = last.map_or(file_size - 1, |last| last.min(file_size - 1));
+                    Some((first, last))
                 }
+            };
+            if let Some(range) = range {
+                ranges.push(range);
+            }
+            if ranges.len() > MAX_RANGES {
+                warn!("Range请求的范围过多，忽略Range头");
+                return OssFileRanges::Full;
             }
         }
-        // 写入剩余的缓存部分
-        if !buffer.is_empty() {
-            file.write_all(&buffer).await?;
+        if ranges.is_empty() {
+            return OssFileRanges::NotSatisfiable;
         }
 
-        if let Some(file_size_provided) = file_size_provided
-            && file_size_provided != &file_size_computed
-        {
-            fs::remove_file(new_file_path).await?;
-            return Err(SvcError::Runtime(anyhow!(
-                "上传文件大小错误，请重新上传: {file_size_provided}->{file_size_computed}"
-            )));
-        }
-        let hash_computed = hex::encode(hasher.finalize());
-        if let Some(hash_provided) = hash_provided.clone()
-            && hash_provided != hash_computed
-        {
-            fs::remove_file(new_file_path).await?;
-            return Err(SvcError::Runtime(anyhow!(
-                "上传文件内容校验错误，请重新上传: {hash_provided}->{hash_computed}"
-            )));
+        // 合并重叠或相邻的范围
+        ranges.sort_unstable();
+        let mut merged_ranges: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged_ranges.last_mut() {
+                Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+                _ => merged_ranges.push((start, end)),
+            }
         }
-        Ok((file_size_computed, hash_computed))
+        OssFileRanges::Satisfiable(merged_ranges)
+    }
+}
+
+/// # 上传时接收到的文件
+enum ReceivedFile {
+    /// 已存在相同的对象，未写入文件
+    Existing { file_name: String, obj_id: u64 },
+    /// 已写入存储后端的未完成对象
+    Written {
+        file_name: String,
+        obj_id: u64,
+        path: String,
+        received: OssFileReceived,
+    },
+}
+
+/// # 接收文件的结果
+pub struct OssFileReceived {
+    /// 计算出的写入存储后端的文件大小
+    pub size: u64,
+    /// 计算出的写入存储后端的文件hash
+    pub hash: String,
+    /// 根据文件头部的特征字节检测出的MIME类型，无法检测时为None
+    pub content_type: Option<String>,
+    /// 接收到的原始文件大小，按EXIF策略处理后与写入的文件大小不同，用于校验提供的文件大小
+    pub original_size: u64,
+    /// 接收到的原始文件hash，按EXIF策略处理后与写入的文件hash不同，用于校验提供的文件hash
+    pub original_hash: String,
+}
+
+/// # 要下载的文件
+pub struct OssFileDownload {
+    /// 对象ID
+    pub obj_id: u64,
+    /// 文件名
+    pub file_name: String,
+    /// 文件扩展名
+    pub ext: Option<String>,
+    /// MIME类型
+    pub content_type: String,
+    /// 是否可预览(新增对象引用时MIME类型在存储桶的可预览白名单中)
+    pub previewable: bool,
+    /// 对象在存储后端中的路径
+    pub path: String,
+    /// 文件大小
+    pub file_size: u64,
+    /// 强ETag，即带引号的文件hash
+    pub etag: Option<String>,
+    /// 最后修改时间(HTTP日期格式)
+    pub last_modified: String,
+    /// 根据条件请求头判断文件未修改，应返回304状态码
+    pub not_modified: bool,
+    /// 要下载的范围
+    pub ranges: OssFileRanges,
+    /// 存储桶ID
+    pub bucket_id: u64,
+    /// 要添加的存储桶的水印，访问者是特权用户或存储桶未设置水印时为None
+    pub watermark: Option<String>,
+}
+
+/// # 要下载的范围
+#[derive(Debug, PartialEq, Eq)]
+pub enum OssFileRanges {
+    /// 整个文件
+    Full,
+    /// 一个或多个范围，每个范围为起始位置和结束位置(包含)，已按起始位置排序并合并了重叠的范围
+    Satisfiable(Vec<(u64, u64)>),
+    /// 所有范围都超出了文件末尾
+    NotSatisfiable,
+}
+
+/// # 下载文件的内容
+pub enum OssFileContent {
+    /// 整个文件
+    Full(Body),
+    /// 单个范围的内容
+    Partial { start: u64, end: u64, body: Body },
+    /// 多个范围的内容(`multipart/byteranges`)
+    Multipart {
+        boundary: String,
+        content_length: u64,
+        body: Body,
+    },
+    /// 范围不可满足
+    NotSatisfiable,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(range: &str, file_size: u64) -> OssFileRanges {
+        OssFileSvc::parse_range(&HeaderValue::from_str(range).unwrap(), file_size)
     }
 
-    fn parse_range(range: &HeaderValue) -> Result<(Option<u64>, Option<u64>), SvcError> {
-        // "bytes=500-999"  → (500, 999)
-        // "bytes=500-"     → (500, total-1)
-        let range = range
-            .to_str()
-            .map_err(|_| validator::ValidationError::new("无效的Range格式"))?;
-        let range = range
-            .strip_prefix("bytes=")
-            .ok_or_else(|| validator::ValidationError::new("无效的Range格式"))?;
-
-        let parts: Vec<&str> = range.split("-").collect();
-        let start = Some(
-            parts
-                .get(0)
-                .ok_or_else(|| validator::ValidationError::new("无效的Range格式"))?
-                .parse::<u64>()
-                .map_err(|_| validator::ValidationError::new("无效的Range格式"))?,
+    #[test]
+    fn parse_range_single() {
+        assert_eq!(
+            parse("bytes=0-99", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 99)])
         );
-        let end = if parts.len() > 1 && !parts[1].is_empty() {
-            // len>1，【1]取法没问题
-            Some(
-                parts[1]
-                    .parse::<u64>()
-                    .map_err(|_| validator::ValidationError::new("无效的Range格式"))?,
-            )
-        } else {
-            None
-        };
+        // 单位不区分大小写，允许空白
+        assert_eq!(
+            parse("Bytes= 500 - 999 ", 1000),
+            OssFileRanges::Satisfiable(vec![(500, 999)])
+        );
+        // 结束位置超过文件末尾时截止到文件末尾
+        assert_eq!(
+            parse("bytes=900-2000", 1000),
+            OssFileRanges::Satisfiable(vec![(900, 999)])
+        );
+    }
 
-        Ok((start, end))
+    #[test]
+    fn parse_range_open_ended_and_suffix() {
+        assert_eq!(
+            parse("bytes=100-", 1000),
+            OssFileRanges::Satisfiable(vec![(100, 999)])
+        );
+        assert_eq!(
+            parse("bytes=-500", 1000),
+            OssFileRanges::Satisfiable(vec![(500, 999)])
+        );
+        // 后缀长度超过文件大小时返回整个文件的范围
+        assert_eq!(
+            parse("bytes=-5000", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 999)])
+        );
     }
 
-    fn is_previewable(ext: &Option<String>) -> bool {
-        if let Some(ext) = ext {
-            match ext.as_str() {
-                "jpg" | "jpeg" | "png" | "gif" | "webp" | "svg" | "pdf" | "txt" | "md" | "mp3"
-                | "wav" | "ogg" | "aac" | "flac" | "mp4" => true,
-                _ => false,
-            }
-        } else {
-            false
-        }
+    #[test]
+    fn parse_range_not_satisfiable() {
+        assert_eq!(parse("bytes=1000-", 1000), OssFileRanges::NotSatisfiable);
+        assert_eq!(
+            parse("bytes=2000-3000", 1000),
+            OssFileRanges::NotSatisfiable
+        );
+        assert_eq!(parse("bytes=-0", 1000), OssFileRanges::NotSatisfiable);
+        assert_eq!(parse("bytes=-10", 0), OssFileRanges::NotSatisfiable);
+        assert_eq!(parse("bytes=0-", 0), OssFileRanges::NotSatisfiable);
+        // 只要有一个范围可满足就只返回可满足的范围
+        assert_eq!(
+            parse("bytes=2000-3000, 0-9", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 9)])
+        );
     }
 
-    pub fn get_content_type_of_preview(ext: &Option<String>) -> Option<&'static str> {
-        if let Some(ext) = ext {
-            match ext.as_str() {
-                "jpg" | "jpeg" => Some("image/jpeg"),
-                "png" => Some("image/png"),
-                "gif" => Some("image/gif"),
-                "webp" => Some("image/webp"),
-                "svg" => Some("image/svg+xml"),
-                "pdf" => Some("application/pdf"),
-                "txt" | "md" => Some("text/plain; charset=utf-8"),
-                "mp3" | "wav" | "ogg" | "aac" | "flac" => Some("audio/mpeg"),
-                "mp4" => Some("video/mp4"),
-                _ => None,
-            }
-        } else {
-            None
+    #[test]
+    fn parse_range_invalid() {
+        assert_eq!(parse("0-99", 1000), OssFileRanges::Full);
+        assert_eq!(parse("items=0-99", 1000), OssFileRanges::Full);
+        assert_eq!(parse("bytes=abc", 1000), OssFileRanges::Full);
+        assert_eq!(parse("bytes=a-99", 1000), OssFileRanges::Full);
+        assert_eq!(parse("bytes=99-0", 1000), OssFileRanges::Full);
+        assert_eq!(parse("bytes=-", 1000), OssFileRanges::Full);
+        // 有一个范围格式不正确时忽略整个Range头
+        assert_eq!(parse("bytes=0-9, x-y", 1000), OssFileRanges::Full);
+    }
+
+    #[test]
+    fn parse_range_merge() {
+        // 重叠及相邻的范围合并，按起始位置排序
+        assert_eq!(
+            parse("bytes=500-599, 0-99, 50-149, 150-199", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 199), (500, 599)])
+        );
+        assert_eq!(
+            parse("bytes=0-99, -100, 950-", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 99), (900, 999)])
+        );
+        // 空的范围被跳过
+        assert_eq!(
+            parse("bytes=0-9,,20-29", 1000),
+            OssFileRanges::Satisfiable(vec![(0, 9), (20, 29)])
+        );
+    }
+
+    #[test]
+    fn parse_range_too_many() {
+        let specs = |count: u64| {
+            (0..count)
+                .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match parse(&format!("bytes={}", specs(MAX_RANGES as u64)), 10000) {
+            OssFileRanges::Satisfiable(ranges) => assert_eq!(ranges.len(), MAX_RANGES),
+            ranges => panic!("unexpected ranges: {:?}", ranges),
         }
+        assert_eq!(
+            parse(&format!("bytes={}", specs(MAX_RANGES as u64 + 1)), 10000),
+            OssFileRanges::Full
+        );
     }
 }
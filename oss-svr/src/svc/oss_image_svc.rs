@@ -0,0 +1,1149 @@
+use crate::app::{ImageConfig, get_app_config};
+use crate::storage::get_storage_backend;
+use crate::svc::{OssAccessor, OssBucketSvc, OssObjRefSvc};
+use crate::svc::{OssFileDownload, OssFileSvc, OssMimeSvc, OssObjSvc};
+use crate::vo::OssObjVo;
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont, point};
+use anyhow::anyhow;
+use axum::http::HeaderMap;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use idworker::get_id_worker;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, Rgba, RgbaImage, imageops};
+use robotech::env::{APP_ENV, EnvError};
+use robotech::svc::SvcError;
+use sea_orm::DatabaseTransaction;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use strum::EnumString;
+use tokio::fs;
+use tracing::{debug, warn};
+
+/// 处理参数的前缀，目前只支持图片处理
+const PROCESS_PREFIX: &str = "image";
+
+/// 未指定时JPEG的输出质量
+const DEFAULT_QUALITY: u8 = 90;
+
+/// 水印操作的名称
+const WATERMARK_ACTION: &str = "watermark";
+
+/// 未指定时水印文字的大小(像素)
+const DEFAULT_WATERMARK_SIZE: u32 = 40;
+
+/// 未指定时水印与图片边缘的距离(像素)
+const DEFAULT_WATERMARK_MARGIN: u32 = 10;
+
+/// 水印文字的最大长度(字符数)
+const MAX_WATERMARK_TEXT_LENGTH: usize = 64;
+
+/// 文字水印的字体，第一次使用时加载
+static WATERMARK_FONT: OnceLock<FontVec> = OnceLock::new();
+
+/// 可以处理的图片类型(SVG是矢量图，不处理)
+const PROCESSABLE_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/bmp",
+];
+
+/// # 图片处理参数
+///
+/// 由 `x-oss-process` 参数解析而来，格式与阿里云OSS相同，如 `image/resize,w_200/quality,q_80/format,webp`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OssImageProcess {
+    /// 按顺序执行的缩放、裁剪、旋转、水印操作
+    pub actions: Vec<OssImageAction>,
+    /// 输出格式，未指定时与原图相同
+    pub format: Option<OssImageFormat>,
+    /// 输出质量(1-100)，只对JPEG有效(WebP为无损压缩)
+    pub quality: Option<u8>,
+}
+
+/// # 图片处理操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum OssImageAction {
+    /// 缩放
+    Resize(OssImageResize),
+    /// 裁剪
+    Crop(OssImageCrop),
+    /// 顺时针旋转的角度，只支持90的倍数
+    Rotate(u32),
+    /// 水印
+    Watermark(OssImageWatermark),
+}
+
+/// # 缩放参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct OssImageResize {
+    /// 缩放模式
+    pub mode: OssImageResizeMode,
+    /// 目标宽度
+    pub width: Option<u32>,
+    /// 目标高度
+    pub height: Option<u32>,
+    /// 按百分比缩放(1-1000)，指定时忽略宽度和高度
+    pub percent: Option<u32>,
+    /// 目标尺寸大于原图时是否不放大
+    pub limit: bool,
+    /// `pad` 模式填充的颜色
+    pub color: [u8; 3],
+}
+
+/// # 缩放模式
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum OssImageResizeMode {
+    /// 等比缩放到宽高都不超过目标尺寸
+    #[default]
+    Lfit,
+    /// 等比缩放到宽高都不小于目标尺寸
+    Mfit,
+    /// 等比缩放到覆盖目标尺寸后居中裁剪
+    Fill,
+    /// 等比缩放到不超过目标尺寸后居中填充
+    Pad,
+    /// 强制缩放到目标尺寸
+    Fixed,
+}
+
+/// # 裁剪参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct OssImageCrop {
+    /// 裁剪的宽度，未指定时裁剪到右边缘
+    pub width: Option<u32>,
+    /// 裁剪的高度，未指定时裁剪到下边缘
+    pub height: Option<u32>,
+    /// 相对于原点的横向偏移
+    pub x: u32,
+    /// 相对于原点的纵向偏移
+    pub y: u32,
+    /// 裁剪的原点
+    pub gravity: OssImageGravity,
+}
+
+/// # 水印参数
+///
+/// 文字水印和图片水印只能指定其中之一
+#[derive(Debug, Clone, PartialEq)]
+pub struct OssImageWatermark {
+    /// 水印文字
+    pub text: Option<String>,
+    /// 水印图片的对象引用ID
+    pub image: Option<u64>,
+    /// 文字的颜色
+    pub color: [u8; 3],
+    /// 文字的大小(像素)
+    pub size: u32,
+    /// 不透明度(0-100)
+    pub opacity: u32,
+    /// 水印的宽度占图片宽度的百分比(1-100)，未指定时不缩放水印
+    pub scale: Option<u32>,
+    /// 水印的位置
+    pub gravity: OssImageGravity,
+    /// 水印与图片左右边缘的距离
+    pub x: u32,
+    /// 水印与图片上下边缘的距离
+    pub y: u32,
+}
+
+/// # 裁剪的原点及水印的位置
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum OssImageGravity {
+    /// 左上
+    #[default]
+    Nw,
+    /// 中上
+    North,
+    /// 右上
+    Ne,
+    /// 左中
+    West,
+    /// 中部
+    Center,
+    /// 右中
+    East,
+    /// 左下
+    Sw,
+    /// 中下
+    South,
+    /// 右下
+    Se,
+}
+
+/// # 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum OssImageFormat {
+    #[strum(serialize = "jpg", serialize = "jpeg")]
+    Jpg,
+    Png,
+    Webp,
+    Gif,
+    Bmp,
+}
+
+impl OssImageFormat {
+    /// 根据MIME类型获取格式，不支持时返回None
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        match OssMimeSvc::get_essence(content_type).as_str() {
+            "image/jpeg" => Some(Self::Jpg),
+            "image/png" => Some(Self::Png),
+            "image/webp" => Some(Self::Webp),
+            "image/gif" => Some(Self::Gif),
+            "image/bmp" => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+
+    /// MIME类型
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jpg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+            Self::Gif => "image/gif",
+            Self::Bmp => "image/bmp",
+        }
+    }
+
+    /// 缓存文件的扩展名
+    fn ext(&self) -> &'static str {
+        match self {
+            Self::Jpg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+        }
+    }
+
+    /// 对应的编码格式
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            Self::Jpg => ImageFormat::Jpeg,
+            Self::Png => ImageFormat::Png,
+            Self::Webp => ImageFormat::WebP,
+            Self::Gif => ImageFormat::Gif,
+            Self::Bmp => ImageFormat::Bmp,
+        }
+    }
+}
+
+/// # 图片处理服务
+///
+/// 预览图片时按 `x-oss-process` 参数缩放、裁剪、旋转及转换格式，处理后的图片以原图的hash及处理参数为键缓存在磁盘上，
+/// 相同的请求直接返回缓存；原图是不可变的(内容变化时hash也变化)，所以缓存不需要失效
+pub struct OssImageSvc;
+
+impl OssImageSvc {
+    /// # 解析图片处理参数
+    ///
+    /// 支持的操作:
+    /// - `resize`: `m_`(lfit/mfit/fill/pad/fixed)、`w_`、`h_`、`l_`(最长边)、`s_`(最短边)、`p_`(百分比)、`limit_`(0/1)、`color_`(十六进制RGB)
+    /// - `crop`: `w_`、`h_`、`x_`、`y_`、`g_`(nw/north/ne/west/center/east/sw/south/se)
+    /// - `rotate`: 顺时针旋转的角度，只支持90的倍数
+    /// - `watermark`: `text_`(URL安全的Base64编码的文字)或`image_`(水印图片的对象引用ID)、`color_`(十六进制RGB)、`size_`(文字大小)、
+    ///   `t_`(不透明度0-100)、`P_`(水印宽度占图片宽度的百分比)、`g_`(位置，同crop)、`x_`、`y_`(与边缘的距离)
+    /// - `quality`: `q_`或`Q_`(1-100)
+    /// - `format`: jpg/png/webp/gif/bmp
+    ///
+    /// ## 错误处理
+    /// * 如果不是图片处理、操作不支持或参数格式不正确，返回错误信息
+    pub fn parse(process: &str) -> Result<OssImageProcess, SvcError> {
+        let max_side = get_app_config()?.oss.image.max_side;
+        let mut parts = process.split('/').filter(|part| !part.is_empty());
+        if parts.next() != Some(PROCESS_PREFIX) {
+            return Err(SvcError::Runtime(anyhow!("只支持图片处理: {}", process)));
+        }
+
+        let mut image_process = OssImageProcess::default();
+        for part in parts {
+            let (action, args) = part.split_once(',').unwrap_or((part, ""));
+            match action {
+                "resize" => {
+                    let params = Self::parse_params(action, args)?;
+                    let resize = Self::parse_resize(&params, max_side)?;
+                    image_process.actions.push(OssImageAction::Resize(resize));
+                }
+                "crop" => {
+                    let params = Self::parse_params(action, args)?;
+                    let crop = Self::parse_crop(&params, max_side)?;
+                    image_process.actions.push(OssImageAction::Crop(crop));
+                }
+                "rotate" => {
+                    let degree = Self::parse_u32(action, "", args)?;
+                    if degree % 90 != 0 {
+                        return Err(SvcError::Runtime(anyhow!(
+                            "rotate只支持90的倍数: {}",
+                            degree
+                        )));
+                    }
+                    image_process
+                        .actions
+                        .push(OssImageAction::Rotate(degree % 360));
+                }
+                WATERMARK_ACTION => {
+                    let params = Self::parse_params(action, args)?;
+                    let watermark = Self::parse_watermark(&params, max_side)?;
+                    image_process
+                        .actions
+                        .push(OssImageAction::Watermark(watermark));
+                }
+                "quality" => {
+                    for (key, value) in Self::parse_params(action, args)? {
+                        match key {
+                            "q" | "Q" => {
+                                let quality = Self::parse_u32(action, key, value)?;
+                                if !(1..=100).contains(&quality) {
+                                    return Err(SvcError::Runtime(anyhow!(
+                                        "quality的范围为1-100: {}",
+                                        quality
+                                    )));
+                                }
+                                image_process.quality = Some(quality as u8);
+                            }
+                            _ => return Err(Self::unknown_param(action, key)),
+                        }
+                    }
+                }
+                "format" => {
+                    image_process.format =
+                        Some(OssImageFormat::from_str(args).map_err(|_| {
+                            SvcError::Runtime(anyhow!("不支持的输出格式: {}", args))
+                        })?);
+                }
+                _ => return Err(SvcError::Runtime(anyhow!("不支持的图片处理: {}", action))),
+            }
+        }
+        Ok(image_process)
+    }
+
+    /// # 是否可以处理该类型的图片
+    pub fn is_processable(content_type: &str) -> bool {
+        PROCESSABLE_TYPES.contains(&OssMimeSvc::get_essence(content_type).as_str())
+    }
+
+    /// # 添加存储桶的默认水印
+    ///
+    /// 在请求的处理参数最后添加水印操作，没有请求处理参数时只添加水印
+    ///
+    /// ## 参数
+    /// * `image_process` - 请求的处理参数及解析结果
+    /// * `watermark` - 存储桶的水印参数，格式与 `watermark` 操作的参数相同，如 `text_5ZOB54mM,g_se,t_60`
+    ///
+    /// ## 返回值
+    /// * `Ok((String, OssImageProcess))` - 返回添加水印后的处理参数及解析结果
+    /// * `Err(SvcError)` - 存储桶的水印参数格式不正确，返回错误信息
+    pub fn add_watermark(
+        image_process: Option<(String, OssImageProcess)>,
+        watermark: &str,
+    ) -> Result<(String, OssImageProcess), SvcError> {
+        let max_side = get_app_config()?.oss.image.max_side;
+        let params = Self::parse_params(WATERMARK_ACTION, watermark)?;
+        let watermark_action = OssImageAction::Watermark(Self::parse_watermark(&params, max_side)?);
+        let (process, mut image_process) = image_process
+            .unwrap_or_else(|| (PROCESS_PREFIX.to_string(), OssImageProcess::default()));
+        image_process.actions.push(watermark_action);
+        Ok((
+            format!("{}/{},{}", process, WATERMARK_ACTION, watermark),
+            image_process,
+        ))
+    }
+
+    /// # 加载存储桶的默认水印
+    ///
+    /// 用于打包下载等不经过预览的处理，返回只有水印操作的处理参数及读取的水印图片
+    ///
+    /// ## 参数
+    /// * `watermark` - 存储桶的水印参数
+    /// * `bucket_id` - 存储桶ID
+    /// * `accessor` - 访问者
+    ///
+    /// ## 返回值
+    /// * `Ok((OssImageProcess, HashMap<u64, Bytes>))` - 返回处理参数及以对象引用ID为键的水印图片
+    /// * `Err(SvcError)` - 水印参数格式不正确、水印图片不存在或读取失败，返回错误信息
+    pub async fn load_watermark(
+        watermark: &str,
+        bucket_id: u64,
+        accessor: &OssAccessor,
+    ) -> Result<(OssImageProcess, HashMap<u64, Bytes>), SvcError> {
+        let (_, process) = Self::add_watermark(None, watermark)?;
+        let watermark_objs = Self::get_watermark_objs(&process, bucket_id, accessor).await?;
+        let watermark_images = Self::read_watermark_images(&watermark_objs).await?;
+        Ok((process, watermark_images))
+    }
+
+    /// # 处理图片
+    ///
+    /// 先查找预先生成的派生对象(如缩略图)及缓存，都没有时从存储后端读取原图处理后写入缓存；
+    /// 以缓存的键作为ETag，客户端已缓存时不读取原图；有图片水印时，缓存的键包括水印图片的hash
+    ///
+    /// ## 参数
+    /// * `download` - 要预览的原图
+    /// * `derivation` - 原始的处理参数，用于查找派生对象
+    /// * `process` - 解析后的图片处理参数
+    /// * `headers` - 请求头，用于判断条件请求
+    /// * `accessor` - 访问者，用于判断是否可以使用水印图片
+    /// * `with_body` - 是否读取内容，为false时用于HEAD请求，只在有派生对象或缓存时返回大小
+    ///
+    /// ## 返回值
+    /// * `Ok(OssImageProcessed)` - 返回处理后的图片
+    /// * `Err(SvcError)` - 处理失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果原图超过配置的大小、尺寸，或处理后的尺寸超过配置的最大尺寸，返回错误信息
+    /// * 如果水印图片不存在，或不在原图所在的存储桶中且访问者无权下载，返回 NotFound 错误
+    pub async fn process(
+        download: &OssFileDownload,
+        derivation: &str,
+        process: &OssImageProcess,
+        headers: &HeaderMap,
+        accessor: &OssAccessor,
+        with_body: bool,
+    ) -> Result<OssImageProcessed, SvcError> {
+        let config = get_app_config()?.oss.image;
+        let format = Self::get_format(process, &download.content_type);
+        let watermark_objs =
+            Self::get_watermark_objs(process, download.bucket_id, accessor).await?;
+        // 早期的对象没有hash，以其在存储后端中的路径代替
+        let source = download.etag.as_deref().unwrap_or(&download.path);
+        let mut key_source = format!("{}\n{:?}", source, process);
+        for (_, watermark_obj) in &watermark_objs {
+            key_source.push('\n');
+            key_source.push_str(watermark_obj.hash.as_deref().unwrap_or(&watermark_obj.path));
+        }
+        let key = hex::encode(Sha256::digest(key_source));
+        let etag = Some(format!("\"{}\"", key));
+        let last_modified =
+            OssFileSvc::parse_http_date(&download.last_modified).unwrap_or_default();
+        let not_modified = OssFileSvc::is_not_modified(headers, &etag, last_modified);
+        let mut processed = OssImageProcessed {
+            content_type: format.content_type(),
+            etag: etag.unwrap_or_default(),
+            not_modified,
+            data: None,
+        };
+        if not_modified {
+            return Ok(processed);
+        }
+
+        if let Some(derived) =
+            OssObjSvc::get_derived::<DatabaseTransaction>(download.obj_id, derivation, None)
+                .await?
+                .extra
+        {
+            debug!("读取派生对象<{}>: {}", derived.id, derived.path);
+            let data = Self::read_all(&derived.path, derived.size.unwrap_or_default()).await?;
+            processed.data = Some(Bytes::from(data));
+            return Ok(processed);
+        }
+        let cache_path =
+            Self::cache_dir(&config)?
+                .join(&key[..2])
+                .join(format!("{}.{}", key, format.ext()));
+        if let Ok(data) = fs::read(&cache_path).await {
+            debug!("命中图片处理缓存: {}", cache_path.display());
+            processed.data = Some(Bytes::from(data));
+            return Ok(processed);
+        }
+        if !with_body {
+            return Ok(processed);
+        }
+
+        let source_data = Self::read_source(&download.path, download.file_size).await?;
+        let watermark_images = Self::read_watermark_images(&watermark_objs).await?;
+        let data =
+            Self::process_data(source_data, process.clone(), format, watermark_images).await?;
+        // 先写入临时文件再重命名，避免并发的请求读到写了一半的缓存
+        if let Err(e) = Self::write_cache(&cache_path, &data).await {
+            warn!("写入图片处理缓存失败<{}>: {}", cache_path.display(), e);
+        }
+        processed.data = Some(Bytes::from(data));
+        Ok(processed)
+    }
+
+    /// # 获取输出格式
+    ///
+    /// 未指定输出格式时与原图相同，原图的格式不支持输出时为PNG
+    pub fn get_format(process: &OssImageProcess, content_type: &str) -> OssImageFormat {
+        process
+            .format
+            .or_else(|| OssImageFormat::from_content_type(content_type))
+            .unwrap_or(OssImageFormat::Png)
+    }
+
+    /// # 读取原图
+    ///
+    /// ## 错误处理
+    /// * 如果原图超过配置的可以处理的最大大小，返回错误信息
+    pub async fn read_source(path: &str, file_size: u64) -> Result<Vec<u8>, SvcError> {
+        let max_source_size = get_app_config()?.oss.image.max_source_size;
+        if file_size > max_source_size.as_u64() {
+            return Err(SvcError::Runtime(anyhow!(
+                "图片超过可处理的最大大小: {}",
+                max_source_size
+            )));
+        }
+        Self::read_all(path, file_size).await
+    }
+
+    /// # 处理图片内容
+    ///
+    /// 解码、处理及编码都比较耗时，在阻塞线程中执行
+    ///
+    /// ## 参数
+    /// * `source_data` - 原图的内容
+    /// * `process` - 图片处理参数
+    /// * `format` - 输出格式
+    /// * `watermark_images` - 以对象引用ID为键的水印图片，没有图片水印时为空
+    ///
+    /// ## 返回值
+    /// * `Ok(Vec<u8>)` - 返回处理后的图片内容
+    /// * `Err(SvcError)` - 处理失败，返回错误信息
+    pub async fn process_data(
+        source_data: Vec<u8>,
+        process: OssImageProcess,
+        format: OssImageFormat,
+        watermark_images: HashMap<u64, Bytes>,
+    ) -> Result<Vec<u8>, SvcError> {
+        let config = get_app_config()?.oss.image;
+        tokio::task::spawn_blocking(move || {
+            Self::process_image(&source_data, &process, format, &watermark_images, &config)
+        })
+        .await
+        .map_err(|e| SvcError::Runtime(anyhow!("图片处理任务异常: {}", e)))?
+    }
+
+    /// 获取处理参数中的水印图片对象，水印图片需与原图在同一个存储桶中，或访问者可以下载，否则视为不存在
+    async fn get_watermark_objs(
+        process: &OssImageProcess,
+        bucket_id: u64,
+        accessor: &OssAccessor,
+    ) -> Result<Vec<(u64, OssObjVo)>, SvcError> {
+        let mut watermark_objs: Vec<(u64, OssObjVo)> = vec![];
+        for action in &process.actions {
+            let OssImageAction::Watermark(OssImageWatermark {
+                image: Some(obj_ref_id),
+                ..
+            }) = action
+            else {
+                continue;
+            };
+            if watermark_objs.iter().any(|(id, _)| id == obj_ref_id) {
+                continue;
+            }
+            let not_found = || SvcError::NotFound(format!("watermark image: {}", obj_ref_id));
+            let obj_ref_vo = OssObjRefSvc::get_by_id::<DatabaseTransaction>(*obj_ref_id, None)
+                .await?
+                .extra
+                .ok_or_else(not_found)?;
+            if obj_ref_vo.bucket_id != bucket_id {
+                let bucket_vo =
+                    OssBucketSvc::get_by_id::<DatabaseTransaction>(obj_ref_vo.bucket_id, None)
+                        .await?
+                        .extra
+                        .ok_or_else(not_found)?;
+                if !OssBucketSvc::can_read::<DatabaseTransaction>(
+                    &bucket_vo,
+                    obj_ref_vo.creator_id,
+                    accessor,
+                    None,
+                )
+                .await?
+                {
+                    return Err(not_found());
+                }
+            }
+            if !obj_ref_vo
+                .content_type
+                .as_deref()
+                .is_some_and(Self::is_processable)
+            {
+                return Err(SvcError::Runtime(anyhow!(
+                    "水印图片<{}>不是可处理的图片",
+                    obj_ref_id
+                )));
+            }
+            let obj_vo = OssObjSvc::get_by_id::<DatabaseTransaction>(obj_ref_vo.obj_id, None)
+                .await?
+                .extra
+                .ok_or_else(not_found)?;
+            watermark_objs.push((*obj_ref_id, obj_vo));
+        }
+        Ok(watermark_objs)
+    }
+
+    /// 读取水印图片的内容
+    async fn read_watermark_images(
+        watermark_objs: &[(u64, OssObjVo)],
+    ) -> Result<HashMap<u64, Bytes>, SvcError> {
+        let mut watermark_images = HashMap::new();
+        for (obj_ref_id, obj_vo) in watermark_objs {
+            let data = Self::read_source(&obj_vo.path, obj_vo.size.unwrap_or_default()).await?;
+            watermark_images.insert(*obj_ref_id, Bytes::from(data));
+        }
+        Ok(watermark_images)
+    }
+
+    /// 从存储后端读取整个对象的内容
+    async fn read_all(path: &str, size: u64) -> Result<Vec<u8>, SvcError> {
+        let mut data = Vec::with_capacity(size as usize);
+        let mut stream = get_storage_backend()?.get(path, None).await?;
+        while let Some(chunk) = stream.try_next().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    /// 获取图片处理的缓存目录
+    fn cache_dir(config: &ImageConfig) -> Result<PathBuf, SvcError> {
+        Ok(APP_ENV
+            .get()
+            .ok_or(EnvError::GetAppEnv())?
+            .app_dir
+            .join(&config.cache_dir))
+    }
+
+    /// 写入缓存文件
+    async fn write_cache(cache_path: &PathBuf, data: &[u8]) -> Result<(), SvcError> {
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let temp_path = cache_path.with_extension(format!("{}.tmp", get_id_worker()?.next_id()?));
+        fs::write(&temp_path, data).await?;
+        if let Err(e) = fs::rename(&temp_path, cache_path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// 解码原图，依次执行处理操作后编码为输出格式
+    fn process_image(
+        source_data: &[u8],
+        process: &OssImageProcess,
+        format: OssImageFormat,
+        watermark_images: &HashMap<u64, Bytes>,
+        config: &ImageConfig,
+    ) -> Result<Vec<u8>, SvcError> {
+        let max_side = config.max_side;
+        let mut image = Self::decode(source_data, max_side)?;
+
+        for action in &process.actions {
+            image = match action {
+                OssImageAction::Resize(resize) => Self::resize(image, resize),
+                OssImageAction::Crop(crop) => Self::crop(image, crop)?,
+                OssImageAction::Rotate(90) => image.rotate90(),
+                OssImageAction::Rotate(180) => image.rotate180(),
+                OssImageAction::Rotate(270) => image.rotate270(),
+                OssImageAction::Rotate(_) => image,
+                OssImageAction::Watermark(watermark) => {
+                    Self::watermark(image, watermark, watermark_images, config)?
+                }
+            };
+            if image.width() > max_side || image.height() > max_side {
+                return Err(SvcError::Runtime(anyhow!(
+                    "处理后的图片超过最大尺寸: {}x{}",
+                    image.width(),
+                    image.height()
+                )));
+            }
+        }
+
+        let mut data = Cursor::new(Vec::new());
+        let result = match format {
+            OssImageFormat::Jpg => {
+                let quality = process.quality.unwrap_or(DEFAULT_QUALITY);
+                let encoder = JpegEncoder::new_with_quality(&mut data, quality);
+                // JPEG不支持透明通道
+                DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)
+            }
+            OssImageFormat::Png => image.write_to(&mut data, ImageFormat::Png),
+            _ => DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_to(&mut data, format.image_format()),
+        };
+        result.map_err(|e| SvcError::Runtime(anyhow!("编码图片失败: {}", e)))?;
+        Ok(data.into_inner())
+    }
+
+    /// 解码图片，宽高不能超过配置的最大尺寸
+    fn decode(data: &[u8], max_side: u32) -> Result<DynamicImage, SvcError> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(max_side);
+        limits.max_image_height = Some(max_side);
+        let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+        reader.limits(limits);
+        reader
+            .decode()
+            .map_err(|e| SvcError::Runtime(anyhow!("解码图片失败: {}", e)))
+    }
+
+    /// 缩放图片
+    fn resize(image: DynamicImage, resize: &OssImageResize) -> DynamicImage {
+        let (source_width, source_height) = (image.width(), image.height());
+        let (width, height) = match resize.percent {
+            Some(percent) => (
+                Self::scale(source_width, percent as f64 / 100.0),
+                Self::scale(source_height, percent as f64 / 100.0),
+            ),
+            None => (
+                resize.width.unwrap_or(source_width),
+                resize.height.unwrap_or(source_height),
+            ),
+        };
+        // 只指定了宽度或高度时，按指定的边等比缩放
+        let (width_ratio, height_ratio) = (
+            width as f64 / source_width as f64,
+            height as f64 / source_height as f64,
+        );
+        let ratio = match (resize.width, resize.height, resize.percent) {
+            (_, _, Some(_)) | (Some(_), Some(_), _) => match resize.mode {
+                OssImageResizeMode::Mfit | OssImageResizeMode::Fill => {
+                    width_ratio.max(height_ratio)
+                }
+                _ => width_ratio.min(height_ratio),
+            },
+            (Some(_), None, _) => width_ratio,
+            _ => height_ratio,
+        };
+        let exact = resize.percent.is_none()
+            && resize.width.is_some()
+            && resize.height.is_some()
+            && matches!(
+                resize.mode,
+                OssImageResizeMode::Fill | OssImageResizeMode::Pad | OssImageResizeMode::Fixed
+            );
+        if resize.limit
+            && (ratio > 1.0 || (exact && (width > source_width || height > source_height)))
+        {
+            return image;
+        }
+
+        let fit_width = Self::scale(source_width, ratio);
+        let fit_height = Self::scale(source_height, ratio);
+        match resize.mode {
+            OssImageResizeMode::Fill if exact => {
+                image.resize_to_fill(width, height, FilterType::CatmullRom)
+            }
+            OssImageResizeMode::Pad if exact => {
+                let resized = image.resize_exact(fit_width, fit_height, FilterType::CatmullRom);
+                let [r, g, b] = resize.color;
+                let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+                imageops::overlay(
+                    &mut canvas,
+                    &resized.to_rgba8(),
+                    (width.saturating_sub(fit_width) / 2) as i64,
+                    (height.saturating_sub(fit_height) / 2) as i64,
+                );
+                DynamicImage::ImageRgba8(canvas)
+            }
+            OssImageResizeMode::Fixed if exact => {
+                image.resize_exact(width, height, FilterType::CatmullRom)
+            }
+            _ => image.resize_exact(fit_width, fit_height, FilterType::CatmullRom),
+        }
+    }
+
+    /// 按比例计算边长，至少为1
+    fn scale(side: u32, ratio: f64) -> u32 {
+        ((side as f64 * ratio).round() as u32).max(1)
+    }
+
+    /// 裁剪图片，裁剪区域超出图片时裁剪到图片边缘
+    fn crop(image: DynamicImage, crop: &OssImageCrop) -> Result<DynamicImage, SvcError> {
+        let (image_width, image_height) = (image.width(), image.height());
+        let width = crop.width.unwrap_or(image_width).min(image_width);
+        let height = crop.height.unwrap_or(image_height).min(image_height);
+        let (center_x, center_y) = ((image_width - width) / 2, (image_height - height) / 2);
+        let (right, bottom) = (image_width - width, image_height - height);
+        let (origin_x, origin_y) = match crop.gravity {
+            OssImageGravity::Nw => (0, 0),
+            OssImageGravity::North => (center_x, 0),
+            OssImageGravity::Ne => (right, 0),
+            OssImageGravity::West => (0, center_y),
+            OssImageGravity::Center => (center_x, center_y),
+            OssImageGravity::East => (right, center_y),
+            OssImageGravity::Sw => (0, bottom),
+            OssImageGravity::South => (center_x, bottom),
+            OssImageGravity::Se => (right, bottom),
+        };
+        let (x, y) = (origin_x + crop.x, origin_y + crop.y);
+        if x >= image_width || y >= image_height {
+            return Err(SvcError::Runtime(anyhow!(
+                "裁剪的起点超出图片范围: {},{}",
+                x,
+                y
+            )));
+        }
+        let width = width.min(image_width - x);
+        let height = height.min(image_height - y);
+        Ok(image.crop_imm(x, y, width, height))
+    }
+
+    /// 添加水印，水印超出图片的部分被裁掉
+    fn watermark(
+        image: DynamicImage,
+        watermark: &OssImageWatermark,
+        watermark_images: &HashMap<u64, Bytes>,
+        config: &ImageConfig,
+    ) -> Result<DynamicImage, SvcError> {
+        let mut mark = match (&watermark.image, &watermark.text) {
+            (Some(obj_ref_id), _) => {
+                let data = watermark_images.get(obj_ref_id).ok_or_else(|| {
+                    SvcError::NotFound(format!("watermark image: {}", obj_ref_id))
+                })?;
+                Self::decode(data, config.max_side)?.to_rgba8()
+            }
+            (None, Some(text)) => Self::render_text(text, watermark, config)?,
+            (None, None) => return Ok(image),
+        };
+        if let Some(scale) = watermark.scale {
+            let width = Self::scale(image.width(), scale as f64 / 100.0);
+            let height = Self::scale(mark.height(), width as f64 / mark.width() as f64);
+            mark = imageops::resize(&mark, width, height, FilterType::CatmullRom);
+        }
+        if watermark.opacity < 100 {
+            for pixel in mark.pixels_mut() {
+                pixel[3] = (pixel[3] as u32 * watermark.opacity / 100) as u8;
+            }
+        }
+
+        let (width, height) = (image.width() as i64, image.height() as i64);
+        let (mark_width, mark_height) = (mark.width() as i64, mark.height() as i64);
+        let (left, top) = (watermark.x as i64, watermark.y as i64);
+        let (center_x, center_y) = ((width - mark_width) / 2, (height - mark_height) / 2);
+        let (right, bottom) = (width - mark_width - left, height - mark_height - top);
+        let (x, y) = match watermark.gravity {
+            OssImageGravity::Nw => (left, top),
+            OssImageGravity::North => (center_x, top),
+            OssImageGravity::Ne => (right, top),
+            OssImageGravity::West => (left, center_y),
+            OssImageGravity::Center => (center_x, center_y),
+            OssImageGravity::East => (right, center_y),
+            OssImageGravity::Sw => (left, bottom),
+            OssImageGravity::South => (center_x, bottom),
+            OssImageGravity::Se => (right, bottom),
+        };
+        let mut canvas = image.to_rgba8();
+        imageops::overlay(&mut canvas, &mark, x, y);
+        Ok(DynamicImage::ImageRgba8(canvas))
+    }
+
+    /// 以配置的字体将水印文字渲染为背景透明的图片
+    fn render_text(
+        text: &str,
+        watermark: &OssImageWatermark,
+        config: &ImageConfig,
+    ) -> Result<RgbaImage, SvcError> {
+        let font = Self::get_watermark_font(config)?;
+        let scale = PxScale::from(watermark.size as f32);
+        let scaled_font = font.as_scaled(scale);
+        let ascent = scaled_font.ascent();
+        let mut glyphs = vec![];
+        let mut caret = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let glyph_id = scaled_font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled_font.kern(previous, glyph_id);
+            }
+            glyphs.push(glyph_id.with_scale_and_position(scale, point(caret, ascent)));
+            caret += scaled_font.h_advance(glyph_id);
+            previous = Some(glyph_id);
+        }
+        let width = caret.ceil() as u32;
+        let height = (ascent - scaled_font.descent()).ceil() as u32;
+        if width == 0 || height == 0 || width > config.max_side || height > config.max_side {
+            return Err(SvcError::Runtime(anyhow!(
+                "水印文字的尺寸超出范围: {}x{}",
+                width,
+                height
+            )));
+        }
+
+        let [r, g, b] = watermark.color;
+        let mut canvas = RgbaImage::new(width, height);
+        for glyph in glyphs {
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                let x = bounds.min.x as i64 + x as i64;
+                let y = bounds.min.y as i64 + y as i64;
+                if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                    let pixel = canvas.get_pixel_mut(x as u32, y as u32);
+                    let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    *pixel = Rgba([r, g, b, pixel[3].max(alpha)]);
+                }
+            });
+        }
+        Ok(canvas)
+    }
+
+    /// 获取文字水印的字体，第一次使用时从配置的字体文件加载
+    fn get_watermark_font(config: &ImageConfig) -> Result<&'static FontVec, SvcError> {
+        if let Some(font) = WATERMARK_FONT.get() {
+            return Ok(font);
+        }
+        let watermark_font = config
+            .watermark_font
+            .as_ref()
+            .ok_or_else(|| SvcError::Runtime(anyhow!("未配置文字水印的字体")))?;
+        let font_path = APP_ENV
+            .get()
+            .ok_or(EnvError::GetAppEnv())?
+            .app_dir
+            .join(watermark_font);
+        let data = std::fs::read(&font_path).map_err(|e| {
+            SvcError::Runtime(anyhow!(
+                "读取文字水印的字体<{}>失败: {}",
+                font_path.display(),
+                e
+            ))
+        })?;
+        let font = FontVec::try_from_vec(data).map_err(|e| {
+            SvcError::Runtime(anyhow!(
+                "文字水印的字体<{}>格式错误: {}",
+                font_path.display(),
+                e
+            ))
+        })?;
+        Ok(WATERMARK_FONT.get_or_init(|| font))
+    }
+
+    /// 将 `k1_v1,k2_v2` 格式的参数解析为键值对
+    fn parse_params<'a>(action: &str, args: &'a str) -> Result<Vec<(&'a str, &'a str)>, SvcError> {
+        args.split(',')
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| {
+                arg.split_once('_')
+                    .ok_or_else(|| SvcError::Runtime(anyhow!("{}的参数格式错误: {}", action, arg)))
+            })
+            .collect()
+    }
+
+    /// 解析缩放参数
+    fn parse_resize(params: &[(&str, &str)], max_side: u32) -> Result<OssImageResize, SvcError> {
+        let mut resize = OssImageResize {
+            mode: OssImageResizeMode::default(),
+            width: None,
+            height: None,
+            percent: None,
+            limit: true,
+            color: [255, 255, 255],
+        };
+        let (mut longest, mut shortest) = (None, None);
+        for &(key, value) in params {
+            match key {
+                "m" => {
+                    resize.mode = OssImageResizeMode::from_str(value)
+                        .map_err(|_| SvcError::Runtime(anyhow!("不支持的缩放模式: {}", value)))?
+                }
+                "w" => resize.width = Some(Self::parse_side("resize", key, value, max_side)?),
+                "h" => resize.height = Some(Self::parse_side("resize", key, value, max_side)?),
+                "l" => longest = Some(Self::parse_side("resize", key, value, max_side)?),
+                "s" => shortest = Some(Self::parse_side("resize", key, value, max_side)?),
+                "p" => {
+                    let percent = Self::parse_u32("resize", key, value)?;
+                    if !(1..=1000).contains(&percent) {
+                        return Err(SvcError::Runtime(anyhow!(
+                            "resize的参数p的范围为1-1000: {}",
+                            percent
+                        )));
+                    }
+                    resize.percent = Some(percent);
+                }
+                "limit" => resize.limit = value != "0",
+                "color" => resize.color = Self::parse_color("resize", key, value)?,
+                _ => return Err(Self::unknown_param("resize", key)),
+            }
+        }
+        // 指定最长边相当于宽高都不超过该值，指定最短边相当于宽高都不小于该值
+        if resize.width.is_none() && resize.height.is_none() {
+            if let Some(longest) = longest {
+                resize.mode = OssImageResizeMode::Lfit;
+                (resize.width, resize.height) = (Some(longest), Some(longest));
+            } else if let Some(shortest) = shortest {
+                resize.mode = OssImageResizeMode::Mfit;
+                (resize.width, resize.height) = (Some(shortest), Some(shortest));
+            }
+        }
+        if resize.width.is_none() && resize.height.is_none() && resize.percent.is_none() {
+            return Err(SvcError::Runtime(anyhow!("resize未指定目标尺寸")));
+        }
+        Ok(resize)
+    }
+
+    /// 解析裁剪参数
+    fn parse_crop(params: &[(&str, &str)], max_side: u32) -> Result<OssImageCrop, SvcError> {
+        let mut crop = OssImageCrop {
+            width: None,
+            height: None,
+            x: 0,
+            y: 0,
+            gravity: OssImageGravity::default(),
+        };
+        for &(key, value) in params {
+            match key {
+                "w" => crop.width = Some(Self::parse_side("crop", key, value, max_side)?),
+                "h" => crop.height = Some(Self::parse_side("crop", key, value, max_side)?),
+                "x" => crop.x = Self::parse_u32("crop", key, value)?,
+                "y" => crop.y = Self::parse_u32("crop", key, value)?,
+                "g" => {
+                    crop.gravity = OssImageGravity::from_str(value)
+                        .map_err(|_| SvcError::Runtime(anyhow!("不支持的裁剪原点: {}", value)))?
+                }
+                _ => return Err(Self::unknown_param("crop", key)),
+            }
+        }
+        Ok(crop)
+    }
+
+    /// 解析水印参数
+    fn parse_watermark(
+        params: &[(&str, &str)],
+        max_side: u32,
+    ) -> Result<OssImageWatermark, SvcError> {
+        let mut watermark = OssImageWatermark {
+            text: None,
+            image: None,
+            color: [0, 0, 0],
+            size: DEFAULT_WATERMARK_SIZE,
+            opacity: 100,
+            scale: None,
+            gravity: OssImageGravity::Se,
+            x: DEFAULT_WATERMARK_MARGIN,
+            y: DEFAULT_WATERMARK_MARGIN,
+        };
+        for &(key, value) in params {
+            match key {
+                "text" => {
+                    let text = URL_SAFE_NO_PAD
+                        .decode(value.trim_end_matches('='))
+                        .ok()
+                        .and_then(|text| String::from_utf8(text).ok())
+                        .filter(|text| !text.trim().is_empty())
+                        .ok_or_else(|| {
+                            SvcError::Runtime(anyhow!("watermark的参数text格式错误: {}", value))
+                        })?;
+                    if text.chars().count() > MAX_WATERMARK_TEXT_LENGTH {
+                        return Err(SvcError::Runtime(anyhow!(
+                            "水印文字不能超过{}个字符",
+                            MAX_WATERMARK_TEXT_LENGTH
+                        )));
+                    }
+                    watermark.text = Some(text);
+                }
+                "image" => {
+                    watermark.image = Some(value.parse().map_err(|_| {
+                        SvcError::Runtime(anyhow!("watermark的参数image格式错误: {}", value))
+                    })?)
+                }
+                "color" => watermark.color = Self::parse_color(WATERMARK_ACTION, key, value)?,
+                "size" => {
+                    watermark.size = Self::parse_side(WATERMARK_ACTION, key, value, 1000)?;
+                }
+                "t" => {
+                    let opacity = Self::parse_u32(WATERMARK_ACTION, key, value)?;
+                    if opacity > 100 {
+                        return Err(SvcError::Runtime(anyhow!(
+                            "watermark的参数t的范围为0-100: {}",
+                            opacity
+                        )));
+                    }
+                    watermark.opacity = opacity;
+                }
+                "P" => watermark.scale = Some(Self::parse_side(WATERMARK_ACTION, key, value, 100)?),
+                "g" => {
+                    watermark.gravity = OssImageGravity::from_str(value)
+                        .map_err(|_| SvcError::Runtime(anyhow!("不支持的水印位置: {}", value)))?
+                }
+                "x" => watermark.x = Self::parse_offset(WATERMARK_ACTION, key, value, max_side)?,
+                "y" => watermark.y = Self::parse_offset(WATERMARK_ACTION, key, value, max_side)?,
+                _ => return Err(Self::unknown_param(WATERMARK_ACTION, key)),
+            }
+        }
+        match (&watermark.text, &watermark.image) {
+            (Some(_), Some(_)) => Err(SvcError::Runtime(anyhow!(
+                "watermark只能指定text或image其中之一"
+            ))),
+            (None, None) => Err(SvcError::Runtime(anyhow!("watermark未指定text或image"))),
+            _ => Ok(watermark),
+        }
+    }
+
+    /// 解析十六进制RGB颜色
+    fn parse_color(action: &str, key: &str, value: &str) -> Result<[u8; 3], SvcError> {
+        hex::decode(value)
+            .ok()
+            .and_then(|color| <[u8; 3]>::try_from(color).ok())
+            .ok_or_else(|| SvcError::Runtime(anyhow!("{}的参数{}格式错误: {}", action, key, value)))
+    }
+
+    /// 解析距离，范围为0至配置的最大尺寸
+    fn parse_offset(action: &str, key: &str, value: &str, max_side: u32) -> Result<u32, SvcError> {
+        let offset = Self::parse_u32(action, key, value)?;
+        if offset > max_side {
+            return Err(SvcError::Runtime(anyhow!(
+                "{}的参数{}的范围为0-{}: {}",
+                action,
+                key,
+                max_side,
+                offset
+            )));
+        }
+        Ok(offset)
+    }
+
+    /// 解析边长，范围为1至配置的最大尺寸
+    fn parse_side(action: &str, key: &str, value: &str, max_side: u32) -> Result<u32, SvcError> {
+        let side = Self::parse_u32(action, key, value)?;
+        if side == 0 || side > max_side {
+            return Err(SvcError::Runtime(anyhow!(
+                "{}的参数{}的范围为1-{}: {}",
+                action,
+                key,
+                max_side,
+                side
+            )));
+        }
+        Ok(side)
+    }
+
+    /// 解析数字参数
+    fn parse_u32(action: &str, key: &str, value: &str) -> Result<u32, SvcError> {
+        value
+            .parse()
+            .map_err(|_| SvcError::Runtime(anyhow!("{}的参数{}格式错误: {}", action, key, value)))
+    }
+
+    /// 不支持的参数
+    fn unknown_param(action: &str, key: &str) -> SvcError {
+        SvcError::Runtime(anyhow!("{}不支持参数: {}", action, key))
+    }
+}
+
+/// # 处理后的图片
+pub struct OssImageProcessed {
+    /// MIME类型
+    pub content_type: &'static str,
+    /// 强ETag，即带引号的缓存的键
+    pub etag: String,
+    /// 根据条件请求头判断未修改，应返回304状态码
+    pub not_modified: bool,
+    /// 图片内容，未修改或HEAD请求没有缓存时为None
+    pub data: Option<Bytes>,
+}
@@ -0,0 +1,302 @@
+use crate::app::{MimeMismatchPolicy, get_app_config};
+use anyhow::anyhow;
+use robotech::svc::SvcError;
+use std::str::FromStr;
+
+/// 未知类型时使用的MIME类型
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// 检测MIME类型时读取的文件头部的最大字节数
+pub const SNIFF_SIZE: usize = 8192;
+
+/// 浏览器会执行其中脚本的MIME类型，与扩展名对应的类型不完全相同时即认为不符
+const ACTIVE_TYPES: [&str; 5] = [
+    "text/html",
+    "application/xhtml+xml",
+    "image/svg+xml",
+    "text/xml",
+    "application/xml",
+];
+
+/// 可能是HTML页面的开头(WHATWG MIME Sniffing 标准中的HTML特征)，后面需紧跟空白或 `>`，以注释 `<!--` 开头的也认为是HTML
+const HTML_PATTERNS: [&[u8]; 16] = [
+    b"<!doctype html",
+    b"<html",
+    b"<head",
+    b"<script",
+    b"<iframe",
+    b"<h1",
+    b"<div",
+    b"<font",
+    b"<table",
+    b"<a",
+    b"<style",
+    b"<title",
+    b"<b",
+    b"<body",
+    b"<br",
+    b"<p",
+];
+
+/// # MIME类型服务
+///
+/// 根据扩展名确定MIME类型，先查找配置中的 `mime-types`，再查找内置的扩展名数据库，都没有时为 `application/octet-stream`；
+/// 可预览的MIME类型优先使用存储桶中设置的白名单，未设置时使用配置中的 `previewable-types`；
+/// 上传时根据文件头部的特征字节检测真实的MIME类型，与扩展名不符时按存储桶的策略处理
+pub struct OssMimeSvc;
+
+impl OssMimeSvc {
+    /// # 根据扩展名获取MIME类型
+    ///
+    /// 文本类型未指定字符集时补充 `charset=utf-8`
+    ///
+    /// ## 参数
+    /// * `ext` - 文件扩展名
+    ///
+    /// ## 返回值
+    /// * `Ok(String)` - 返回MIME类型
+    /// * `Err(SvcError)` - 获取配置失败，返回错误信息
+    pub fn get_content_type(ext: &Option<String>) -> Result<String, SvcError> {
+        let Some(ext) = ext else {
+            return Ok(OCTET_STREAM.to_string());
+        };
+        let ext = ext.to_lowercase();
+        let content_type = match get_app_config()?.oss.mime_types.get(&ext) {
+            Some(content_type) => content_type.clone(),
+            None => match mime_guess::from_ext(&ext).first_raw() {
+                Some(content_type) => content_type.to_string(),
+                None => return Ok(OCTET_STREAM.to_string()),
+            },
+        };
+        if content_type.starts_with("text/") && !content_type.contains("charset=") {
+            return Ok(format!("{}; charset=utf-8", content_type));
+        }
+        Ok(content_type)
+    }
+
+    /// # 判断MIME类型是否可预览
+    ///
+    /// ## 参数
+    /// * `content_type` - MIME类型，可以带参数(如 `text/plain; charset=utf-8`)
+    /// * `previewable_types` - 存储桶中设置的可预览的MIME类型(多个用逗号分隔)，未设置时使用配置中的默认值
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否可预览
+    /// * `Err(SvcError)` - 获取配置失败，返回错误信息
+    pub fn is_previewable(
+        content_type: &str,
+        previewable_types: &Option<String>,
+    ) -> Result<bool, SvcError> {
+        let essence = Self::get_essence(content_type);
+        let previewable = match previewable_types {
+            Some(previewable_types) => previewable_types
+                .split(',')
+                .any(|pattern| Self::is_matched(pattern, &essence)),
+            None => get_app_config()?
+                .oss
+                .previewable_types
+                .iter()
+                .any(|pattern| Self::is_matched(pattern, &essence)),
+        };
+        Ok(previewable)
+    }
+
+    /// # 根据文件头部的特征字节检测MIME类型
+    ///
+    /// 先检测图片、音视频、压缩包、文档等二进制格式，再检测HTML、SVG、XML等浏览器会执行其中脚本的文本格式；
+    /// 文本格式不使用 `infer` 的检测结果，它会将带XML声明的SVG检测为 `text/xml`
+    ///
+    /// ## 参数
+    /// * `head` - 文件头部，最多 `SNIFF_SIZE` 个字节
+    ///
+    /// ## 返回值
+    /// 返回检测出的MIME类型，无法检测(如纯文本)时返回None
+    pub fn sniff(head: &[u8]) -> Option<String> {
+        if let Some(kind) = infer::get(head)
+            && kind.matcher_type() != infer::MatcherType::Text
+        {
+            return Some(kind.mime_type().to_string());
+        }
+
+        // 跳过UTF-8的BOM及开头的空白
+        let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+        let start = head
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .unwrap_or(head.len());
+        let head = head[start..].to_ascii_lowercase();
+        // SVG可能以XML声明或注释(如编辑器生成的注释)开头
+        let is_svg = head.windows(b"<svg".len()).any(|window| window == b"<svg");
+        if is_svg && (head.starts_with(b"<svg") || head.starts_with(b"<!--")) {
+            return Some("image/svg+xml".to_string());
+        }
+        if head.starts_with(b"<?xml") {
+            let content_type = if is_svg {
+                "image/svg+xml"
+            } else {
+                "application/xml"
+            };
+            return Some(content_type.to_string());
+        }
+        if head.starts_with(b"<!--") {
+            return Some("text/html".to_string());
+        }
+        let is_html = HTML_PATTERNS.iter().any(|pattern| {
+            head.starts_with(pattern)
+                && head
+                    .get(pattern.len())
+                    .is_some_and(|byte| byte.is_ascii_whitespace() || *byte == b'>')
+        });
+        is_html.then(|| "text/html".to_string())
+    }
+
+    /// # 判断检测出的MIME类型与扩展名对应的MIME类型是否相符
+    ///
+    /// 扩展名对应的类型未知(`application/octet-stream`)或两者相同时相符；
+    /// 两者的顶级类型相同(如 `audio/flac` 与 `audio/x-flac`)且都不是浏览器会执行其中脚本的类型时也认为相符
+    ///
+    /// ## 参数
+    /// * `content_type` - 扩展名对应的MIME类型
+    /// * `sniffed_content_type` - 检测出的MIME类型
+    pub fn is_consistent(content_type: &str, sniffed_content_type: &str) -> bool {
+        let essence = Self::get_essence(content_type);
+        let sniffed_essence = Self::get_essence(sniffed_content_type);
+        if essence == OCTET_STREAM || essence == sniffed_essence {
+            return true;
+        }
+        let is_xml = |essence: &str| essence == "text/xml" || essence == "application/xml";
+        if is_xml(&essence) && is_xml(&sniffed_essence) {
+            return true;
+        }
+        if ACTIVE_TYPES.contains(&essence.as_str())
+            || ACTIVE_TYPES.contains(&sniffed_essence.as_str())
+        {
+            return false;
+        }
+        essence.split_once('/').map(|(top_level, _)| top_level)
+            == sniffed_essence
+                .split_once('/')
+                .map(|(top_level, _)| top_level)
+    }
+
+    /// # 获取MIME类型不符时的策略
+    ///
+    /// ## 参数
+    /// * `mime_mismatch_policy` - 存储桶中设置的策略，未设置时使用配置中的默认值
+    ///
+    /// ## 返回值
+    /// * `Ok(MimeMismatchPolicy)` - 返回策略
+    /// * `Err(SvcError)` - 获取配置失败或存储桶中设置的策略无效，返回错误信息
+    pub fn get_mismatch_policy(
+        mime_mismatch_policy: &Option<String>,
+    ) -> Result<MimeMismatchPolicy, SvcError> {
+        match mime_mismatch_policy {
+            Some(mime_mismatch_policy) => MimeMismatchPolicy::from_str(mime_mismatch_policy.trim())
+                .map_err(|_| {
+                    SvcError::Runtime(anyhow!("无效的MIME类型不符策略: {}", mime_mismatch_policy))
+                }),
+            None => Ok(get_app_config()?.oss.mime_mismatch_policy),
+        }
+    }
+
+    /// 获取不带参数的MIME类型(小写)
+    pub fn get_essence(content_type: &str) -> String {
+        content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+    }
+
+    /// 判断MIME类型是否匹配，支持 `*/*` 及 `image/*` 形式的通配
+    fn is_matched(pattern: &str, essence: &str) -> bool {
+        let pattern = pattern.trim().to_lowercase();
+        if pattern == "*/*" || pattern == essence {
+            return true;
+        }
+        match pattern.strip_suffix("/*") {
+            Some(top_level) => essence
+                .split_once('/')
+                .is_some_and(|(essence_top_level, _)| essence_top_level == top_level),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniff(head: &[u8]) -> Option<String> {
+        OssMimeSvc::sniff(head)
+    }
+
+    #[test]
+    fn sniff_html_disguised_as_image() {
+        for head in [
+            &b"<!DOCTYPE html><html><body></body></html>"[..],
+            b"<HTML>\n<script>alert(1)</script>",
+            b"  \r\n<script>alert(1)</script>",
+            b"\xEF\xBB\xBF<html>",
+            b"<!-- comment --><p>text</p>",
+            b"<body onload=\"alert(1)\">",
+        ] {
+            let sniffed = sniff(head);
+            assert_eq!(sniffed.as_deref(), Some("text/html"), "{:?}", head);
+            assert!(!OssMimeSvc::is_consistent("image/png", &sniffed.unwrap()));
+        }
+    }
+
+    #[test]
+    fn sniff_svg() {
+        for head in [
+            &b"<svg xmlns=\"http://www.w3.org/2000/svg\"><script/></svg>"[..],
+            b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+            b"<!-- Generator: editor -->\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+        ] {
+            assert_eq!(sniff(head).as_deref(), Some("image/svg+xml"), "{:?}", head);
+        }
+        // SVG与其它图片类型的顶级类型相同，但会执行脚本，所以不相符
+        assert!(!OssMimeSvc::is_consistent("image/png", "image/svg+xml"));
+        assert!(OssMimeSvc::is_consistent("image/svg+xml", "image/svg+xml"));
+    }
+
+    #[test]
+    fn sniff_xml() {
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?><root/>").as_deref(),
+            Some("application/xml")
+        );
+        assert!(OssMimeSvc::is_consistent(
+            "text/xml; charset=utf-8",
+            "application/xml"
+        ));
+        assert!(!OssMimeSvc::is_consistent("text/plain", "application/xml"));
+    }
+
+    #[test]
+    fn mislabeled_harmless_type() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+        assert_eq!(sniff(png).as_deref(), Some("image/png"));
+        // 顶级类型相同且都不会执行脚本时相符
+        assert!(OssMimeSvc::is_consistent("image/jpeg", "image/png"));
+        assert!(OssMimeSvc::is_consistent("audio/flac", "audio/x-flac"));
+        // 扩展名对应的类型未知时总是相符
+        assert!(OssMimeSvc::is_consistent(OCTET_STREAM, "image/png"));
+        // 顶级类型不同时不相符
+        assert!(!OssMimeSvc::is_consistent("application/pdf", "image/png"));
+    }
+
+    #[test]
+    fn sniff_empty_or_short_head() {
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(b"   \n"), None);
+        assert_eq!(sniff(b"\xEF\xBB\xBF"), None);
+        assert_eq!(sniff(b"hello world"), None);
+        // 特征后面没有空白或 `>` 时不认为是HTML
+        assert_eq!(sniff(b"<a"), None);
+        assert_eq!(sniff(b"<bold text"), None);
+        assert_eq!(sniff(b"<b>").as_deref(), Some("text/html"));
+    }
+}
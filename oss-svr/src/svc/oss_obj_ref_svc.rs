@@ -55,4 +55,26 @@ impl OssObjRefSvc {
         let query_dto = OssObjRefQueryDto::builder().bucket_id(bucket_id).build();
         Self::del_by_query_dto(query_dto, Some(db)).await
     }
+
+    /// # 根据对象ID查询对象引用列表
+    ///
+    /// ## 参数
+    /// * `obj_id` - 对象ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<Vec<OssObjRefVo>>)` - 查询成功，返回引用了该对象的所有对象引用
+    /// * `Err(SvcError)` - 查询失败，可能是数据库错误
+    #[db_unwrap]
+    #[log_call]
+    pub async fn list_by_obj_id<C>(
+        obj_id: u64,
+        #[skip_log] db: Option<&C>,
+    ) -> Result<Ro<Vec<OssObjRefVo>>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let query_dto = OssObjRefQueryDto::builder().obj_id(obj_id).build();
+        Self::list_by_query_dto(query_dto, Some(db)).await
+    }
 }
@@ -1,7 +1,8 @@
+use crate::storage::get_storage_backend;
 use anyhow::{Context, anyhow};
 use tracing::warn;
 use robotech::macros::svc;
-use std::{fs, io};
+use std::io;
 
 #[svc]
 pub struct OssObjSvc;
@@ -9,7 +10,8 @@ pub struct OssObjSvc;
 impl OssObjSvc {
     /// # 删除记录及文件
     ///
-    /// 根据提供的ID删除数据库中的相应记录，删除完成后会删除对象对应的文件，如果文件删除不成功则会回滚
+    /// 根据提供的ID删除数据库中的相应记录，删除完成后会删除对象对应的文件，如果文件删除不成功则会回滚；
+    /// 对象的派生对象(如缩略图)及其文件也一并删除
     ///
     /// ## 参数
     /// * `id` - 要删除的记录的ID
@@ -28,21 +30,56 @@ impl OssObjSvc {
     where
         C: ConnectionTrait,
     {
+        for derived in OssObjDao::find_by_source_id(id, db).await? {
+            Self::del_by_id(derived.id as u64, Some(db)).await?;
+            Self::delete_file(&derived.path).await?;
+        }
         let ro = Self::del_by_id(id, Some(db)).await?;
         if let Some(extra) = ro.extra.clone() {
-            let path = extra.path;
-            // 删除文件
-            if let Err(e) = fs::remove_file(&path) {
-                match e.kind() {
-                    io::ErrorKind::NotFound => {
-                        // 忽略文件不存在的错误
-                        warn!("删除文件时发现路径不正确或文件不存在：{path}");
-                    }
-                    _ => Err(SvcError::Runtime(anyhow!("删除文件失败"))).with_context(|| e)?,
+            Self::delete_file(&extra.path).await?;
+        }
+        Ok(ro)
+    }
+
+    /// 删除存储后端中的文件，忽略文件不存在的错误
+    async fn delete_file(path: &str) -> Result<(), SvcError> {
+        if let Err(e) = get_storage_backend()?.delete(path).await {
+            match e.kind() {
+                io::ErrorKind::NotFound => {
+                    // 忽略文件不存在的错误
+                    warn!("删除文件时发现路径不正确或文件不存在：{path}");
                 }
+                _ => Err(SvcError::Runtime(anyhow!("删除文件失败"))).with_context(|| e)?,
             }
         }
-        Ok(ro)
+        Ok(())
+    }
+
+    /// # 获取对象按指定处理参数生成的派生对象
+    ///
+    /// ## 参数
+    /// * `source_id` - 来源对象ID
+    /// * `derivation` - 处理参数
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<Vo>)` - 查询成功，如果派生对象存在，返回封装了Vo的Ro对象，如果不存在则返回对象的extra为None
+    /// * `Err(SvcError)` - 查询失败，可能是数据库错误
+    #[db_unwrap]
+    #[log_call]
+    pub async fn get_derived<C>(
+        source_id: u64,
+        derivation: &str,
+        #[skip_log] db: Option<&C>,
+    ) -> Result<Ro<OssObjVo>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let query_dto = OssObjQueryDto::builder()
+            .source_id(Some(source_id))
+            .derivation(Some(derivation.to_string()))
+            .build();
+        Self::get_by_query_dto(query_dto, Some(db)).await
     }
 
     /// # 删除孤立数据
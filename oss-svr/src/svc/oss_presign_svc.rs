@@ -0,0 +1,578 @@
+use crate::app::{PresignConfig, get_app_config};
+use crate::dto::oss_presign_dto::{OssPresignDto, OssPresignQueryDto};
+use crate::svc::{OssAccessor, OssBucketAclSvc, OssBucketSvc, OssObjRefSvc, OssPermission};
+use crate::vo::OssPresignVo;
+use anyhow::anyhow;
+use axum::http::{HeaderMap, Method, header};
+use hmac::{Hmac, KeyInit, Mac};
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use robotech::macros::db_unwrap;
+use robotech::ro::Ro;
+use robotech::svc::SvcError;
+use sea_orm::ConnectionTrait;
+use sha2::Sha256;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::time::Duration;
+use wheel_rs::time_utils::now_ts;
+
+/// 可以签名的请求方法
+const SIGNABLE_METHODS: [&str; 3] = ["GET", "POST", "PUT"];
+
+/// 可以签名的请求路径的前缀
+const SIGNABLE_PATH_PREFIX: &str = "/oss/file/";
+
+/// 生成预签名URL时路径中需要百分号编码的字符(非ASCII字符总是编码)，不编码路径分隔符 `/`
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// # 预签名URL校验通过的请求
+///
+/// 由校验层放入请求的扩展中，通过预签名URL上传时以签发者作为创建者
+#[derive(Debug, Clone)]
+pub struct OssPresigned {
+    /// 签发者的用户ID
+    pub user_id: u64,
+    /// 过期时间戳(秒)
+    pub expires: u64,
+}
+
+/// # 要签名的请求路径访问的目标
+#[derive(Debug, PartialEq, Eq)]
+pub enum OssPresignTarget {
+    /// 下载、预览、渲染预览或读取EXIF，签发者需要可以下载该对象引用的文件
+    Read(u64),
+    /// 上传，签发者需要可以上传到该存储桶
+    Write(String),
+}
+
+/// # 预签名URL服务
+///
+/// 预签名URL在请求路径后附加允许的请求方法、过期时间、签发者及可选的IP和请求体长度限制，
+/// 并以配置的密钥对这些参数计算HMAC-SHA256签名，在有效期内无需其它认证即可下载、预览或上传文件；
+/// 签名的是百分号解码后的请求路径，所以无论客户端如何编码路径都能校验通过
+pub struct OssPresignSvc;
+
+impl OssPresignSvc {
+    /// # 生成预签名URL
+    ///
+    /// 只能签名下载、预览、渲染预览、读取EXIF(`GET`)及上传(`POST`、`PUT`)的请求路径，
+    /// 且签发者需要可以访问该路径(见 `can_access`)，否则持有预签名URL的人可以访问签发者自己都无权访问的文件
+    ///
+    /// ## 参数
+    /// * `dto` - 请求方法、请求路径、有效期及可选的限制
+    /// * `current_user_id` - 当前用户ID，作为签发者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Some(Ro<OssPresignVo>))` - 生成成功时返回预签名URL，参数不正确时返回警告
+    /// * `Ok(None)` - 签发者无权访问要签名的请求路径
+    /// * `Err(SvcError)` - 未配置签名密钥、获取配置或查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn sign<C>(
+        dto: OssPresignDto,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Option<Ro<OssPresignVo>>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let PresignConfig {
+            secret,
+            default_expires,
+            max_expires,
+            ..
+        } = get_app_config()?.oss.presign;
+        let secret =
+            secret.ok_or_else(|| SvcError::Runtime(anyhow!("未配置预签名URL的签名密钥")))?;
+
+        let method = dto.method.trim().to_uppercase();
+        if !SIGNABLE_METHODS.contains(&method.as_str()) {
+            return Ok(Some(Ro::warn(format!(
+                "不支持签名的请求方法: {}",
+                dto.method
+            ))));
+        }
+        let target = match Self::decode_path(&dto.path) {
+            Some(path) if !dto.path.contains(['?', '#']) => {
+                Self::parse_target(&method, &path).map(|target| (path, target))
+            }
+            _ => None,
+        };
+        let Some((path, target)) = target else {
+            return Ok(Some(Ro::warn(format!(
+                "不支持签名的请求路径: {} {}",
+                method, dto.path
+            ))));
+        };
+        if let Some(ip) = &dto.ip
+            && ip.parse::<IpAddr>().is_err()
+        {
+            return Ok(Some(Ro::warn(format!("IP格式错误: {}", ip))));
+        }
+        let expires_in = dto
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(default_expires);
+        if expires_in.is_zero() || expires_in > max_expires {
+            return Ok(Some(Ro::warn(format!(
+                "有效期必须大于0且不能超过{}秒",
+                max_expires.as_secs()
+            ))));
+        }
+        if !Self::can_access(&target, current_user_id, Some(db)).await? {
+            return Ok(None);
+        }
+
+        let expires = now_ts()? / 1000 + expires_in.as_secs();
+        let query = OssPresignQueryDto {
+            method: Some(method.clone()),
+            expires: Some(expires),
+            user_id: Some(current_user_id),
+            ip: dto.ip,
+            max_content_length: dto.max_content_length,
+            signature: None,
+        };
+        let url = Self::build_url(&secret, &path, &query)?;
+
+        Ok(Some(Ro::success("生成成功".to_string()).extra(Some(
+            OssPresignVo {
+                url,
+                method,
+                expires,
+            },
+        ))))
+    }
+
+    /// # 判断签发者是否可以访问要签名的请求路径
+    ///
+    /// 以签发者作为访问者(不使用请求头中的角色)，与通过预签名URL访问时的判断相同:
+    /// - 下载、预览、渲染预览及读取EXIF需要可以下载对应的文件，对象引用不存在时视为不可以
+    /// - 上传需要存储桶存在，启用访问控制时还需要拥有存储桶的 `write` 权限
+    ///
+    /// ## 参数
+    /// * `target` - 要签名的请求路径访问的目标
+    /// * `current_user_id` - 当前用户ID，作为签发者
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(bool)` - 是否可以访问
+    /// * `Err(SvcError)` - 查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn can_access<C>(
+        target: &OssPresignTarget,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<bool, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let accessor = OssAccessor {
+            user_id: Some(current_user_id),
+            roles: vec![],
+            presigned: true,
+        };
+        match target {
+            OssPresignTarget::Read(obj_ref_id) => {
+                let Some(obj_ref_vo) = OssObjRefSvc::get_by_id(*obj_ref_id, Some(db)).await?.extra
+                else {
+                    return Ok(false);
+                };
+                let Some(bucket_vo) = OssBucketSvc::get_by_id(obj_ref_vo.bucket_id, Some(db))
+                    .await?
+                    .extra
+                else {
+                    return Ok(false);
+                };
+                OssBucketSvc::can_read(&bucket_vo, obj_ref_vo.creator_id, &accessor, Some(db)).await
+            }
+            OssPresignTarget::Write(bucket) => {
+                let Some(bucket_vo) = OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra
+                else {
+                    return Ok(false);
+                };
+                if !OssBucketAclSvc::is_enabled()? {
+                    return Ok(true);
+                }
+                OssBucketAclSvc::has_permission(
+                    &bucket_vo,
+                    &accessor,
+                    OssPermission::Write,
+                    Some(db),
+                )
+                .await
+            }
+        }
+    }
+
+    /// 根据请求方法及解码后的请求路径解析访问的目标，不是可以签名的接口时返回None
+    fn parse_target(method: &str, path: &str) -> Option<OssPresignTarget> {
+        let segments: Vec<&str> = path
+            .strip_prefix(SIGNABLE_PATH_PREFIX)?
+            .split('/')
+            .collect();
+        match (method, segments.as_slice()) {
+            ("GET", ["download" | "preview" | "render" | "exif", obj_id]) => {
+                // 与接口解析对象ID相同，数字ID加可选的文件扩展名后缀
+                let (obj_ref_id, ext) = obj_id.split_once('.').unwrap_or((obj_id, ""));
+                if obj_ref_id.is_empty()
+                    || !obj_ref_id.bytes().all(|b| b.is_ascii_digit())
+                    || !ext.bytes().all(|b| b.is_ascii_alphanumeric())
+                {
+                    return None;
+                }
+                obj_ref_id.parse().ok().map(OssPresignTarget::Read)
+            }
+            ("POST", ["upload" | "upload-batch", bucket]) if !bucket.is_empty() => {
+                Some(OssPresignTarget::Write(bucket.to_string()))
+            }
+            ("PUT", ["raw", bucket, name]) if !bucket.is_empty() && !name.is_empty() => {
+                Some(OssPresignTarget::Write(bucket.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// 百分号解码请求路径，解码后不是UTF-8时返回None
+    fn decode_path(path: &str) -> Option<String> {
+        percent_decode_str(path)
+            .decode_utf8()
+            .ok()
+            .map(|path| path.into_owned())
+    }
+
+    /// 以解码后的请求路径计算签名，返回百分号编码路径后的预签名URL
+    fn build_url(secret: &str, path: &str, query: &OssPresignQueryDto) -> Result<String, SvcError> {
+        let signature = Self::compute_signature(secret, path, query)?;
+        let mut url = format!(
+            "{}?x-oss-method={}&x-oss-expires={}&x-oss-user-id={}",
+            utf8_percent_encode(path, PATH_ENCODE_SET),
+            query.method.as_deref().unwrap_or_default(),
+            query.expires.unwrap_or_default(),
+            query.user_id.unwrap_or_default()
+        );
+        if let Some(ip) = &query.ip {
+            let _ = write!(url, "&x-oss-ip={}", ip);
+        }
+        if let Some(max_content_length) = &query.max_content_length {
+            let _ = write!(url, "&x-oss-max-content-length={}", max_content_length);
+        }
+        let _ = write!(url, "&x-oss-signature={}", signature);
+        Ok(url)
+    }
+
+    /// # 校验预签名URL
+    ///
+    /// 依次校验签名、过期时间、请求方法(签名为 `GET` 时也允许 `HEAD`)、IP及请求体长度
+    ///
+    /// ## 参数
+    /// * `method` - 请求方法
+    /// * `path` - 请求路径(不含查询参数，可以是百分号编码的)
+    /// * `query` - 预签名URL的查询参数
+    /// * `headers` - 请求头
+    /// * `connect_ip` - 连接的对端IP
+    ///
+    /// ## 返回值
+    /// * `Ok(OssPresigned)` - 校验通过
+    /// * `Err(SvcError)` - 校验不通过，返回原因
+    pub fn verify(
+        method: &Method,
+        path: &str,
+        query: &OssPresignQueryDto,
+        headers: &HeaderMap,
+        connect_ip: Option<IpAddr>,
+    ) -> Result<OssPresigned, SvcError> {
+        let PresignConfig {
+            secret,
+            trust_proxy_headers,
+            ..
+        } = get_app_config()?.oss.presign;
+        let secret =
+            secret.ok_or_else(|| SvcError::Runtime(anyhow!("未配置预签名URL的签名密钥")))?;
+        Self::verify_with_secret(
+            &secret,
+            trust_proxy_headers,
+            method,
+            path,
+            query,
+            headers,
+            connect_ip,
+        )
+    }
+
+    /// 以指定的签名密钥校验预签名URL，见 `verify`
+    fn verify_with_secret(
+        secret: &str,
+        trust_proxy_headers: bool,
+        method: &Method,
+        path: &str,
+        query: &OssPresignQueryDto,
+        headers: &HeaderMap,
+        connect_ip: Option<IpAddr>,
+    ) -> Result<OssPresigned, SvcError> {
+        let (Some(signed_method), Some(expires), Some(user_id), Some(signature)) = (
+            &query.method,
+            query.expires,
+            query.user_id,
+            &query.signature,
+        ) else {
+            return Err(SvcError::Runtime(anyhow!("预签名URL的参数不完整")));
+        };
+
+        let signature =
+            hex::decode(signature).map_err(|_| SvcError::Runtime(anyhow!("签名格式错误")))?;
+        let path = Self::decode_path(path)
+            .ok_or_else(|| SvcError::Runtime(anyhow!("请求路径格式错误")))?;
+        Self::new_mac(secret, &path, query)?
+            .verify_slice(&signature)
+            .map_err(|_| SvcError::Runtime(anyhow!("签名不正确")))?;
+
+        if now_ts()? / 1000 > expires {
+            return Err(SvcError::Runtime(anyhow!("预签名URL已过期")));
+        }
+        let (method, signed_method) = (method.as_str(), signed_method.as_str());
+        if method != signed_method && !(method == "HEAD" && signed_method == "GET") {
+            return Err(SvcError::Runtime(anyhow!("预签名URL不允许{}请求", method)));
+        }
+        if let Some(ip) = &query.ip {
+            let client_ip = Self::get_client_ip(headers, connect_ip, trust_proxy_headers);
+            if client_ip.is_none_or(|client_ip| ip.parse::<IpAddr>().ok() != Some(client_ip)) {
+                return Err(SvcError::Runtime(anyhow!("预签名URL不允许此IP访问")));
+            }
+        }
+        if let Some(max_content_length) = query.max_content_length {
+            let content_length = headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            if content_length.is_none_or(|content_length| content_length > max_content_length) {
+                return Err(SvcError::Runtime(anyhow!(
+                    "请求体长度超出限制: {}",
+                    max_content_length
+                )));
+            }
+        }
+
+        Ok(OssPresigned { user_id, expires })
+    }
+
+    /// 计算签名，返回十六进制字符串
+    fn compute_signature(
+        secret: &str,
+        path: &str,
+        query: &OssPresignQueryDto,
+    ) -> Result<String, SvcError> {
+        let mac = Self::new_mac(secret, path, query)?;
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// 以请求方法、请求路径、过期时间、签发者、IP及请求体长度限制(每项一行)作为待签名的内容
+    fn new_mac(
+        secret: &str,
+        path: &str,
+        query: &OssPresignQueryDto,
+    ) -> Result<Hmac<Sha256>, SvcError> {
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            query.method.as_deref().unwrap_or_default(),
+            path,
+            query.expires.unwrap_or_default(),
+            query.user_id.unwrap_or_default(),
+            query.ip.as_deref().unwrap_or_default(),
+            query
+                .max_content_length
+                .map(|max_content_length| max_content_length.to_string())
+                .unwrap_or_default(),
+        );
+        let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(secret.as_bytes())
+            .map_err(|e| SvcError::Runtime(anyhow!("创建签名失败: {}", e)))?;
+        mac.update(string_to_sign.as_bytes());
+        Ok(mac)
+    }
+
+    /// 获取客户端IP，信任代理服务器的请求头时优先使用 `X-Forwarded-For` 中的第一个IP及 `X-Real-IP`
+    fn get_client_ip(
+        headers: &HeaderMap,
+        connect_ip: Option<IpAddr>,
+        trust_proxy_headers: bool,
+    ) -> Option<IpAddr> {
+        if trust_proxy_headers {
+            let forwarded_ip = headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .or_else(|| {
+                    headers
+                        .get("x-real-ip")
+                        .and_then(|value| value.to_str().ok())
+                })
+                .and_then(|value| value.trim().parse().ok());
+            if forwarded_ip.is_some() {
+                return forwarded_ip;
+            }
+        }
+        connect_ip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Query;
+    use axum::http::{HeaderValue, Uri};
+
+    const SECRET: &str = "test-secret";
+
+    fn new_query(method: &str, expires_in: i64) -> OssPresignQueryDto {
+        OssPresignQueryDto {
+            method: Some(method.to_string()),
+            expires: Some((now_ts().unwrap() / 1000).saturating_add_signed(expires_in)),
+            user_id: Some(1),
+            ..Default::default()
+        }
+    }
+
+    /// 生成预签名URL后按请求的方式解析出路径及查询参数再校验
+    fn verify_url(
+        url: &str,
+        method: &Method,
+        headers: &HeaderMap,
+        connect_ip: Option<IpAddr>,
+        trust_proxy_headers: bool,
+    ) -> Result<OssPresigned, SvcError> {
+        let uri: Uri = url.parse().unwrap();
+        let Query(query) = Query::<OssPresignQueryDto>::try_from_uri(&uri).unwrap();
+        OssPresignSvc::verify_with_secret(
+            SECRET,
+            trust_proxy_headers,
+            method,
+            uri.path(),
+            &query,
+            headers,
+            connect_ip,
+        )
+    }
+
+    #[test]
+    fn sign_and_verify_round_trip() {
+        let query = new_query("GET", 60);
+        let url = OssPresignSvc::build_url(SECRET, "/oss/file/download/123.jpg", &query).unwrap();
+        let presigned = verify_url(&url, &Method::GET, &HeaderMap::new(), None, false).unwrap();
+        assert_eq!(presigned.user_id, 1);
+        assert_eq!(Some(presigned.expires), query.expires);
+        // 签名为GET时也允许HEAD，不允许其它请求方法
+        assert!(verify_url(&url, &Method::HEAD, &HeaderMap::new(), None, false).is_ok());
+        assert!(verify_url(&url, &Method::PUT, &HeaderMap::new(), None, false).is_err());
+    }
+
+    #[test]
+    fn sign_and_verify_encoded_path() {
+        let query = new_query("PUT", 60);
+        let url =
+            OssPresignSvc::build_url(SECRET, "/oss/file/raw/bucket/中文 #1.pdf", &query).unwrap();
+        assert!(url.starts_with("/oss/file/raw/bucket/%E4%B8%AD%E6%96%87%20%231.pdf?"));
+        assert!(verify_url(&url, &Method::PUT, &HeaderMap::new(), None, false).is_ok());
+    }
+
+    #[test]
+    fn verify_expired() {
+        let url =
+            OssPresignSvc::build_url(SECRET, "/oss/file/download/123", &new_query("GET", -10))
+                .unwrap();
+        assert!(verify_url(&url, &Method::GET, &HeaderMap::new(), None, false).is_err());
+    }
+
+    #[test]
+    fn verify_ip() {
+        let query = OssPresignQueryDto {
+            ip: Some("10.0.0.1".to_string()),
+            ..new_query("GET", 60)
+        };
+        let url = OssPresignSvc::build_url(SECRET, "/oss/file/download/123", &query).unwrap();
+        let headers = HeaderMap::new();
+        assert!(verify_url(&url, &Method::GET, &headers, "10.0.0.1".parse().ok(), false).is_ok());
+        assert!(verify_url(&url, &Method::GET, &headers, "10.0.0.2".parse().ok(), false).is_err());
+        assert!(verify_url(&url, &Method::GET, &headers, None, false).is_err());
+
+        // 只有信任代理服务器的请求头时才使用X-Forwarded-For
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("10.0.0.1, 192.168.0.1"),
+        );
+        let connect_ip = "192.168.0.1".parse().ok();
+        assert!(verify_url(&url, &Method::GET, &headers, connect_ip, true).is_ok());
+        assert!(verify_url(&url, &Method::GET, &headers, connect_ip, false).is_err());
+    }
+
+    #[test]
+    fn verify_tampered() {
+        let url = OssPresignSvc::build_url(SECRET, "/oss/file/download/123", &new_query("GET", 60))
+            .unwrap();
+        let headers = HeaderMap::new();
+        // 修改路径、签发者或签名都不能通过校验
+        let tampered = url.replace("/download/123", "/download/124");
+        assert!(verify_url(&tampered, &Method::GET, &headers, None, false).is_err());
+        let tampered = url.replace("x-oss-user-id=1", "x-oss-user-id=2");
+        assert!(verify_url(&tampered, &Method::GET, &headers, None, false).is_err());
+        let (rest, signature) = url.rsplit_once('=').unwrap();
+        let first = if signature.starts_with('0') { '1' } else { '0' };
+        let tampered = format!("{}={}{}", rest, first, &signature[1..]);
+        assert!(verify_url(&tampered, &Method::GET, &headers, None, false).is_err());
+        // 以其它密钥签名的也不能通过校验
+        let query = new_query("GET", 60);
+        let other = OssPresignSvc::build_url("other", "/oss/file/download/123", &query).unwrap();
+        assert!(verify_url(&other, &Method::GET, &headers, None, false).is_err());
+    }
+
+    #[test]
+    fn parse_target() {
+        assert_eq!(
+            OssPresignSvc::parse_target("GET", "/oss/file/download/123.jpg"),
+            Some(OssPresignTarget::Read(123))
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("GET", "/oss/file/exif/123"),
+            Some(OssPresignTarget::Read(123))
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("POST", "/oss/file/upload/bucket"),
+            Some(OssPresignTarget::Write("bucket".to_string()))
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("PUT", "/oss/file/raw/bucket/中文.pdf"),
+            Some(OssPresignTarget::Write("bucket".to_string()))
+        );
+        // 请求方法与接口不符、不支持预签名的接口及格式错误的路径都不能签名
+        assert_eq!(
+            OssPresignSvc::parse_target("GET", "/oss/file/upload/bucket"),
+            None
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("POST", "/oss/file/archive"),
+            None
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("POST", "/oss/file/presign"),
+            None
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("GET", "/oss/file/download/abc"),
+            None
+        );
+        assert_eq!(
+            OssPresignSvc::parse_target("GET", "/oss/file/download/1/2"),
+            None
+        );
+        assert_eq!(OssPresignSvc::parse_target("GET", "/oss/bucket/123"), None);
+    }
+}
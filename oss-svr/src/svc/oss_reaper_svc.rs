@@ -0,0 +1,240 @@
+use crate::app::ReaperConfig;
+use crate::dao::OssObjDao;
+use crate::svc::{OssObjSvc, OssTusSvc, OssUploadSessionSvc};
+use anyhow::anyhow;
+use robotech::macros::db_unwrap;
+use robotech::svc::SvcError;
+use sea_orm::{ConnectionTrait, DatabaseTransaction};
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, warn};
+use wheel_rs::time_utils::now_ts;
+
+/// 每批清理的未完成对象数
+const REAP_BATCH_SIZE: u64 = 100;
+
+/// 正在运行的清理任务，重新加载配置时先停止旧的任务
+static REAPER_TASK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// # 清理结果
+#[derive(Debug, Default)]
+pub struct OssReapResult {
+    /// 清理的未完成对象数
+    pub incomplete_count: usize,
+    /// 清理的孤立对象数
+    pub orphaned_count: usize,
+    /// 清理的上传临时目录数
+    pub temp_dir_count: usize,
+}
+
+/// # 清理服务
+///
+/// 客户端断开连接或进程异常退出时，未完成(`is_completed = false`)的对象及已写入的部分文件会一直保留，
+/// 删除对象引用后也会留下没有被引用的孤立对象，由清理任务定时清理:
+/// - 超过宽限期仍未完成的对象及其文件，以及对应的分片上传会话或tus上传的临时目录；
+///   分片上传会话和tus上传按最后一次接收内容的时间(临时目录及其中文件的修改时间)计算，其它按创建时间计算
+/// - 超过宽限期没有被引用的已完成对象及其文件
+/// - 超过宽限期没有活动、且不属于未完成对象的上传临时目录
+pub struct OssReaperSvc;
+
+impl OssReaperSvc {
+    /// # 执行一次清理
+    ///
+    /// 未完成的对象分批查询，每个对象在单独的事务中删除，某个对象删除失败时记录日志并继续清理其它对象
+    ///
+    /// ## 参数
+    /// * `grace_period` - 宽限期
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(OssReapResult)` - 返回清理的数量
+    /// * `Err(SvcError)` - 查询失败，返回错误信息
+    #[db_unwrap]
+    pub async fn reap<C>(grace_period: Duration, db: Option<&C>) -> Result<OssReapResult, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let before_timestamp = now_ts()?.saturating_sub(grace_period.as_millis() as u64) as i64;
+        let before_time = SystemTime::now()
+            .checked_sub(grace_period)
+            .ok_or_else(|| SvcError::Runtime(anyhow!("宽限期过长")))?;
+        let mut result = OssReapResult::default();
+
+        // 清理过期的未完成对象，创建时间早于宽限期的才可能过期
+        let mut after_id = 0;
+        loop {
+            let objs =
+                OssObjDao::find_incomplete_before(before_timestamp, after_id, REAP_BATCH_SIZE, db)
+                    .await?;
+            let Some(last_obj) = objs.last() else {
+                break;
+            };
+            after_id = last_obj.id as u64;
+            for obj in objs {
+                let id = obj.id as u64;
+                // 分片上传会话或tus上传在宽限期内接收过内容的不清理
+                if Self::is_active(id, before_time).await? {
+                    continue;
+                }
+                match OssObjSvc::del_with_file::<DatabaseTransaction>(id, None).await {
+                    Ok(_) => {
+                        info!("清理未完成的对象<{}>: {}", id, obj.path);
+                        result.incomplete_count += 1;
+                    }
+                    Err(e) => warn!("清理未完成的对象<{}>失败: {}", id, e),
+                }
+            }
+        }
+
+        // 清理孤立对象
+        for obj in OssObjDao::find_orphaned_before(before_timestamp, db).await? {
+            let id = obj.id as u64;
+            match OssObjSvc::del_with_file::<DatabaseTransaction>(id, None).await {
+                Ok(_) => {
+                    info!("清理孤立对象<{}>: {}", id, obj.path);
+                    result.orphaned_count += 1;
+                }
+                Err(e) => warn!("清理孤立对象<{}>失败: {}", id, e),
+            }
+        }
+
+        // 清理上传临时目录，仍未完成的对象对应的目录保留
+        for temp_dir in [OssUploadSessionSvc::sessions_dir()?, OssTusSvc::uploads_dir()?] {
+            result.temp_dir_count += Self::reap_temp_dirs(&temp_dir, before_time, db).await?;
+        }
+
+        Ok(result)
+    }
+
+    /// 判断对象对应的分片上传会话或tus上传在指定时间之后是否接收过内容
+    async fn is_active(id: u64, before_time: SystemTime) -> Result<bool, SvcError> {
+        for temp_dir in [
+            OssUploadSessionSvc::sessions_dir()?,
+            OssTusSvc::uploads_dir()?,
+        ] {
+            if let Some(modified) = Self::last_modified(&temp_dir.join(id.to_string())).await?
+                && modified > before_time
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// 获取目录及其中文件的最后修改时间，目录不存在时返回None
+    ///
+    /// 追加内容只会更新文件的修改时间，不会更新目录的修改时间，所以需要检查其中的每个文件
+    async fn last_modified(dir: &Path) -> Result<Option<SystemTime>, SvcError> {
+        let mut last_modified = match fs::metadata(dir).await {
+            Ok(metadata) => metadata.modified()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = match fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            // 文件可能在遍历时被删除(如重命名分片的临时文件)
+            match entry.metadata().await {
+                Ok(metadata) => last_modified = last_modified.max(metadata.modified()?),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(last_modified))
+    }
+
+    /// 删除临时目录中不属于未完成对象、且最后修改时间早于指定时间的子目录，返回删除的数量
+    async fn reap_temp_dirs<C>(
+        temp_dir: &Path,
+        before_time: SystemTime,
+        db: &C,
+    ) -> Result<usize, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let mut entries = match fs::read_dir(temp_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut count = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let path = entry.path();
+            if Self::last_modified(&path)
+                .await?
+                .is_none_or(|modified| modified > before_time)
+            {
+                continue;
+            }
+            // 对象删除失败时保留，下次清理时与对象一起清理
+            if OssObjSvc::get_by_id(id, Some(db))
+                .await?
+                .extra
+                .is_some_and(|obj_vo| !obj_vo.is_completed)
+            {
+                continue;
+            }
+            match fs::remove_dir_all(&path).await {
+                Ok(_) => {
+                    info!("清理上传临时目录: {}", path.display());
+                    count += 1;
+                }
+                Err(e) => warn!("清理上传临时目录<{}>失败: {}", path.display(), e),
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// # 初始化清理任务
+///
+/// 按配置的间隔在后台定时执行清理，启动时立即执行一次；重新加载配置时会先停止旧的任务
+pub fn init_reaper(config: &ReaperConfig) -> anyhow::Result<()> {
+    let mut reaper_task = REAPER_TASK
+        .lock()
+        .map_err(|_| anyhow!("获取清理任务锁失败"))?;
+    if let Some(old_task) = reaper_task.take() {
+        old_task.abort();
+    }
+    if !config.enabled {
+        info!("清理任务未启用");
+        return Ok(());
+    }
+    if config.interval.is_zero() {
+        return Err(anyhow!("清理任务的执行间隔不能为0"));
+    }
+
+    let ReaperConfig {
+        interval,
+        grace_period,
+        ..
+    } = config.clone();
+    *reaper_task = Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match OssReaperSvc::reap::<DatabaseTransaction>(grace_period, None).await {
+                Ok(result) => info!("清理完成: {:?}", result),
+                Err(e) => error!("清理失败: {}", e),
+            }
+        }
+    }));
+    info!("启动清理任务，执行间隔: {:?}，宽限期: {:?}", interval, grace_period);
+    Ok(())
+}
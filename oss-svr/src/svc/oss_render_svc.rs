@@ -0,0 +1,349 @@
+use crate::app::{RenderConfig, get_app_config};
+use crate::storage::get_storage_backend;
+use crate::svc::{OssFileDownload, OssFileSvc, OssMimeSvc};
+use anyhow::anyhow;
+use axum::http::HeaderMap;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use futures_util::TryStreamExt;
+use pulldown_cmark::{Options, Parser, html};
+use robotech::svc::SvcError;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::sync::LazyLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+/// 渲染后页面的内容安全策略，禁止执行脚本及加载除图片外的资源，即使净化有遗漏也不会执行其中的脚本
+pub const RENDER_CSP: &str =
+    "sandbox; default-src 'none'; style-src 'unsafe-inline'; img-src https: http: data:";
+
+/// 渲染后页面的样式
+const PAGE_STYLE: &str = "body{margin:0 auto;max-width:980px;padding:24px;color:#24292f;line-height:1.6;\
+font-family:-apple-system,\"Segoe UI\",\"PingFang SC\",\"Microsoft YaHei\",sans-serif}\
+pre{padding:16px;overflow:auto;font-size:14px;line-height:1.45}\
+code,pre{font-family:ui-monospace,SFMono-Regular,Menlo,Consolas,monospace}\
+img{max-width:100%}\
+.table{overflow:auto}\
+table{border-collapse:collapse}\
+th,td{border:1px solid #d0d7de;padding:4px 10px;white-space:pre-wrap}\
+thead th{background:#f6f8fa}\
+nav{margin:12px 0}\
+nav a{margin-left:12px}";
+
+/// 内置的语法定义，第一次使用时加载
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// 内置的语法高亮主题，第一次使用时加载
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// # 渲染方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum OssRenderKind {
+    /// Markdown，渲染为净化后的HTML
+    Markdown,
+    /// 源代码及纯文本，按语法高亮，值为语法定义的名称
+    Code(String),
+    /// CSV/TSV，显示为分页的表格，值为分隔符
+    Table(u8),
+}
+
+/// # 渲染预览服务
+///
+/// 将Markdown渲染为净化后的HTML，源代码语法高亮，CSV/TSV显示为分页的表格(第一行为表头)，以完整的HTML页面返回；
+/// 渲染前检测文本的编码，GBK等非UTF-8编码的文件也能正确显示
+pub struct OssRenderSvc;
+
+impl OssRenderSvc {
+    /// # 获取渲染方式
+    ///
+    /// 先按扩展名判断Markdown、CSV/TSV及内置语法定义支持的源代码，都不是时按MIME类型判断，其它 `text/*` 类型按纯文本显示
+    ///
+    /// ## 参数
+    /// * `ext` - 文件扩展名
+    /// * `content_type` - MIME类型
+    ///
+    /// ## 返回值
+    /// 返回渲染方式，不支持渲染预览时返回None
+    pub fn get_kind(ext: &Option<String>, content_type: &str) -> Option<OssRenderKind> {
+        let ext = ext.as_deref().map(str::to_lowercase).unwrap_or_default();
+        match ext.as_str() {
+            "md" | "markdown" | "mdown" | "mkd" => return Some(OssRenderKind::Markdown),
+            "csv" => return Some(OssRenderKind::Table(b',')),
+            "tsv" | "tab" => return Some(OssRenderKind::Table(b'\t')),
+            _ => {}
+        }
+        if !ext.is_empty()
+            && let Some(syntax) = SYNTAX_SET.find_syntax_by_extension(&ext)
+        {
+            return Some(OssRenderKind::Code(syntax.name.clone()));
+        }
+        let essence = OssMimeSvc::get_essence(content_type);
+        match essence.as_str() {
+            "text/markdown" => Some(OssRenderKind::Markdown),
+            "text/csv" => Some(OssRenderKind::Table(b',')),
+            "text/tab-separated-values" => Some(OssRenderKind::Table(b'\t')),
+            _ if essence.starts_with("text/") => Some(OssRenderKind::Code(
+                SYNTAX_SET.find_syntax_plain_text().name.clone(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// # 渲染文件
+    ///
+    /// 以文件的hash、渲染方式、页码及查询参数计算ETag，客户端已缓存时不读取文件；渲染比较耗时，在阻塞线程中执行
+    ///
+    /// ## 参数
+    /// * `download` - 要渲染的文件
+    /// * `kind` - 渲染方式
+    /// * `page` - 表格的页码(从1开始)，其它渲染方式忽略
+    /// * `query` - 原始的查询参数，生成翻页链接时保留其中除页码外的参数(如预签名URL的签名)
+    /// * `headers` - 请求头，用于判断条件请求
+    ///
+    /// ## 返回值
+    /// * `Ok(OssRendered)` - 返回渲染后的页面
+    /// * `Err(SvcError)` - 渲染失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果文件超过配置的可渲染的最大大小，或配置的语法高亮主题不存在，返回错误信息
+    pub async fn render(
+        download: &OssFileDownload,
+        kind: OssRenderKind,
+        page: usize,
+        query: Option<&str>,
+        headers: &HeaderMap,
+    ) -> Result<OssRendered, SvcError> {
+        let config = get_app_config()?.oss.render;
+        let page = match kind {
+            OssRenderKind::Table(_) => page.max(1),
+            _ => 1,
+        };
+        // 早期的对象没有hash，以其在存储后端中的路径代替
+        let source = download.etag.as_deref().unwrap_or(&download.path);
+        let key = hex::encode(Sha256::digest(format!(
+            "{}\n{:?}\n{}\n{}\n{}\n{}",
+            source,
+            kind,
+            page,
+            query.unwrap_or_default(),
+            config.page_size,
+            config.theme
+        )));
+        let etag = Some(format!("\"{}\"", key));
+        let last_modified =
+            OssFileSvc::parse_http_date(&download.last_modified).unwrap_or_default();
+        let not_modified = OssFileSvc::is_not_modified(headers, &etag, last_modified);
+        let mut rendered = OssRendered {
+            etag: etag.unwrap_or_default(),
+            not_modified,
+            html: None,
+        };
+        if not_modified {
+            return Ok(rendered);
+        }
+
+        if download.file_size > config.max_source_size.as_u64() {
+            return Err(SvcError::Runtime(anyhow!(
+                "文件超过可渲染的最大大小: {}",
+                config.max_source_size
+            )));
+        }
+        let data = Self::read_all(&download.path, download.file_size).await?;
+        let title = download.file_name.clone();
+        let query = query.map(str::to_string);
+        let html = tokio::task::spawn_blocking(move || {
+            Self::render_data(&data, &kind, page, query.as_deref(), &title, &config)
+        })
+        .await
+        .map_err(|e| SvcError::Runtime(anyhow!("渲染任务异常: {}", e)))??;
+        rendered.html = Some(html);
+        Ok(rendered)
+    }
+
+    /// # 解码文本
+    ///
+    /// 有BOM时按BOM解码，是有效的UTF-8时按UTF-8解码，否则检测编码(如GBK、Big5、Shift_JIS)，无效的字节替换为U+FFFD
+    ///
+    /// ## 参数
+    /// * `data` - 文件内容
+    ///
+    /// ## 返回值
+    /// 返回解码后的文本及使用的编码
+    pub fn decode(data: &[u8]) -> (String, &'static Encoding) {
+        let encoding = match Encoding::for_bom(data) {
+            Some((encoding, _)) => encoding,
+            None if std::str::from_utf8(data).is_ok() => UTF_8,
+            None => {
+                let mut detector = EncodingDetector::new();
+                detector.feed(data, true);
+                detector.guess(None, true)
+            }
+        };
+        let (text, encoding, _) = encoding.decode(data);
+        (text.into_owned(), encoding)
+    }
+
+    /// 从存储后端读取整个文件的内容
+    async fn read_all(path: &str, size: u64) -> Result<Vec<u8>, SvcError> {
+        let mut data = Vec::with_capacity(size as usize);
+        let mut stream = get_storage_backend()?.get(path, None).await?;
+        while let Some(chunk) = stream.try_next().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    /// 解码文件内容，按渲染方式渲染后放入完整的HTML页面中
+    fn render_data(
+        data: &[u8],
+        kind: &OssRenderKind,
+        page: usize,
+        query: Option<&str>,
+        title: &str,
+        config: &RenderConfig,
+    ) -> Result<String, SvcError> {
+        let (text, _) = Self::decode(data);
+        let body = match kind {
+            OssRenderKind::Markdown => Self::render_markdown(&text),
+            OssRenderKind::Code(syntax_name) => {
+                Self::render_code(&text, syntax_name, &config.theme)?
+            }
+            OssRenderKind::Table(delimiter) => {
+                Self::render_table(&text, *delimiter, page, config.page_size, query)?
+            }
+        };
+        Ok(format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+            <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            Self::escape(title),
+            PAGE_STYLE,
+            body
+        ))
+    }
+
+    /// 将Markdown渲染为HTML，再去除脚本、事件属性、`javascript:` 链接等不安全的内容
+    fn render_markdown(text: &str) -> String {
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+        options.insert(Options::ENABLE_FOOTNOTES);
+        let mut markdown_html = String::new();
+        html::push_html(&mut markdown_html, Parser::new_ext(text, options));
+        format!("<article>\n{}</article>\n", ammonia::clean(&markdown_html))
+    }
+
+    /// 按语法定义高亮源代码，生成带内联样式的 `<pre>`，代码中的内容都已转义
+    fn render_code(text: &str, syntax_name: &str, theme: &str) -> Result<String, SvcError> {
+        let syntax = SYNTAX_SET
+            .find_syntax_by_name(syntax_name)
+            .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+        let theme = THEME_SET
+            .themes
+            .get(theme)
+            .ok_or_else(|| SvcError::Runtime(anyhow!("不支持的语法高亮主题: {}", theme)))?;
+        highlighted_html_for_string(text, &SYNTAX_SET, syntax, theme)
+            .map_err(|e| SvcError::Runtime(anyhow!("语法高亮失败: {}", e)))
+    }
+
+    /// 将CSV/TSV渲染为表格，第一行为表头，每页显示表头及 `page_size` 行，行的列数可以不同
+    fn render_table(
+        text: &str,
+        delimiter: u8,
+        page: usize,
+        page_size: usize,
+        query: Option<&str>,
+    ) -> Result<String, SvcError> {
+        let page_size = page_size.max(1);
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let mut records = reader.records();
+        let mut table = String::from("<div class=\"table\">\n<table>\n");
+        if let Some(header) = records.next() {
+            let header = header.map_err(|e| SvcError::Runtime(anyhow!("解析表格失败: {}", e)))?;
+            table.push_str("<thead><tr>");
+            for cell in header.iter() {
+                let _ = write!(table, "<th>{}</th>", Self::escape(cell));
+            }
+            table.push_str("</tr></thead>\n");
+        }
+        table.push_str("<tbody>\n");
+        let start = (page - 1).saturating_mul(page_size);
+        let end = start.saturating_add(page_size);
+        let mut total = 0;
+        for record in records {
+            let record = record.map_err(|e| SvcError::Runtime(anyhow!("解析表格失败: {}", e)))?;
+            if (start..end).contains(&total) {
+                table.push_str("<tr>");
+                for cell in record.iter() {
+                    let _ = write!(table, "<td>{}</td>", Self::escape(cell));
+                }
+                table.push_str("</tr>\n");
+            }
+            total += 1;
+        }
+        table.push_str("</tbody>\n</table>\n</div>\n");
+
+        let pages = total.div_ceil(page_size).max(1);
+        let mut nav = format!("<nav>第 {} / {} 页，共 {} 行", page, pages, total);
+        if page > 1 {
+            let _ = write!(
+                nav,
+                "<a href=\"{}\">上一页</a>",
+                Self::page_url(query, (page - 1).min(pages))
+            );
+        }
+        if page < pages {
+            let _ = write!(
+                nav,
+                "<a href=\"{}\">下一页</a>",
+                Self::page_url(query, page + 1)
+            );
+        }
+        nav.push_str("</nav>\n");
+        Ok(format!("{}{}{}", nav, table, nav))
+    }
+
+    /// 生成表格的翻页链接，保留除页码外的查询参数
+    fn page_url(query: Option<&str>, page: usize) -> String {
+        let page_param = format!("page={}", page);
+        let mut params: Vec<&str> = query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| !param.is_empty() && !param.starts_with("page="))
+            .collect();
+        params.push(&page_param);
+        Self::escape(&format!("?{}", params.join("&")))
+    }
+
+    /// 转义HTML的特殊字符
+    fn escape(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+/// # 渲染后的页面
+pub struct OssRendered {
+    /// 强ETag，即带引号的渲染结果的键
+    pub etag: String,
+    /// 根据条件请求头判断未修改，应返回304状态码
+    pub not_modified: bool,
+    /// 渲染后的HTML页面，未修改时为None
+    pub html: Option<String>,
+}
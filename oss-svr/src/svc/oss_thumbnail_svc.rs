@@ -0,0 +1,238 @@
+use crate::app::get_app_config;
+use crate::dto::oss_obj_dto::OssObjAddDto;
+use crate::storage::get_storage_backend;
+use crate::svc::{OssImageSvc, OssObjSvc};
+use crate::vo::OssObjVo;
+use bytes::Bytes;
+use futures_util::{future, stream};
+use idworker::get_id_worker;
+use robotech::svc::SvcError;
+use sea_orm::DatabaseTransaction;
+use std::collections::HashMap;
+use tracing::{debug, error, info, warn};
+
+/// # 待生成的缩略图
+///
+/// 新增对象引用时确定，新增对象引用的事务提交后再在后台生成，避免生成时读不到还未提交的对象
+#[derive(Debug)]
+pub struct OssThumbnailTask {
+    /// 原图的对象
+    pub obj_vo: OssObjVo,
+    /// 原图的MIME类型
+    pub content_type: String,
+    /// 生成缩略图的处理参数
+    pub derivations: Vec<String>,
+    /// 当前用户ID，作为缩略图的创建者
+    pub current_user_id: u64,
+}
+
+/// # 缩略图服务
+///
+/// 存储桶设置了缩略图的尺寸时，上传可处理的图片后在后台按各个尺寸生成缩略图。
+/// 缩略图作为原图的派生对象保存(`source_id` 为原图的对象ID，`derivation` 为生成缩略图的处理参数)，
+/// 不记录hash，不参与秒传和去重，删除原图时一并删除；
+/// 缩略图URL即带处理参数的预览URL，预览时直接读取派生对象，还未生成时再实时处理
+pub struct OssThumbnailSvc;
+
+impl OssThumbnailSvc {
+    /// # 获取生成缩略图的处理参数
+    ///
+    /// 将存储桶的缩略图尺寸(如 `200x200,400x300`，只有一个数字时为正方形)转换为 `image/resize,w_200,h_200` 形式的处理参数，
+    /// 即等比缩放到宽高都不超过该尺寸；格式不正确或超过最大尺寸的尺寸会被忽略
+    ///
+    /// ## 参数
+    /// * `thumbnail_sizes` - 存储桶的缩略图尺寸
+    ///
+    /// ## 返回值
+    /// * `Ok(Vec<String>)` - 返回去重后的处理参数，未设置缩略图尺寸时为空
+    /// * `Err(SvcError)` - 获取配置失败，返回错误信息
+    pub fn get_derivations(thumbnail_sizes: &Option<String>) -> Result<Vec<String>, SvcError> {
+        let Some(thumbnail_sizes) = thumbnail_sizes else {
+            return Ok(vec![]);
+        };
+        let max_side = get_app_config()?.oss.image.max_side;
+        Ok(Self::parse_derivations(thumbnail_sizes, max_side))
+    }
+
+    /// 将缩略图尺寸转换为处理参数，宽高都需在 `1..=max_side` 范围内，见 `get_derivations`
+    fn parse_derivations(thumbnail_sizes: &str, max_side: u32) -> Vec<String> {
+        let mut derivations = vec![];
+        for size in thumbnail_sizes
+            .split(',')
+            .map(str::trim)
+            .filter(|size| !size.is_empty())
+        {
+            let (width, height) = size.split_once(['x', 'X']).unwrap_or((size, size));
+            match (width.trim().parse::<u32>(), height.trim().parse::<u32>()) {
+                (Ok(width), Ok(height))
+                    if (1..=max_side).contains(&width) && (1..=max_side).contains(&height) =>
+                {
+                    let derivation = format!("image/resize,w_{},h_{}", width, height);
+                    if !derivations.contains(&derivation) {
+                        derivations.push(derivation);
+                    }
+                }
+                _ => warn!("忽略无效的缩略图尺寸: {}", size),
+            }
+        }
+        derivations
+    }
+
+    /// # 获取缩略图URL
+    ///
+    /// 缩略图URL为带处理参数的预览URL，多个用逗号分隔，没有缩略图时返回None
+    pub fn get_thumbnail_urls(preview_url: &str, derivations: &[String]) -> Option<String> {
+        if derivations.is_empty() {
+            return None;
+        }
+        let thumbnail_urls: Vec<String> = derivations
+            .iter()
+            .map(|derivation| format!("{}?x-oss-process={}", preview_url, derivation))
+            .collect();
+        Some(thumbnail_urls.join(","))
+    }
+
+    /// # 在后台生成缩略图
+    ///
+    /// 需在新增对象引用的事务提交后调用；不等待生成完成，生成失败时只记录日志，预览缩略图URL时会实时处理
+    ///
+    /// ## 参数
+    /// * `thumbnail_task` - 待生成的缩略图，为None时不做处理
+    pub fn spawn_generate(thumbnail_task: Option<OssThumbnailTask>) {
+        let Some(OssThumbnailTask {
+            obj_vo,
+            content_type,
+            derivations,
+            current_user_id,
+        }) = thumbnail_task
+        else {
+            return;
+        };
+        tokio::spawn(async move {
+            let obj_id = obj_vo.id;
+            if let Err(e) =
+                Self::generate(obj_vo, &content_type, derivations, current_user_id).await
+            {
+                error!("生成对象<{}>的缩略图失败: {}", obj_id, e);
+            }
+        });
+    }
+
+    /// # 生成缩略图
+    ///
+    /// 跳过已生成的缩略图(同一对象被多次引用时)，原图只读取一次；每个缩略图先写入存储后端再新增派生对象，
+    /// 新增失败(如并发生成了相同的缩略图)时删除刚写入的内容
+    ///
+    /// ## 参数
+    /// * `obj_vo` - 原图的对象
+    /// * `content_type` - 原图的MIME类型
+    /// * `derivations` - 生成缩略图的处理参数
+    /// * `current_user_id` - 当前用户ID，作为缩略图的创建者
+    ///
+    /// ## 返回值
+    /// * `Ok(())` - 生成成功
+    /// * `Err(SvcError)` - 读取原图、处理或写入失败，返回错误信息
+    pub async fn generate(
+        obj_vo: OssObjVo,
+        content_type: &str,
+        derivations: Vec<String>,
+        current_user_id: u64,
+    ) -> Result<(), SvcError> {
+        let mut missing_derivations = vec![];
+        for derivation in derivations {
+            let derived =
+                OssObjSvc::get_derived::<DatabaseTransaction>(obj_vo.id, &derivation, None)
+                    .await?
+                    .extra;
+            if derived.is_none() {
+                missing_derivations.push(derivation);
+            }
+        }
+        if missing_derivations.is_empty() {
+            return Ok(());
+        }
+
+        let storage_backend = get_storage_backend()?;
+        let source_data =
+            OssImageSvc::read_source(&obj_vo.path, obj_vo.size.unwrap_or_default()).await?;
+        for derivation in missing_derivations {
+            let process = OssImageSvc::parse(&derivation)?;
+            let format = OssImageSvc::get_format(&process, content_type);
+            let data =
+                OssImageSvc::process_data(source_data.clone(), process, format, HashMap::new())
+                    .await?;
+
+            let derived_id = get_id_worker()?.next_id()?;
+            let path = format!("{}-{}", obj_vo.path, derived_id);
+            let body = Box::pin(stream::once(future::ready(Ok(Bytes::from(data)))));
+            let size = storage_backend.put(&path, body).await?;
+            let oss_obj_add_dto = OssObjAddDto::builder()
+                .id(derived_id)
+                .path(path.clone())
+                .size(Some(size))
+                .content_type(Some(format.content_type().to_string()))
+                .source_id(Some(obj_vo.id))
+                .derivation(Some(derivation.clone()))
+                .is_completed(true)
+                ._current_user_id(current_user_id)
+                .build();
+            debug!("新增派生对象: {:?}", oss_obj_add_dto);
+            match OssObjSvc::add::<DatabaseTransaction>(oss_obj_add_dto, None).await {
+                Ok(_) => info!("生成对象<{}>的缩略图<{}>: {}", obj_vo.id, derivation, path),
+                Err(e) => {
+                    warn!("新增对象<{}>的缩略图<{}>失败: {}", obj_vo.id, derivation, e);
+                    if let Err(e) = storage_backend.delete(&path).await {
+                        warn!("删除缩略图文件<{}>失败: {}", path, e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_derivations() {
+        assert_eq!(
+            OssThumbnailSvc::parse_derivations("200x200, 400X300,100", 4096),
+            [
+                "image/resize,w_200,h_200",
+                "image/resize,w_400,h_300",
+                "image/resize,w_100,h_100",
+            ]
+        );
+        assert!(OssThumbnailSvc::parse_derivations("", 4096).is_empty());
+        assert!(OssThumbnailSvc::parse_derivations(" , ,", 4096).is_empty());
+    }
+
+    #[test]
+    fn parse_derivations_invalid() {
+        // 格式不正确的尺寸被忽略，不影响其它尺寸
+        assert_eq!(
+            OssThumbnailSvc::parse_derivations("abc,200x,x200,200*200,-1,1.5,200x200x2,300", 4096),
+            ["image/resize,w_300,h_300"]
+        );
+        assert!(OssThumbnailSvc::parse_derivations("0,0x100,100x0", 4096).is_empty());
+    }
+
+    #[test]
+    fn parse_derivations_oversized() {
+        assert_eq!(
+            OssThumbnailSvc::parse_derivations("4096,4097,100x5000,99999999999", 4096),
+            ["image/resize,w_4096,h_4096"]
+        );
+    }
+
+    #[test]
+    fn parse_derivations_duplicate() {
+        // 相同的尺寸(包括只有一个数字的正方形及不同写法)只生成一次
+        assert_eq!(
+            OssThumbnailSvc::parse_derivations("200,200x200, 200X200 ,200 x 200,300x200", 4096),
+            ["image/resize,w_200,h_200", "image/resize,w_300,h_200"]
+        );
+    }
+}
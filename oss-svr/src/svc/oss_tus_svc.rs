@@ -0,0 +1,587 @@
+use crate::app::get_app_config;
+use crate::svc::{OssBucketSvc, OssExifSvc, OssFileSvc, OssObjSvc, OssThumbnailSvc};
+use crate::vo::OssObjRefVo;
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use robotech::dao::begin_transaction;
+use robotech::macros::db_unwrap;
+use robotech::ro::Ro;
+use robotech::svc::SvcError;
+use sea_orm::{ConnectionTrait, DatabaseTransaction};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use tokio::fs;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Handle;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info, warn};
+use wheel_rs::time_utils::now_ts;
+
+/// tus上传在上传临时目录中的子目录名
+const TUS_DIR_NAME: &str = "tus";
+/// 上传信息文件名
+const INFO_FILE_NAME: &str = "info.json";
+/// 已接收内容的文件名
+const DATA_FILE_NAME: &str = "data";
+
+/// 正在处理PATCH请求的上传ID，同一个上传同时只允许一个PATCH请求
+static PATCHING_UPLOADS: LazyLock<Mutex<HashSet<u64>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// # tus上传信息
+///
+/// 保存在上传临时目录的 `tus/{id}/info.json` 中，已接收的内容保存在同一目录的 `data` 中
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OssTusUpload {
+    /// 上传ID(即未完成的对象ID)
+    id: u64,
+    /// 存储桶ID
+    bucket_id: u64,
+    /// 存储桶名称
+    bucket: String,
+    /// 文件名
+    name: String,
+    /// 文件大小(Upload-Length)
+    length: u64,
+    /// 文件Hash(通过Upload-Metadata的hash提供)
+    hash: Option<String>,
+    /// 对象在存储后端中的路径
+    path: String,
+    /// 创建者ID
+    creator_id: u64,
+    /// 创建时间戳
+    create_timestamp: u64,
+    /// 上传完成后生成的对象引用
+    obj_ref: Option<OssTusObjRef>,
+}
+
+/// # 上传完成后生成的对象引用
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OssTusObjRef {
+    /// 对象引用ID
+    pub id: u64,
+    /// 下载URL
+    pub download_url: String,
+    /// 预览URL
+    pub preview_url: Option<String>,
+}
+
+impl From<OssObjRefVo> for OssTusObjRef {
+    fn from(vo: OssObjRefVo) -> Self {
+        Self {
+            id: vo.id,
+            download_url: vo.download_url,
+            preview_url: vo.preview_url,
+        }
+    }
+}
+
+/// # tus上传的偏移量信息(HEAD请求的结果)
+#[derive(Debug)]
+pub struct OssTusOffset {
+    /// 已接收的大小(Upload-Offset)
+    pub offset: u64,
+    /// 文件大小(Upload-Length)
+    pub length: u64,
+    /// 上传完成后生成的对象引用
+    pub obj_ref: Option<OssTusObjRef>,
+}
+
+/// # tus的校验和(checksum扩展)
+#[derive(Debug)]
+pub struct OssTusChecksum {
+    /// 算法
+    pub algorithm: OssTusChecksumAlgorithm,
+    /// 期望的校验和
+    pub expected: Vec<u8>,
+}
+
+/// # tus支持的校验和算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OssTusChecksumAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl OssTusChecksumAlgorithm {
+    /// 支持的算法，用于 `Tus-Checksum-Algorithm` 头
+    pub const SUPPORTED: &'static str = "sha1,sha256";
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// 计算校验和的hasher
+enum ChecksumHasher {
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+}
+
+impl ChecksumHasher {
+    fn new(algorithm: OssTusChecksumAlgorithm) -> Self {
+        match algorithm {
+            OssTusChecksumAlgorithm::Sha1 => Self::Sha1(sha1::Sha1::new()),
+            OssTusChecksumAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha1(hasher) => hasher.finalize().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// 正在处理的PATCH请求，释放时从 `PATCHING_UPLOADS` 中移除上传ID
+struct OssTusPatching(u64);
+
+impl OssTusPatching {
+    /// 锁定上传，该上传正在处理其它PATCH请求时返回None
+    fn lock(id: u64) -> Result<Option<Self>, SvcError> {
+        let mut patching_uploads = PATCHING_UPLOADS
+            .lock()
+            .map_err(|_| SvcError::Runtime(anyhow!("获取上传锁失败")))?;
+        Ok(patching_uploads.insert(id).then_some(Self(id)))
+    }
+}
+
+impl Drop for OssTusPatching {
+    fn drop(&mut self) {
+        if let Ok(mut patching_uploads) = PATCHING_UPLOADS.lock() {
+            patching_uploads.remove(&self.0);
+        }
+    }
+}
+
+/// # 正在追加的内容
+///
+/// 确认(commit)或回滚(rollback)前被释放的(写入出错、请求被取消等)，在后台截断回追加前的偏移量，
+/// 截断完成后才释放上传的锁，避免下一个PATCH请求读到不完整的内容
+struct OssTusAppending {
+    /// 上传ID
+    id: u64,
+    /// 已接收内容的文件，确认或回滚后为None
+    file: Option<File>,
+    /// 追加前的偏移量
+    offset: u64,
+    /// 上传的锁
+    patching: Option<OssTusPatching>,
+}
+
+impl OssTusAppending {
+    /// 追加内容
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), SvcError> {
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// 确认追加的内容
+    async fn commit(&mut self) -> Result<(), SvcError> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush().await?;
+        }
+        self.file = None;
+        Ok(())
+    }
+
+    /// 丢弃追加的内容
+    async fn rollback(&mut self) -> Result<(), SvcError> {
+        if let Some(file) = self.file.as_mut() {
+            Self::truncate(file, self.offset).await?;
+        }
+        self.file = None;
+        Ok(())
+    }
+
+    /// 等待未完成的写入后截断到指定的偏移量
+    async fn truncate(file: &mut File, offset: u64) -> io::Result<()> {
+        file.flush().await?;
+        file.set_len(offset).await
+    }
+}
+
+impl Drop for OssTusAppending {
+    fn drop(&mut self) {
+        let Some(mut file) = self.file.take() else {
+            return;
+        };
+        let (id, offset, patching) = (self.id, self.offset, self.patching.take());
+        warn!(
+            "tus上传<{}>的PATCH请求未正常结束，截断回偏移量: {}",
+            id, offset
+        );
+        match Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = Self::truncate(&mut file, offset).await {
+                        warn!("tus上传<{}>截断失败: {}", id, e);
+                    }
+                    drop(patching);
+                });
+            }
+            Err(e) => warn!("tus上传<{}>截断失败: {}", id, e),
+        }
+    }
+}
+
+/// # PATCH请求的结果
+#[derive(Debug)]
+pub enum OssTusPatchResult {
+    /// 接收成功，返回新的偏移量
+    Appended(u64),
+    /// 接收成功且上传已完成，返回新的偏移量和生成的对象引用
+    Completed(u64, OssTusObjRef),
+    /// 请求的偏移量与已接收的大小不一致，返回已接收的大小
+    OffsetMismatch(u64),
+    /// 接收的内容与校验和不一致，本次接收的内容已丢弃
+    ChecksumMismatch,
+    /// 接收的内容超出了文件大小，本次接收的内容已丢弃
+    LengthExceeded,
+    /// 该上传正在处理其它PATCH请求
+    Locked,
+}
+
+/// # tus 1.0 断点续传协议服务
+///
+/// 支持 core、creation、checksum、termination 扩展，上传完成后与 `OssFileSvc::upload` 一样根据大小和Hash去重并生成对象引用
+pub struct OssTusSvc;
+
+impl OssTusSvc {
+    /// # 获取允许上传的最大文件大小(Tus-Max-Size)
+    pub fn max_size() -> Result<u64, SvcError> {
+        Ok(get_app_config()?.oss.upload_file_limit_size.as_u64())
+    }
+
+    /// # 新建上传(creation扩展)
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `length` - 文件大小(Upload-Length)
+    /// * `metadata` - 解码后的Upload-Metadata，文件名取自 `filename` 或 `name`，文件Hash取自 `hash`
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(u64)` - 新建成功，返回上传ID
+    /// * `Err(SvcError)` - 新建失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果存储桶不存在，返回 NotFound 错误
+    /// * 如果没有提供文件名，返回验证错误
+    #[db_unwrap(transaction_required)]
+    pub async fn create<C>(
+        bucket: &str,
+        length: u64,
+        metadata: HashMap<String, String>,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<u64, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let one_bucket = OssBucketSvc::get_by_name(bucket, Some(db))
+            .await?
+            .extra
+            .ok_or_else(|| SvcError::NotFound(format!("存储桶<{}>", bucket)))?;
+        let name = metadata
+            .get("filename")
+            .or_else(|| metadata.get("name"))
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| validator::ValidationError::new("上传文件没有文件名"))?
+            .to_string();
+
+        let (obj_id, path) =
+            OssFileSvc::add_incomplete_obj(bucket, current_user_id, Some(db)).await?;
+        let upload = OssTusUpload {
+            id: obj_id,
+            bucket_id: one_bucket.id,
+            bucket: bucket.to_string(),
+            name,
+            length,
+            hash: metadata.get("hash").map(|hash| hash.to_lowercase()),
+            path,
+            creator_id: current_user_id,
+            create_timestamp: now_ts()?,
+            obj_ref: None,
+        };
+        let upload_dir = Self::upload_dir(obj_id)?;
+        fs::create_dir_all(&upload_dir).await?;
+        File::create(upload_dir.join(DATA_FILE_NAME)).await?;
+        Self::save_upload(&upload).await?;
+        info!("新建tus上传: {:?}", upload);
+        Ok(obj_id)
+    }
+
+    /// # 获取上传的偏移量(HEAD请求)
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 上传ID
+    /// * `current_user_id` - 当前用户ID，只有上传的创建者可以获取偏移量
+    ///
+    /// ## 错误处理
+    /// * 如果上传不存在、不属于该存储桶或不是当前用户创建的，返回 NotFound 错误
+    pub async fn get_offset(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+    ) -> Result<OssTusOffset, SvcError> {
+        let upload = Self::load_upload(bucket, id, current_user_id).await?;
+        let offset = match &upload.obj_ref {
+            Some(_) => upload.length,
+            None => Self::received_size(id).await?,
+        };
+        Ok(OssTusOffset {
+            offset,
+            length: upload.length,
+            obj_ref: upload.obj_ref,
+        })
+    }
+
+    /// # 接收内容(PATCH请求)
+    ///
+    /// 从 `offset` 处开始追加接收的内容，连接中断时保留已接收的部分以便续传(提供了校验和时除外)，
+    /// 其它未成功的情况(超出文件大小、校验和不一致、写入出错、请求被取消等)都丢弃本次接收的内容，
+    /// 接收完整个文件后写入存储后端并生成对象引用；先取得上传的锁再读取上传信息，
+    /// 避免读取后其它请求完成了上传(删除了已接收的内容)
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 上传ID
+    /// * `current_user_id` - 当前用户ID，只有上传的创建者可以上传内容
+    /// * `offset` - 请求的偏移量(Upload-Offset)
+    /// * `checksum` - 本次请求内容的校验和(Upload-Checksum)
+    /// * `stream` - 本次请求的内容
+    ///
+    /// ## 错误处理
+    /// * 如果上传不存在、不属于该存储桶或不是当前用户创建的，返回 NotFound 错误
+    pub async fn patch<S, E>(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+        offset: u64,
+        checksum: Option<OssTusChecksum>,
+        stream: S,
+    ) -> Result<OssTusPatchResult, SvcError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let Some(patching) = OssTusPatching::lock(id)? else {
+            return Ok(OssTusPatchResult::Locked);
+        };
+        let upload = Self::load_upload(bucket, id, current_user_id).await?;
+        if upload.obj_ref.is_some() {
+            return Ok(OssTusPatchResult::OffsetMismatch(upload.length));
+        }
+        Self::append(&upload, offset, checksum, stream, patching).await
+    }
+
+    /// # 终止上传(termination扩展)
+    ///
+    /// 删除已接收的内容；如果上传尚未完成，同时删除预留的未完成对象
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 上传ID
+    /// * `current_user_id` - 当前用户ID，只有上传的创建者可以终止上传
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    #[db_unwrap(transaction_required)]
+    pub async fn terminate<C>(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<()>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let upload = Self::load_upload(bucket, id, current_user_id).await?;
+        if upload.obj_ref.is_none() {
+            OssObjSvc::del_with_file(id, Some(db)).await?;
+        }
+        fs::remove_dir_all(Self::upload_dir(id)?).await?;
+        info!("tus上传<{}>已终止", id);
+        Ok(Ro::success("终止上传成功".to_string()))
+    }
+
+    /// 追加接收的内容，如果接收完整个文件则完成上传，完成前一直持有上传的锁
+    async fn append<S, E>(
+        upload: &OssTusUpload,
+        offset: u64,
+        checksum: Option<OssTusChecksum>,
+        mut stream: S,
+        patching: OssTusPatching,
+    ) -> Result<OssTusPatchResult, SvcError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let received_size = Self::received_size(upload.id).await?;
+        if offset != received_size {
+            return Ok(OssTusPatchResult::OffsetMismatch(received_size));
+        }
+
+        let data_file_path = Self::upload_dir(upload.id)?.join(DATA_FILE_NAME);
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&data_file_path)
+            .await?;
+        let mut appending = OssTusAppending {
+            id: upload.id,
+            file: Some(file),
+            offset,
+            patching: Some(patching),
+        };
+        let mut hasher = checksum
+            .as_ref()
+            .map(|checksum| ChecksumHasher::new(checksum.algorithm));
+        let mut new_offset = offset;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    if hasher.is_some() {
+                        // 内容不完整无法校验，丢弃本次接收的内容
+                        appending.rollback().await?;
+                    } else {
+                        appending.commit().await?;
+                    }
+                    return Err(io::Error::other(e).into());
+                }
+            };
+            new_offset += chunk.len() as u64;
+            if new_offset > upload.length {
+                appending.rollback().await?;
+                return Ok(OssTusPatchResult::LengthExceeded);
+            }
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk);
+            }
+            appending.write(&chunk).await?;
+        }
+        if let (Some(hasher), Some(checksum)) = (hasher, checksum)
+            && hasher.finalize() != checksum.expected
+        {
+            warn!("tus上传<{}>校验和不一致，丢弃本次接收的内容", upload.id);
+            appending.rollback().await?;
+            return Ok(OssTusPatchResult::ChecksumMismatch);
+        }
+        appending.commit().await?;
+        debug!("tus上传<{}>接收到: {}/{}", upload.id, new_offset, upload.length);
+
+        if new_offset < upload.length {
+            return Ok(OssTusPatchResult::Appended(new_offset));
+        }
+        let obj_ref = Self::finish(upload.clone()).await?;
+        Ok(OssTusPatchResult::Completed(new_offset, obj_ref))
+    }
+
+    /// # 完成上传
+    ///
+    /// 将已接收的内容写入存储后端，根据大小和Hash去重，新增对象引用后删除已接收的内容；
+    /// 写入存储后端时不占用事务，写入完成后再在一个短事务中完成对象并新增对象引用
+    async fn finish(mut upload: OssTusUpload) -> Result<OssTusObjRef, SvcError> {
+        let data_file_path = Self::upload_dir(upload.id)?.join(DATA_FILE_NAME);
+        let file = File::open(&data_file_path).await?;
+        let exif_policy = OssExifSvc::get_bucket_policy(upload.bucket_id).await?;
+        let received = OssFileSvc::receive_and_write(
+            &upload.hash,
+            &Some(upload.length),
+            ReaderStream::new(file),
+            &upload.path,
+            exif_policy,
+        )
+        .await?;
+        let (obj_ref_ro, thumbnail_task) = OssFileSvc::finish_upload::<DatabaseTransaction>(
+            upload.bucket_id,
+            upload.id,
+            &upload.path,
+            received,
+            &upload.name,
+            upload.creator_id,
+            None,
+        )
+        .await?;
+        OssThumbnailSvc::spawn_generate(thumbnail_task);
+        let obj_ref_vo = obj_ref_ro
+            .extra
+            .ok_or_else(|| SvcError::Runtime(anyhow!("新增对象引用失败")))?;
+
+        // 保留上传信息，以便客户端完成后仍能通过HEAD请求获取对象引用
+        let obj_ref = OssTusObjRef::from(obj_ref_vo);
+        upload.obj_ref = Some(obj_ref.clone());
+        Self::save_upload(&upload).await?;
+        fs::remove_file(&data_file_path).await?;
+        info!("tus上传<{}>已完成", upload.id);
+        Ok(obj_ref)
+    }
+
+    /// # 获取存放所有tus上传的临时目录
+    ///
+    /// 每个上传占用其中以上传ID命名的子目录，上传完成后仍保留上传信息，以便客户端通过HEAD请求获取对象引用
+    pub fn uploads_dir() -> Result<PathBuf, SvcError> {
+        Ok(OssFileSvc::upload_temp_dir()?.join(TUS_DIR_NAME))
+    }
+
+    /// 获取上传的临时目录
+    fn upload_dir(id: u64) -> Result<PathBuf, SvcError> {
+        Ok(Self::uploads_dir()?.join(id.to_string()))
+    }
+
+    /// 获取已接收的大小
+    async fn received_size(id: u64) -> Result<u64, SvcError> {
+        let data_file_path = Self::upload_dir(id)?.join(DATA_FILE_NAME);
+        Ok(fs::metadata(data_file_path).await?.len())
+    }
+
+    /// 读取上传信息，上传不存在、不属于该存储桶或不是当前用户创建的时返回 NotFound 错误
+    async fn load_upload(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+    ) -> Result<OssTusUpload, SvcError> {
+        let info_file_path = Self::upload_dir(id)?.join(INFO_FILE_NAME);
+        let info_json = match fs::read(&info_file_path).await {
+            Ok(info_json) => info_json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(SvcError::NotFound(format!("tus上传: {}", id)));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let upload: OssTusUpload =
+            serde_json::from_slice(&info_json).map_err(|e| SvcError::Runtime(anyhow!(e)))?;
+        if upload.bucket != bucket || upload.creator_id != current_user_id {
+            return Err(SvcError::NotFound(format!("tus上传: {}", id)));
+        }
+        Ok(upload)
+    }
+
+    /// 保存上传信息
+    async fn save_upload(upload: &OssTusUpload) -> Result<(), SvcError> {
+        let info_json = serde_json::to_vec(upload).map_err(|e| SvcError::Runtime(anyhow!(e)))?;
+        fs::write(Self::upload_dir(upload.id)?.join(INFO_FILE_NAME), info_json).await?;
+        Ok(())
+    }
+}
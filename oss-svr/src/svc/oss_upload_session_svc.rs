@@ -0,0 +1,497 @@
+use crate::app::get_app_config;
+use crate::dto::oss_upload_session_dto::OssUploadSessionInitDto;
+use crate::svc::{OssBucketSvc, OssExifSvc, OssFileSvc, OssObjSvc, OssThumbnailSvc};
+use crate::vo::{OssObjRefVo, OssUploadPartVo, OssUploadSessionVo};
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use idworker::get_id_worker;
+use robotech::dao::begin_transaction;
+use robotech::macros::db_unwrap;
+use robotech::ro::Ro;
+use robotech::svc::SvcError;
+use sea_orm::{ConnectionTrait, DatabaseTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info};
+use wheel_rs::time_utils::now_ts;
+
+/// 上传会话在上传临时目录中的子目录名
+const SESSION_DIR_NAME: &str = "session";
+/// 会话信息文件名
+const SESSION_FILE_NAME: &str = "session.json";
+/// 分片文件的扩展名
+const PART_FILE_EXT: &str = "part";
+/// 分片序号的最大值
+const MAX_PART_NUMBER: u32 = 10000;
+
+/// 正在完成及正在保存分片的会话
+static LOCKED_SESSIONS: LazyLock<Mutex<OssUploadSessionLocks>> =
+    LazyLock::new(|| Mutex::new(OssUploadSessionLocks::default()));
+
+/// # 上传会话
+///
+/// 保存在上传临时目录的 `session/{id}/session.json` 中，分片保存在同一目录的 `{part_number}.part` 中
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OssUploadSession {
+    /// 会话ID(即未完成的对象ID)
+    id: u64,
+    /// 存储桶ID
+    bucket_id: u64,
+    /// 存储桶名称
+    bucket: String,
+    /// 文件名
+    name: String,
+    /// 文件大小
+    size: u64,
+    /// 文件Hash
+    hash: Option<String>,
+    /// 对象在存储后端中的路径
+    path: String,
+    /// 创建者ID
+    creator_id: u64,
+    /// 创建时间戳
+    create_timestamp: u64,
+}
+
+/// 正在完成及正在保存分片的会话
+///
+/// 同一个会话同时只允许一个完成(或中止)请求，完成时不再保存分片；
+/// 保存分片(重新读取会话并将临时文件重命名为分片文件)时也不能开始完成，避免分片保存到已完成的会话中
+#[derive(Debug, Default)]
+struct OssUploadSessionLocks {
+    /// 正在完成(或中止)的会话ID
+    completing: HashSet<u64>,
+    /// 正在保存分片的会话ID及正在保存的分片数
+    saving: HashMap<u64, usize>,
+}
+
+/// 会话的锁，释放时从 `LOCKED_SESSIONS` 中移除
+enum OssUploadSessionLock {
+    /// 正在完成(或中止)
+    Completing(u64),
+    /// 正在保存分片
+    Saving(u64),
+}
+
+impl OssUploadSessionLock {
+    /// 锁定会话以完成(或中止)，该会话正在完成或正在保存分片时返回验证错误
+    fn lock_completing(id: u64) -> Result<Self, SvcError> {
+        let mut locked_sessions = Self::locked_sessions()?;
+        if locked_sessions.completing.contains(&id) {
+            Err(validator::ValidationError::new("上传会话正在完成"))?;
+        }
+        if locked_sessions.saving.contains_key(&id) {
+            Err(validator::ValidationError::new("上传会话正在保存分片"))?;
+        }
+        locked_sessions.completing.insert(id);
+        Ok(Self::Completing(id))
+    }
+
+    /// 锁定会话以保存分片，同一个会话可以同时保存多个分片，该会话正在完成时返回验证错误
+    fn lock_saving(id: u64) -> Result<Self, SvcError> {
+        let mut locked_sessions = Self::locked_sessions()?;
+        if locked_sessions.completing.contains(&id) {
+            Err(validator::ValidationError::new("上传会话正在完成"))?;
+        }
+        *locked_sessions.saving.entry(id).or_default() += 1;
+        Ok(Self::Saving(id))
+    }
+
+    /// 判断会话是否正在完成，正在完成时返回验证错误
+    fn check(id: u64) -> Result<(), SvcError> {
+        if Self::locked_sessions()?.completing.contains(&id) {
+            Err(validator::ValidationError::new("上传会话正在完成"))?;
+        }
+        Ok(())
+    }
+
+    /// 获取正在完成及正在保存分片的会话
+    fn locked_sessions() -> Result<MutexGuard<'static, OssUploadSessionLocks>, SvcError> {
+        LOCKED_SESSIONS
+            .lock()
+            .map_err(|_| SvcError::Runtime(anyhow!("获取上传会话锁失败")))
+    }
+}
+
+impl Drop for OssUploadSessionLock {
+    fn drop(&mut self) {
+        let Ok(mut locked_sessions) = LOCKED_SESSIONS.lock() else {
+            return;
+        };
+        match self {
+            Self::Completing(id) => {
+                locked_sessions.completing.remove(id);
+            }
+            Self::Saving(id) => {
+                if let Some(count) = locked_sessions.saving.get_mut(id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        locked_sessions.saving.remove(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// # 分片上传会话服务
+///
+/// 用于大文件在不稳定网络下的断点续传:
+/// 1. 新建会话，预留一个未完成(`is_completed = false`)的对象
+/// 2. 按序号上传分片，可重复上传同一序号的分片
+/// 3. 查询已接收的分片，断线后只需补传缺失的分片
+/// 4. 完成会话，按序号合并分片写入存储后端并生成对象引用；或中止会话
+pub struct OssUploadSessionSvc;
+
+impl OssUploadSessionSvc {
+    /// # 新建上传会话
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `dto` - 文件名、文件大小及可选的文件Hash
+    /// * `current_user_id` - 当前用户ID
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssUploadSessionVo>)` - 新建成功，返回会话信息
+    /// * `Err(SvcError)` - 新建失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果存储桶不存在，返回警告信息
+    /// * 如果文件名为空或文件大小超出上传限制，返回验证错误
+    #[db_unwrap(transaction_required)]
+    pub async fn init<C>(
+        bucket: &str,
+        dto: OssUploadSessionInitDto,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<OssUploadSessionVo>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let one_bucket = match OssBucketSvc::get_by_name(bucket, Some(db)).await?.extra {
+            Some(bucket) => bucket,
+            None => return Ok(Ro::warn(format!("未找到存储桶<{}>", bucket))),
+        };
+        if dto.name.is_empty() {
+            Err(validator::ValidationError::new("上传文件没有文件名"))?;
+        }
+        let upload_file_limit_size = get_app_config()?.oss.upload_file_limit_size;
+        if dto.size > upload_file_limit_size.as_u64() {
+            Err(validator::ValidationError::new("上传文件大小超出限制"))?;
+        }
+
+        let (obj_id, path) = OssFileSvc::add_incomplete_obj(bucket, current_user_id, Some(db)).await?;
+        let session = OssUploadSession {
+            id: obj_id,
+            bucket_id: one_bucket.id,
+            bucket: bucket.to_string(),
+            name: dto.name,
+            size: dto.size,
+            hash: dto.hash.map(|hash| hash.to_lowercase()),
+            path,
+            creator_id: current_user_id,
+            create_timestamp: now_ts()?,
+        };
+        let session_dir = Self::session_dir(obj_id)?;
+        fs::create_dir_all(&session_dir).await?;
+        let session_json =
+            serde_json::to_vec(&session).map_err(|e| SvcError::Runtime(anyhow!(e)))?;
+        fs::write(session_dir.join(SESSION_FILE_NAME), session_json).await?;
+        info!("新建上传会话: {:?}", session);
+
+        Ok(Ro::success("新建上传会话成功".to_string())
+            .extra(Some(Self::to_vo(session, vec![]))))
+    }
+
+    /// # 上传分片
+    ///
+    /// 分片先写入本次请求独有的临时文件，写完后再重命名，避免中断或同时上传同一序号的分片时留下不完整的分片；
+    /// 重复上传同一序号的分片会覆盖之前的分片，会话正在完成时不再接收分片
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 会话ID
+    /// * `part_number` - 分片序号，从1开始
+    /// * `current_user_id` - 当前用户ID，只有会话的创建者可以上传分片
+    /// * `stream` - 分片内容
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssUploadSessionVo>)` - 上传成功，返回会话信息(包含已接收的分片)
+    /// * `Err(SvcError)` - 上传失败，返回错误信息
+    pub async fn upload_part<S, E>(
+        bucket: &str,
+        id: u64,
+        part_number: u32,
+        current_user_id: u64,
+        stream: S,
+    ) -> Result<Ro<OssUploadSessionVo>, SvcError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        if part_number < 1 || part_number > MAX_PART_NUMBER {
+            Err(validator::ValidationError::new("分片序号超出范围"))?;
+        }
+        let session = Self::load_session(bucket, id, current_user_id).await?;
+        OssUploadSessionLock::check(id)?;
+        let session_dir = Self::session_dir(id)?;
+        let part_file_path = session_dir.join(format!("{}.{}", part_number, PART_FILE_EXT));
+        let temp_file_path = session_dir.join(format!(
+            "{}.{}.{}.tmp",
+            part_number,
+            PART_FILE_EXT,
+            get_id_worker()?.next_id()?
+        ));
+
+        let part_size = match Self::write_part(&temp_file_path, session.size, stream).await {
+            Ok(part_size) => part_size,
+            Err(e) => {
+                fs::remove_file(&temp_file_path).await.ok();
+                return Err(e);
+            }
+        };
+        // 保存期间不能开始完成；获取锁之后再读取会话，写入分片期间会话可能已完成或中止
+        let saved = async {
+            let _saving = OssUploadSessionLock::lock_saving(id)?;
+            Self::load_session(bucket, id, current_user_id).await?;
+            fs::rename(&temp_file_path, &part_file_path).await?;
+            Ok::<(), SvcError>(())
+        }
+        .await;
+        if let Err(e) = saved {
+            fs::remove_file(&temp_file_path).await.ok();
+            return Err(e);
+        }
+        debug!("上传会话<{}>接收分片<{}>: {}字节", id, part_number, part_size);
+
+        let parts = Self::list_parts(&session_dir).await?;
+        Ok(Ro::success("上传分片成功".to_string()).extra(Some(Self::to_vo(session, parts))))
+    }
+
+    /// # 获取上传会话
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 会话ID
+    /// * `current_user_id` - 当前用户ID，只有会话的创建者可以获取会话
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssUploadSessionVo>)` - 返回会话信息(包含已接收的分片)
+    /// * `Err(SvcError)` - 会话不存在时返回 NotFound 错误
+    pub async fn get(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+    ) -> Result<Ro<OssUploadSessionVo>, SvcError> {
+        let session = Self::load_session(bucket, id, current_user_id).await?;
+        let parts = Self::list_parts(&Self::session_dir(id)?).await?;
+        Ok(Ro::success("获取上传会话成功".to_string()).extra(Some(Self::to_vo(session, parts))))
+    }
+
+    /// # 完成上传会话
+    ///
+    /// 按序号合并分片写入存储后端，校验文件大小和Hash，与 `OssFileSvc::upload` 一样根据大小和Hash去重，
+    /// 然后新增对象引用并删除会话的临时目录；同一个会话同时只允许一个完成请求
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 会话ID
+    /// * `current_user_id` - 当前用户ID，只有会话的创建者可以完成会话
+    ///
+    /// ## 返回值
+    /// * `Ok(Ro<OssObjRefVo>)` - 完成成功，返回包含文件引用信息的结果对象
+    /// * `Err(SvcError)` - 完成失败，返回错误信息
+    ///
+    /// ## 错误处理
+    /// * 如果分片序号不连续或分片总大小与文件大小不一致，返回验证错误
+    /// * 如果合并后的文件Hash与提供的Hash不一致，返回错误
+    /// * 如果会话正在完成，返回验证错误
+    ///
+    /// ## 事务
+    /// 合并分片写入存储后端时不占用事务，写入完成后再在一个短事务中完成对象并新增对象引用；
+    /// 失败时保留会话及预留的对象，可重新完成或中止会话
+    pub async fn complete(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+    ) -> Result<Ro<OssObjRefVo>, SvcError> {
+        let _completing = OssUploadSessionLock::lock_completing(id)?;
+        // 获取锁之后再读取会话，之前的完成请求可能已删除会话
+        let session = Self::load_session(bucket, id, current_user_id).await?;
+        let session_dir = Self::session_dir(id)?;
+        let parts = Self::list_parts(&session_dir).await?;
+        for (index, part) in parts.iter().enumerate() {
+            if part.part_number != index as u32 + 1 {
+                Err(validator::ValidationError::new("分片不完整"))?;
+            }
+        }
+        let received_size: u64 = parts.iter().map(|part| part.size).sum();
+        if received_size != session.size {
+            Err(validator::ValidationError::new("分片总大小与文件大小不一致"))?;
+        }
+
+        // 按序号依次读取分片，合并成一个流写入存储后端
+        let part_file_paths: Vec<PathBuf> = parts
+            .iter()
+            .map(|part| session_dir.join(format!("{}.{}", part.part_number, PART_FILE_EXT)))
+            .collect();
+        let stream = stream::iter(part_file_paths)
+            .then(|path| async move { File::open(path).await.map(ReaderStream::new) })
+            .try_flatten();
+        let exif_policy = OssExifSvc::get_bucket_policy(session.bucket_id).await?;
+        let received = OssFileSvc::receive_and_write(
+            &session.hash,
+            &Some(session.size),
+            stream,
+            &session.path,
+            exif_policy,
+        )
+        .await?;
+        let (obj_ref_ro, thumbnail_task) = OssFileSvc::finish_upload::<DatabaseTransaction>(
+            session.bucket_id,
+            id,
+            &session.path,
+            received,
+            &session.name,
+            current_user_id,
+            None,
+        )
+        .await?;
+        OssThumbnailSvc::spawn_generate(thumbnail_task);
+
+        fs::remove_dir_all(&session_dir).await?;
+        info!("上传会话<{}>已完成", id);
+        Ok(obj_ref_ro.msg("上传成功".to_string()))
+    }
+
+    /// # 中止上传会话
+    ///
+    /// 删除会话的临时目录及预留的未完成对象，会话正在完成时不能中止
+    ///
+    /// ## 参数
+    /// * `bucket` - 存储桶名称
+    /// * `id` - 会话ID
+    /// * `current_user_id` - 当前用户ID，只有会话的创建者可以中止会话
+    /// * `db` - 数据库连接，如果未提供则使用全局数据库连接
+    #[db_unwrap(transaction_required)]
+    pub async fn abort<C>(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+        db: Option<&C>,
+    ) -> Result<Ro<()>, SvcError>
+    where
+        C: ConnectionTrait,
+    {
+        let _completing = OssUploadSessionLock::lock_completing(id)?;
+        Self::load_session(bucket, id, current_user_id).await?;
+        OssObjSvc::del_with_file(id, Some(db)).await?;
+        fs::remove_dir_all(Self::session_dir(id)?).await?;
+        info!("上传会话<{}>已中止", id);
+        Ok(Ro::success("中止上传会话成功".to_string()))
+    }
+
+    /// # 获取存放所有会话的临时目录
+    ///
+    /// 每个会话占用其中以会话ID命名的子目录
+    pub fn sessions_dir() -> Result<PathBuf, SvcError> {
+        Ok(OssFileSvc::upload_temp_dir()?.join(SESSION_DIR_NAME))
+    }
+
+    /// 获取会话的临时目录
+    fn session_dir(id: u64) -> Result<PathBuf, SvcError> {
+        Ok(Self::sessions_dir()?.join(id.to_string()))
+    }
+
+    /// 将分片内容写入临时文件，返回分片大小
+    async fn write_part<S, E>(
+        temp_file_path: &Path,
+        session_size: u64,
+        mut stream: S,
+    ) -> Result<u64, SvcError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut file = File::create(temp_file_path).await?;
+        let mut part_size: u64 = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(io::Error::other)?;
+            part_size += chunk.len() as u64;
+            // 单个分片不可能大于整个文件
+            if part_size > session_size {
+                Err(validator::ValidationError::new("分片大小超出文件大小"))?;
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(part_size)
+    }
+
+    /// 读取会话信息，会话不存在、不属于该存储桶或不是当前用户创建的时返回 NotFound 错误
+    async fn load_session(
+        bucket: &str,
+        id: u64,
+        current_user_id: u64,
+    ) -> Result<OssUploadSession, SvcError> {
+        let session_file_path = Self::session_dir(id)?.join(SESSION_FILE_NAME);
+        let session_json = match fs::read(&session_file_path).await {
+            Ok(session_json) => session_json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(SvcError::NotFound(format!("上传会话: {}", id)));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let session: OssUploadSession =
+            serde_json::from_slice(&session_json).map_err(|e| SvcError::Runtime(anyhow!(e)))?;
+        if session.bucket != bucket || session.creator_id != current_user_id {
+            return Err(SvcError::NotFound(format!("上传会话: {}", id)));
+        }
+        Ok(session)
+    }
+
+    /// 列出会话已接收的分片，按分片序号排序
+    async fn list_parts(session_dir: &PathBuf) -> Result<Vec<OssUploadPartVo>, SvcError> {
+        let mut parts = vec![];
+        let mut entries = fs::read_dir(session_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let Some(part_number) = file_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(&format!(".{}", PART_FILE_EXT)))
+                .and_then(|part_number| part_number.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            parts.push(OssUploadPartVo {
+                part_number,
+                size: entry.metadata().await?.len(),
+            });
+        }
+        parts.sort_by_key(|part| part.part_number);
+        Ok(parts)
+    }
+
+    fn to_vo(session: OssUploadSession, received_parts: Vec<OssUploadPartVo>) -> OssUploadSessionVo {
+        OssUploadSessionVo {
+            id: session.id,
+            bucket: session.bucket,
+            name: session.name,
+            size: session.size,
+            hash: session.hash,
+            received_size: received_parts.iter().map(|part| part.size).sum(),
+            received_parts,
+            creator_id: session.creator_id,
+            create_timestamp: session.create_timestamp,
+        }
+    }
+}
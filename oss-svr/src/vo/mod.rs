@@ -1,7 +1,13 @@
+mod oss_bucket_acl_vo;
 mod oss_bucket_vo;
 mod oss_obj_ref_vo;
 mod oss_obj_vo;
+mod oss_presign_vo;
+mod oss_upload_session_vo;
 
+pub use oss_bucket_acl_vo::*;
 pub use oss_bucket_vo::*;
 pub use oss_obj_ref_vo::*;
 pub use oss_obj_vo::*;
+pub use oss_presign_vo::*;
+pub use oss_upload_session_vo::*;
@@ -0,0 +1,24 @@
+use crate::model::oss_bucket_acl::Model;
+use robotech::macros::vo;
+
+#[vo]
+pub struct OssBucketAclVo {
+    /// ID
+    pub id: u64,
+    /// 存储桶ID
+    pub bucket_id: u64,
+    /// 授权对象的类型
+    pub grantee_type: String,
+    /// 授权对象
+    pub grantee: String,
+    /// 权限
+    pub permission: String,
+    /// 创建者ID
+    pub creator_id: u64,
+    /// 创建时间
+    pub create_timestamp: u64,
+    /// 更新者ID
+    pub updator_id: u64,
+    /// 更新时间
+    pub update_timestamp: u64,
+}
@@ -9,6 +9,18 @@ pub struct OssBucketVo {
     pub name: String,
     /// 备注
     pub remark: Option<String>,
+    /// 可预览的MIME类型
+    pub previewable_types: Option<String>,
+    /// MIME类型不符时的策略
+    pub mime_mismatch_policy: Option<String>,
+    /// 访问策略
+    pub access: Option<String>,
+    /// 缩略图的尺寸
+    pub thumbnail_sizes: Option<String>,
+    /// EXIF策略
+    pub exif_policy: Option<String>,
+    /// 水印
+    pub watermark: Option<String>,
     /// 创建者ID
     pub creator_id: u64,
     /// 创建时间
@@ -0,0 +1,14 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// # 预签名URL
+#[derive(ToSchema, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssPresignVo {
+    /// 预签名URL(不含协议和主机)
+    pub url: String,
+    /// 允许的请求方法
+    pub method: String,
+    /// 过期时间戳(秒)
+    pub expires: u64,
+}
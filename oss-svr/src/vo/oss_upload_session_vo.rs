@@ -0,0 +1,38 @@
+use serde::Serialize;
+use serde_with::skip_serializing_none;
+use utoipa::ToSchema;
+
+/// # 上传会话
+#[skip_serializing_none] // 忽略空字段(好像必须放在#[derive(Serialize)]的上方才能起效)
+#[derive(ToSchema, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssUploadSessionVo {
+    /// 会话ID(即未完成的对象ID)
+    pub id: u64,
+    /// 存储桶名称
+    pub bucket: String,
+    /// 文件名
+    pub name: String,
+    /// 文件大小
+    pub size: u64,
+    /// 文件Hash
+    pub hash: Option<String>,
+    /// 已接收的分片
+    pub received_parts: Vec<OssUploadPartVo>,
+    /// 已接收的大小
+    pub received_size: u64,
+    /// 创建者ID
+    pub creator_id: u64,
+    /// 创建时间戳
+    pub create_timestamp: u64,
+}
+
+/// # 已接收的分片
+#[derive(ToSchema, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OssUploadPartVo {
+    /// 分片序号(从1开始)
+    pub part_number: u32,
+    /// 分片大小
+    pub size: u64,
+}
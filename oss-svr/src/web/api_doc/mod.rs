@@ -1,4 +1,7 @@
+pub mod oss_bucket_acl_api_doc;
 pub mod oss_bucket_api_doc;
 pub mod oss_file_api_doc;
 pub mod oss_obj_api_doc;
 pub mod oss_obj_ref_api_doc;
+pub mod oss_tus_api_doc;
+pub mod oss_upload_session_api_doc;
@@ -0,0 +1,4 @@
+use robotech::macros::api_doc;
+
+#[api_doc(add, modify, del_by_id, get_by_id, list_by_query_dto)]
+pub struct OssBucketAclApiDoc;
@@ -8,7 +8,7 @@ use robotech::macros::api_doc;
     del_by_query_dto,
     get_by_id,
     get_by_query_dto,
-    list_by_query_dto,
+    list_readable,
     page_by_query_dto,
     del_cascade
 )]
@@ -1,4 +1,4 @@
 use robotech::macros::api_doc;
 
-#[api_doc(upload, download, preview)]
+#[api_doc(upload, upload_batch, upload_raw, instant_upload, download, download_head, preview, preview_head, render, exif, archive, presign)]
 pub struct OssFileApiDoc;
@@ -0,0 +1,4 @@
+use robotech::macros::api_doc;
+
+#[api_doc(tus_options, tus_create, tus_head, tus_patch, tus_delete)]
+pub struct OssTusApiDoc;
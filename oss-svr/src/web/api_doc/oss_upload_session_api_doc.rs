@@ -0,0 +1,4 @@
+use robotech::macros::api_doc;
+
+#[api_doc(init_session, get_session, upload_part, complete_session, abort_session)]
+pub struct OssUploadSessionApiDoc;
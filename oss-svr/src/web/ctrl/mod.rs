@@ -1,4 +1,7 @@
+pub mod oss_bucket_acl_ctrl;
 pub mod oss_bucket_ctrl;
 pub mod oss_file_ctrl;
 pub mod oss_obj_ctrl;
 pub mod oss_obj_ref_ctrl;
+pub mod oss_tus_ctrl;
+pub mod oss_upload_session_ctrl;
@@ -0,0 +1,4 @@
+use robotech::macros::ctrl;
+
+#[ctrl]
+struct OssBucketAclCtrl;
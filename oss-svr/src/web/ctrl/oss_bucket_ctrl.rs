@@ -1,3 +1,4 @@
+use crate::web::get_accessor;
 use robotech::macros::ctrl;
 
 #[ctrl]
@@ -31,3 +32,29 @@ pub async fn del_cascade(
     let ro = OssBucketSvc::del_cascade::<DatabaseTransaction>(id, None).await?;
     Ok(Json(ro))
 }
+
+/// # 查询存储桶列表
+///
+/// 该接口用于根据查询条件获取存储桶列表，启用访问控制时只返回当前用户可以查看的存储桶
+///
+/// ## 请求参数
+/// * 查询条件，参见`OssBucketQueryDto`
+///
+/// ## 错误处理
+/// * 当查询失败时，返回相应的错误信息
+#[utoipa::path(
+    get,
+    path = "/oss/bucket/list",
+    params(OssBucketQueryDto),
+    responses((status = OK, body = Ro<Vec<OssBucketVo>>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn list_readable(
+    Query(query_dto): Query<OssBucketQueryDto>,
+    headers: HeaderMap,
+) -> Result<Json<Ro<Vec<OssBucketVo>>>, CtrlError> {
+    let accessor = get_accessor(&headers, None)?;
+    let ro = OssBucketSvc::list_readable::<DatabaseTransaction>(query_dto, &accessor, None).await?;
+    Ok(Json(ro))
+}
@@ -1,24 +1,45 @@
-use crate::svc::OssFileSvc;
-use crate::vo::OssObjRefVo;
-use axum::extract::{Multipart, Path};
-use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use crate::dto::oss_file_dto::{
+    OssFileArchiveDto, OssFileDownloadQueryDto, OssFileInstantDto, OssFileRenderQueryDto,
+};
+use crate::dto::oss_presign_dto::OssPresignDto;
+use crate::svc::{
+    OCTET_STREAM, OssAccessor, OssArchiveSvc, OssExifSvc, OssFileContent, OssFileDownload,
+    OssFileSvc, OssImageProcess, OssImageSvc, OssMimeSvc, OssPresignSvc, OssPresigned,
+    OssRenderSvc, RENDER_CSP,
+};
+use crate::web::{forbidden, get_accessor};
+use crate::vo::{OssObjRefVo, OssPresignVo};
+use axum::body::Body;
+use axum::extract::{Multipart, Path, Query, RawQuery};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
 use axum::response::{IntoResponse, Response};
-use axum::{Json, debug_handler};
+use axum::{Extension, Json, debug_handler};
 use regex::Regex;
 use robotech::macros::log_call;
 use robotech::ro::Ro;
 use robotech::web::CtrlError;
 use robotech::web::ctrl_utils::get_current_user_id;
 use sea_orm::DatabaseTransaction;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::sync::LazyLock;
 
+/// 上传时提供文件hash(SHA256)的请求头
+static X_OSS_CONTENT_SHA256: HeaderName = HeaderName::from_static("x-oss-content-sha256");
+/// 上传时提供文件大小的请求头
+static X_OSS_SIZE: HeaderName = HeaderName::from_static("x-oss-size");
+
 /// # 上传文件到指定的存储桶
 ///
 /// 该接口接收一个文件和可选的哈希值，将其上传到指定的存储桶中。
 /// 如果提供了哈希值，会与计算出的文件哈希值进行比对，确保文件完整性。
+/// 哈希值和文件大小可以通过请求头`X-Oss-Content-Sha256`、`X-Oss-Size`提供，
+/// 也可以通过表单字段`hash`、`size`提供，表单字段在`file`字段之前或之后均可。
 ///
 /// ## 参数
 /// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `headers`: 请求头，包含当前用户ID及可选的文件哈希值和文件大小
 /// - `form`: Multipart表单数据，包含上传的文件和其他元数据
 ///
 /// ## 返回值
@@ -26,12 +47,16 @@ use std::sync::LazyLock;
 ///
 /// ## 错误处理
 /// - 如果存储桶名称为空，返回验证错误
+/// - 如果请求头中的文件大小格式不正确，返回验证错误
+/// - 如果请求头和表单字段提供的哈希值或文件大小不一致，返回验证错误
 /// - 如果提供的哈希值与计算出的哈希值不匹配，返回验证错误
 #[utoipa::path(
     post,
     path = "/oss/file/upload/{bucket}",
     params(
-        ("bucket" = String, Path, description = "存储桶名称")
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("X-Oss-Content-Sha256" = Option<String>, Header, description = "文件哈希值(SHA256)"),
+        ("X-Oss-Size" = Option<u64>, Header, description = "文件大小")
     ),
     responses((status = OK, body = Ro<OssObjRefVo>))
 )]
@@ -40,13 +65,193 @@ use std::sync::LazyLock;
 pub async fn upload(
     Path(bucket): Path<String>,
     headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
     multipart: Multipart,
 ) -> Result<Json<Ro<OssObjRefVo>>, CtrlError> {
     // 从header中解析当前用户ID，如果没有或解析失败则抛出ApiError
-    let current_user_id = get_current_user_id(&headers)?;
+    let current_user_id = get_uploader_id(&headers, presigned)?;
+    let (hash_provided, file_size_provided) = get_upload_metadata(&headers)?;
 
     Ok(Json(
-        OssFileSvc::upload::<DatabaseTransaction>(&bucket, multipart, current_user_id, None)
+        OssFileSvc::upload::<DatabaseTransaction>(
+            &bucket,
+            hash_provided,
+            file_size_provided,
+            multipart,
+            current_user_id,
+            None,
+        )
+        .await?,
+    ))
+}
+
+/// # 批量上传文件到指定的存储桶
+///
+/// 该接口在一次请求中接收多个文件，每个`file`字段之前可以提供该文件的`hash`、`size`字段。
+/// 每个文件单独上传，某个文件上传失败不影响其它文件。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `form`: Multipart表单数据，包含上传的多个文件和其他元数据
+///
+/// ## 返回值
+/// 成功时返回`Ro<Vec<Ro<OssObjRefVo>>>`对象，按上传顺序包含每个文件的上传结果
+///
+/// ## 错误处理
+/// - 如果存储桶不存在，返回警告信息
+/// - 如果有文件上传失败，返回警告信息，失败文件的上传结果包含失败原因
+#[utoipa::path(
+    post,
+    path = "/oss/file/upload-batch/{bucket}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称")
+    ),
+    responses((status = OK, body = Ro<Vec<Ro<OssObjRefVo>>>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn upload_batch(
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+    multipart: Multipart,
+) -> Result<Json<Ro<Vec<Ro<OssObjRefVo>>>>, CtrlError> {
+    let current_user_id = get_uploader_id(&headers, presigned)?;
+
+    Ok(Json(
+        OssFileSvc::upload_batch::<DatabaseTransaction>(&bucket, multipart, current_user_id, None)
+            .await?,
+    ))
+}
+
+/// # 以原始请求体上传文件到指定的存储桶
+///
+/// 该接口直接以请求体作为文件内容，不需要multipart编码，便于服务间调用及curl等脚本上传。
+/// 文件名取自路径，哈希值和文件大小通过请求头`X-Oss-Content-Sha256`、`X-Oss-Size`提供。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `name`: 路径参数，文件名
+/// - `headers`: 请求头，包含当前用户ID及可选的文件哈希值和文件大小
+/// - `body`: 请求体，文件内容
+///
+/// ## 返回值
+/// 成功时返回包含文件引用信息的`Ro<OssObjRefVo>`对象
+///
+/// ## 错误处理
+/// - 如果请求头中的文件大小格式不正确，返回验证错误
+/// - 如果提供的哈希值或文件大小与计算出的不匹配，返回验证错误
+#[utoipa::path(
+    put,
+    path = "/oss/file/raw/{bucket}/{name}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("name" = String, Path, description = "文件名"),
+        ("X-Oss-Content-Sha256" = Option<String>, Header, description = "文件哈希值(SHA256)"),
+        ("X-Oss-Size" = Option<u64>, Header, description = "文件大小")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = OK, body = Ro<OssObjRefVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn upload_raw(
+    Path((bucket, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+    body: Body,
+) -> Result<Json<Ro<OssObjRefVo>>, CtrlError> {
+    let current_user_id = get_uploader_id(&headers, presigned)?;
+    let (hash_provided, file_size_provided) = get_upload_metadata(&headers)?;
+
+    Ok(Json(
+        OssFileSvc::upload_raw::<DatabaseTransaction, _, _>(
+            &bucket,
+            &name,
+            hash_provided,
+            file_size_provided,
+            body.into_data_stream(),
+            current_user_id,
+            None,
+        )
+        .await?,
+    ))
+}
+
+/// # 获取上传者的用户ID
+///
+/// 通过预签名URL上传时为签发者的用户ID，否则从请求头中解析当前用户ID
+fn get_uploader_id(
+    headers: &HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+) -> Result<u64, CtrlError> {
+    match presigned {
+        Some(Extension(presigned)) => Ok(presigned.user_id),
+        None => Ok(get_current_user_id(headers)?),
+    }
+}
+
+/// # 从请求头中解析上传文件的哈希值和文件大小
+///
+/// 格式不正确时返回验证错误，避免客户端提供的校验信息被忽略
+fn get_upload_metadata(headers: &HeaderMap) -> Result<(Option<String>, Option<u64>), CtrlError> {
+    let hash_provided = match headers.get(&X_OSS_CONTENT_SHA256) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| validator::ValidationError::new("文件hash格式错误"))?
+                .trim()
+                .to_lowercase(),
+        ),
+        None => None,
+    };
+    let file_size_provided = match headers.get(&X_OSS_SIZE) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| validator::ValidationError::new("文件大小格式错误"))?,
+        ),
+        None => None,
+    };
+    Ok((hash_provided, file_size_provided))
+}
+
+/// # 秒传文件
+///
+/// 该接口只接收文件名、文件大小和文件哈希值，不传输文件内容。
+/// 如果系统中已存在相同的文件，则直接生成文件引用，否则提示需要上传文件。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `dto`: 请求体，包含文件名、文件大小和文件哈希值
+///
+/// ## 返回值
+/// 秒传成功时返回包含文件引用信息的`Ro<OssObjRefVo>`对象，
+/// 文件不存在时返回不含文件引用信息的警告，客户端需再调用上传接口上传文件
+///
+/// ## 错误处理
+/// - 如果存储桶不存在，返回警告信息
+#[utoipa::path(
+    post,
+    path = "/oss/file/instant/{bucket}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称")
+    ),
+    request_body = OssFileInstantDto,
+    responses((status = OK, body = Ro<OssObjRefVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn instant_upload(
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+    Json(dto): Json<OssFileInstantDto>,
+) -> Result<Json<Ro<OssObjRefVo>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssFileSvc::instant_upload::<DatabaseTransaction>(&bucket, dto, current_user_id, None)
             .await?,
     ))
 }
@@ -62,10 +267,12 @@ enum DownloadMode {
 
 /// # 下载文件
 ///
-/// 该接口根据对象ID下载对应的文件内容。
+/// 该接口根据对象ID下载对应的文件内容，支持RFC 7233的Range请求(包括后缀范围和多个范围)，
+/// 以及`If-None-Match`、`If-Modified-Since`、`If-Range`条件请求。
 ///
 /// ## 参数
 /// - `obj_id`: 路径参数，指定要下载的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.jpg)
+/// - `filename`: 查询参数，保存的文件名，未提供时使用上传时的文件名
 ///
 /// ## 返回值
 /// 成功时返回文件的二进制内容，以及适当的HTTP头部信息
@@ -73,11 +280,15 @@ enum DownloadMode {
 /// ## 错误处理
 /// - 如果对象ID格式不正确，返回验证错误
 /// - 如果找不到对应的对象，由服务层返回相应错误
+/// - 如果存储桶不是公共读且当前用户无权下载(也未通过预签名URL访问)，视为找不到对应的对象
+///
+/// 存储桶设置了水印时，除文件的上传者及拥有存储桶 `write` 权限的用户外，下载的图片都添加水印
 #[utoipa::path(
     get,
     path = "/oss/file/download/{obj_id}",
     params(
-        ("obj_id" = String, Path, description = "对象ID")
+        ("obj_id" = String, Path, description = "对象ID"),
+        ("filename" = Option<String>, Query, description = "保存的文件名，未提供时使用上传时的文件名")
     ),
     responses((status = OK, body = Ro<OssObjRefVo>))
 )]
@@ -85,17 +296,74 @@ enum DownloadMode {
 #[log_call]
 pub async fn download(
     Path(obj_id): Path<String>,
+    Query(query): Query<OssFileDownloadQueryDto>,
     headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
 ) -> Result<Response, CtrlError> {
-    download_or_preview(DownloadMode::Download, obj_id, headers).await
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    download_or_preview(
+        DownloadMode::Download,
+        obj_id,
+        query.filename,
+        None,
+        headers,
+        &accessor,
+        true,
+    )
+    .await
+}
+
+/// # 获取下载文件的响应头
+///
+/// 该接口与下载文件接口返回相同的响应头(文件大小、类型、Range支持、ETag等)，但不返回内容，也不打开文件。
+///
+/// ## 参数
+/// - `obj_id`: 路径参数，指定要下载的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.jpg)
+/// - `filename`: 查询参数，保存的文件名，未提供时使用上传时的文件名
+///
+/// ## 错误处理
+/// - 如果对象ID格式不正确，返回验证错误
+/// - 如果找不到对应的对象，由服务层返回相应错误
+#[utoipa::path(
+    head,
+    path = "/oss/file/download/{obj_id}",
+    params(
+        ("obj_id" = String, Path, description = "对象ID"),
+        ("filename" = Option<String>, Query, description = "保存的文件名，未提供时使用上传时的文件名")
+    ),
+    responses((status = OK))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn download_head(
+    Path(obj_id): Path<String>,
+    Query(query): Query<OssFileDownloadQueryDto>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+) -> Result<Response, CtrlError> {
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    download_or_preview(
+        DownloadMode::Download,
+        obj_id,
+        query.filename,
+        None,
+        headers,
+        &accessor,
+        false,
+    )
+    .await
 }
 
 /// # 预览文件
 ///
-/// 该接口根据对象ID预览对应的文件内容，支持Range请求用于视频、音频等大文件的分段加载
+/// 该接口根据对象ID预览对应的文件内容，支持Range请求用于视频、音频等大文件的分段加载；
+/// 预览图片时可通过`x-oss-process`参数缩放、裁剪、旋转、添加水印及转换格式，处理后的图片不支持Range请求；
+/// 存储桶设置了水印时，除文件的上传者及拥有存储桶 `write` 权限的用户外，在处理参数的最后添加存储桶的水印
 ///
 /// ## 参数
 /// - `obj_id`: 路径参数，指定要预览的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.jpg)
+/// - `filename`: 查询参数，保存的文件名，未提供时使用上传时的文件名
+/// - `x-oss-process`: 查询参数，图片处理参数，如`image/resize,w_200/quality,q_80/format,webp`
 /// - `req`: HTTP请求对象，用于检查是否包含Range头以支持部分内容请求
 ///
 /// ## 返回值
@@ -106,14 +374,19 @@ pub async fn download(
 ///
 /// ## 错误处理
 /// - 如果对象ID格式不正确，返回验证错误
-/// - 如果Range头格式不正确，返回验证错误
+/// - 如果Range头格式不正确，忽略Range头，返回整个文件
+/// - 如果请求的范围都超出了文件末尾，返回416状态码
+/// - 如果图片处理参数格式不正确，或文件不是可处理的图片，返回验证错误
 /// - 如果ID不是有效数字，返回验证错误
 /// - 如果找不到对应的对象，由服务层返回相应错误
+/// - 如果存储桶不是公共读且当前用户无权预览(也未通过预签名URL访问)，视为找不到对应的对象
 #[utoipa::path(
     get,
     path = "/oss/file/preview/{obj_id}",
     params(
-        ("obj_id" = String, Path, description = "对象ID")
+        ("obj_id" = String, Path, description = "对象ID"),
+        ("filename" = Option<String>, Query, description = "保存的文件名，未提供时使用上传时的文件名"),
+        ("x-oss-process" = Option<String>, Query, description = "图片处理参数")
     ),
     responses((status = OK, body = Ro<OssObjRefVo>))
 )]
@@ -121,54 +394,413 @@ pub async fn download(
 #[log_call]
 pub async fn preview(
     Path(obj_id): Path<String>,
+    Query(query): Query<OssFileDownloadQueryDto>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+) -> Result<Response, CtrlError> {
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    download_or_preview(
+        DownloadMode::Preview,
+        obj_id,
+        query.filename,
+        query.process,
+        headers,
+        &accessor,
+        true,
+    )
+    .await
+}
+
+/// # 获取预览文件的响应头
+///
+/// 该接口与预览文件接口返回相同的响应头(文件大小、类型、Range支持、ETag等)，但不返回内容，也不打开文件。
+///
+/// ## 参数
+/// - `obj_id`: 路径参数，指定要预览的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.jpg)
+/// - `filename`: 查询参数，保存的文件名，未提供时使用上传时的文件名
+/// - `x-oss-process`: 查询参数，图片处理参数
+///
+/// ## 错误处理
+/// - 如果对象ID格式不正确，返回验证错误
+/// - 如果找不到对应的对象，由服务层返回相应错误
+#[utoipa::path(
+    head,
+    path = "/oss/file/preview/{obj_id}",
+    params(
+        ("obj_id" = String, Path, description = "对象ID"),
+        ("filename" = Option<String>, Query, description = "保存的文件名，未提供时使用上传时的文件名"),
+        ("x-oss-process" = Option<String>, Query, description = "图片处理参数")
+    ),
+    responses((status = OK))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn preview_head(
+    Path(obj_id): Path<String>,
+    Query(query): Query<OssFileDownloadQueryDto>,
     headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
 ) -> Result<Response, CtrlError> {
-    download_or_preview(DownloadMode::Preview, obj_id, headers).await
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    download_or_preview(
+        DownloadMode::Preview,
+        obj_id,
+        query.filename,
+        query.process,
+        headers,
+        &accessor,
+        false,
+    )
+    .await
 }
 
+/// # 渲染预览文件
+///
+/// 该接口将Markdown渲染为净化后的HTML，源代码语法高亮，CSV/TSV显示为分页的表格(第一行为表头)，以HTML页面返回；
+/// 渲染前检测文本的编码，GBK等非UTF-8编码的文件也能正确显示。页面只包含净化或转义后的内容，
+/// 并以禁止执行脚本的 `Content-Security-Policy` 返回，因此不受存储桶的可预览白名单限制；访问控制与下载文件相同
+///
+/// ## 参数
+/// - `obj_id`: 路径参数，指定要渲染的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.md)
+/// - `page`: 查询参数，表格的页码，从1开始，只对CSV/TSV有效
+///
+/// ## 返回值
+/// 成功时返回渲染后的HTML页面，响应头包含以文件hash、渲染方式及页码计算的 `ETag`
+///
+/// ## 错误处理
+/// - 如果对象ID格式不正确，或文件不是Markdown、源代码、CSV/TSV等文本文件，返回验证错误
+/// - 如果找不到对应的对象，由服务层返回相应错误
+/// - 如果存储桶不是公共读且当前用户无权下载(也未通过预签名URL访问)，视为找不到对应的对象
+/// - 如果文件超过可渲染的最大大小，返回错误信息
+#[utoipa::path(
+    get,
+    path = "/oss/file/render/{obj_id}",
+    params(
+        ("obj_id" = String, Path, description = "对象ID"),
+        ("page" = Option<usize>, Query, description = "表格的页码，从1开始")
+    ),
+    responses((status = OK, content_type = "text/html"))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn render(
+    Path(obj_id): Path<String>,
+    Query(query): Query<OssFileRenderQueryDto>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+) -> Result<Response, CtrlError> {
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    let (obj_id, ext) = parse_obj_id(&obj_id)?;
+    let download =
+        OssFileSvc::download::<DatabaseTransaction>(headers.clone(), obj_id, ext, &accessor, None)
+            .await?;
+    let kind = OssRenderSvc::get_kind(&download.ext, &download.content_type)
+        .ok_or_else(|| validator::ValidationError::new("该文件不支持渲染预览"))?;
+    let rendered = OssRenderSvc::render(
+        &download,
+        kind,
+        query.page.unwrap_or(1),
+        raw_query.as_deref(),
+        &headers,
+    )
+    .await?;
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&rendered.etag)?);
+    response_headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&download.last_modified)?,
+    );
+    if rendered.not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    response_headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(RENDER_CSP),
+    );
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    let html = rendered.html.unwrap_or_default();
+    Ok((StatusCode::OK, response_headers, html).into_response())
+}
+
+/// # 读取图片的EXIF信息
+///
+/// 该接口读取图片中的EXIF字段，以字段名及带单位的可读文本返回，只读不修改文件；
+/// 访问控制与下载文件相同
+///
+/// ## 参数
+/// - `obj_id`: 路径参数，指定要读取的对象ID，格式为数字ID加可选的文件扩展名后缀(如: 12345.jpg)
+///
+/// ## 返回值
+/// 成功时返回EXIF字段，图片中没有EXIF信息时为空
+///
+/// ## 错误处理
+/// - 如果对象ID格式不正确，或文件不是图片，返回验证错误
+/// - 如果找不到对应的对象，由服务层返回相应错误
+/// - 如果存储桶不是公共读且当前用户无权下载(也未通过预签名URL访问)，视为找不到对应的对象
+/// - 如果图片超过可处理的最大大小，返回错误信息
+#[utoipa::path(
+    get,
+    path = "/oss/file/exif/{obj_id}",
+    params(("obj_id" = String, Path, description = "对象ID")),
+    responses((status = OK, body = Ro<BTreeMap<String, String>>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn exif(
+    Path(obj_id): Path<String>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+) -> Result<Json<Ro<BTreeMap<String, String>>>, CtrlError> {
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    let (obj_id, ext) = parse_obj_id(&obj_id)?;
+    // 不使用请求头中的条件请求，总是返回EXIF信息
+    let download =
+        OssFileSvc::download::<DatabaseTransaction>(HeaderMap::new(), obj_id, ext, &accessor, None)
+            .await?;
+    if !OssMimeSvc::get_essence(&download.content_type).starts_with("image/") {
+        Err(validator::ValidationError::new("该文件不是图片"))?;
+    }
+    let fields = OssExifSvc::read(&download).await?;
+    Ok(Json(
+        Ro::success("读取EXIF成功".to_string()).extra(Some(fields)),
+    ))
+}
+
+/// # 打包下载文件
+///
+/// 将指定的多个对象引用或整个存储桶中的文件打包为ZIP下载，压缩包边读取文件边生成，
+/// 压缩包中的文件名为上传时的文件名，重名时在扩展名前加上序号；
+/// 要打包的文件由请求体指定而请求体不在签名范围内，所以不支持预签名URL，只按请求头中的当前用户判断权限
+///
+/// ## 参数
+/// - `dto`: 请求体，包含要打包的对象引用ID列表或存储桶名称，以及可选的压缩包文件名
+///
+/// ## 返回值
+/// 返回ZIP格式的压缩包，以分块传输编码发送，没有`Content-Length`
+///
+/// ## 错误处理
+/// - 如果既没有提供对象引用ID也没有提供存储桶名称，返回验证错误
+/// - 如果存储桶或某个对象引用不存在，返回相应的错误信息
+/// - 指定对象引用ID时，如果无权下载其中某个文件，视为不存在；打包整个存储桶时跳过无权下载的文件
+#[utoipa::path(
+    post,
+    path = "/oss/file/archive",
+    request_body = OssFileArchiveDto,
+    responses((status = OK, content_type = "application/zip"))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn archive(
+    headers: HeaderMap,
+    Json(dto): Json<OssFileArchiveDto>,
+) -> Result<Response, CtrlError> {
+    let has_obj_ref_ids = dto
+        .obj_ref_ids
+        .as_ref()
+        .is_some_and(|obj_ref_ids| !obj_ref_ids.is_empty());
+    let has_bucket = dto
+        .bucket
+        .as_ref()
+        .is_some_and(|bucket| !bucket.trim().is_empty());
+    if !has_obj_ref_ids && !has_bucket {
+        Err(validator::ValidationError::new(
+            "未提供要打包的对象引用ID或存储桶名称",
+        ))?;
+    }
+
+    let accessor = get_accessor(&headers, None)?;
+    let archive = OssArchiveSvc::archive::<DatabaseTransaction>(dto, &accessor, None).await?;
+    let content_disposition = build_content_disposition("attachment", &archive.file_name);
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(content_disposition.as_str())?,
+    );
+    Ok((StatusCode::OK, response_headers, archive.body).into_response())
+}
+
+/// # 生成预签名URL
+///
+/// 为下载、预览或上传接口生成带有效期的预签名URL，可分享给第三方在有效期内访问，
+/// 通过预签名URL上传的文件以签发者作为创建者；签发者需要可以下载要签名的文件或上传到要签名的存储桶
+///
+/// ## 参数
+/// - `headers`: 请求头，包含当前用户ID
+/// - `dto`: 请求体，包含请求方法、请求路径、有效期及可选的IP和请求体长度限制
+///
+/// ## 返回值
+/// 成功时返回包含预签名URL的`Ro<OssPresignVo>`对象，参数不正确时返回警告信息
+///
+/// ## 错误处理
+/// - 如果未配置签名密钥，返回错误信息
+/// - 如果当前用户无权访问要签名的请求路径(包括文件或存储桶不存在)，返回403状态码
+#[utoipa::path(
+    post,
+    path = "/oss/file/presign",
+    request_body = OssPresignDto,
+    responses(
+        (status = OK, body = Ro<OssPresignVo>),
+        (status = FORBIDDEN, body = Ro<()>)
+    )
+)]
+#[debug_handler]
+#[log_call]
+pub async fn presign(
+    headers: HeaderMap,
+    Json(dto): Json<OssPresignDto>,
+) -> Result<Response, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    match OssPresignSvc::sign::<DatabaseTransaction>(dto, current_user_id, None).await? {
+        Some(ro) => Ok(Json(ro).into_response()),
+        None => Ok(forbidden("无权访问要签名的请求路径".to_string())),
+    }
+}
+
+/// # 下载或预览文件
+///
+/// `with_body`为false时用于HEAD请求，返回相同的响应头，但不打开文件，内容为空；
+/// `process`只在预览时有效，提供时返回处理后的图片；需添加存储桶的水印时，下载和预览都返回添加水印后的图片
 async fn download_or_preview(
     mode: DownloadMode,
     obj_id: String,
+    file_name: Option<String>,
+    process: Option<String>,
     headers: HeaderMap,
+    accessor: &OssAccessor,
+    with_body: bool,
 ) -> Result<Response, CtrlError> {
     let (obj_id, ext) = parse_obj_id(&obj_id)?;
-    let (file_name, ext, file_size, chunk_size, body, start, end) =
-        OssFileSvc::download::<DatabaseTransaction>(headers, obj_id, ext, None).await?;
+    // 先校验图片处理参数，参数错误时不必查询文件
+    let image_process = match process.filter(|_| mode == DownloadMode::Preview) {
+        Some(process) => {
+            let image_process = OssImageSvc::parse(&process).map_err(|e| {
+                validator::ValidationError::new("x-oss-process格式错误")
+                    .with_message(Cow::Owned(e.to_string()))
+            })?;
+            Some((process, image_process))
+        }
+        None => None,
+    };
+    let download =
+        OssFileSvc::download::<DatabaseTransaction>(headers.clone(), obj_id, ext, accessor, None)
+            .await?;
+    if image_process.is_some()
+        && (!download.previewable || !OssImageSvc::is_processable(&download.content_type))
+    {
+        Err(validator::ValidationError::new("该文件不支持图片处理"))?;
+    }
+    // 在请求的处理参数后添加存储桶的水印
+    let image_process = match &download.watermark {
+        Some(watermark) if OssImageSvc::is_processable(&download.content_type) => {
+            Some(OssImageSvc::add_watermark(image_process, watermark)?)
+        }
+        _ => image_process,
+    };
+    if let Some((process, image_process)) = image_process {
+        return preview_image(
+            mode,
+            &download,
+            &process,
+            &image_process,
+            file_name,
+            &headers,
+            accessor,
+            with_body,
+        )
+        .await;
+    }
+
+    let mut response_headers = HeaderMap::new();
+    if let Some(etag) = &download.etag {
+        response_headers.insert(header::ETAG, HeaderValue::from_str(etag)?);
+    }
+    response_headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&download.last_modified)?,
+    );
+    if download.not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
 
-    let content_type = if mode == DownloadMode::Download {
-        "application/octet-stream"
+    // 只有预览可预览的文件时才使用记录的MIME类型，否则一律作为附件下载
+    let content_type = if mode == DownloadMode::Preview && download.previewable {
+        download.content_type.as_str()
     } else {
-        if let Some(content_type) = OssFileSvc::get_content_type_of_preview(&ext) {
-            content_type
-        } else {
-            "application/octet-stream"
-        }
+        OCTET_STREAM
     };
-    let content_disposition = if content_type == "application/octet-stream" {
-        format!("attachment; filename=\"{}\"", file_name).to_string()
+    // 保存的文件名，优先使用查询参数中提供的文件名
+    let file_name = file_name
+        .filter(|file_name| !file_name.trim().is_empty())
+        .unwrap_or_else(|| download.file_name.clone());
+    let disposition_type = if content_type == OCTET_STREAM {
+        "attachment"
     } else {
-        "inline".to_string()
+        "inline"
     };
+    let content_disposition = build_content_disposition(disposition_type, &file_name);
 
-    let mut response_headers = HeaderMap::new();
     response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes")); // 默认告知客户端本服务器支持 Range 请求
-    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+    // 禁止浏览器根据内容猜测类型，避免将伪装成其它类型的HTML页面作为网页执行
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
     response_headers.insert(
         header::CONTENT_DISPOSITION,
         HeaderValue::from_str(content_disposition.as_str())?,
     );
 
-    let (status_code, content_length) = if let (Some(start_pos), Some(end_pos)) = (start, end) {
-        response_headers.insert(
-            header::CONTENT_RANGE,
-            HeaderValue::from_str(
-                format!("bytes {}-{}/{}", start_pos, end_pos, file_size).as_str(),
-            )?,
-        );
-        (StatusCode::PARTIAL_CONTENT, file_size)
-    } else {
-        (StatusCode::OK, chunk_size)
-    };
+    let file_size = download.file_size;
+    let (status_code, content_length, body) =
+        match OssFileSvc::read_content(&download, content_type, with_body).await? {
+            OssFileContent::Full(body) => (StatusCode::OK, file_size, body),
+            OssFileContent::Partial { start, end, body } => {
+                response_headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(
+                        format!("bytes {}-{}/{}", start, end, file_size).as_str(),
+                    )?,
+                );
+                (StatusCode::PARTIAL_CONTENT, end - start + 1, body)
+            }
+            OssFileContent::Multipart {
+                boundary,
+                content_length,
+                body,
+            } => {
+                // 多个范围时，每个部分有各自的Content-Type和Content-Range
+                response_headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_str(
+                        format!("multipart/byteranges; boundary={}", boundary).as_str(),
+                    )?,
+                );
+                (StatusCode::PARTIAL_CONTENT, content_length, body)
+            }
+            OssFileContent::NotSatisfiable => {
+                response_headers.remove(header::CONTENT_DISPOSITION);
+                response_headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(format!("bytes */{}", file_size).as_str())?,
+                );
+                (StatusCode::RANGE_NOT_SATISFIABLE, 0, Body::empty())
+            }
+        };
     response_headers.insert(
         header::CONTENT_LENGTH,
         HeaderValue::from_str(content_length.to_string().as_str())?,
@@ -177,6 +809,113 @@ async fn download_or_preview(
     Ok((status_code, response_headers, body).into_response())
 }
 
+/// # 预览处理后的图片
+///
+/// 处理后的图片以缓存的键作为ETag，不支持Range请求；HEAD请求在没有缓存时不处理图片，也不返回`Content-Length`；
+/// 下载或预览不可预览的图片(只添加了存储桶的水印)时作为附件下载
+async fn preview_image(
+    mode: DownloadMode,
+    download: &OssFileDownload,
+    process: &str,
+    image_process: &OssImageProcess,
+    file_name: Option<String>,
+    headers: &HeaderMap,
+    accessor: &OssAccessor,
+    with_body: bool,
+) -> Result<Response, CtrlError> {
+    let processed = OssImageSvc::process(
+        download,
+        process,
+        image_process,
+        headers,
+        accessor,
+        with_body,
+    )
+    .await?;
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&processed.etag)?);
+    response_headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&download.last_modified)?,
+    );
+    if processed.not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let file_name = file_name
+        .filter(|file_name| !file_name.trim().is_empty())
+        .unwrap_or_else(|| download.file_name.clone());
+    // 与下载文件相同，只有预览可预览的文件时才使用图片的MIME类型，否则作为附件下载
+    let inline = mode == DownloadMode::Preview && download.previewable;
+    let (content_type, disposition_type) = if inline {
+        (processed.content_type, "inline")
+    } else {
+        (OCTET_STREAM, "attachment")
+    };
+    let content_disposition = build_content_disposition(disposition_type, &file_name);
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("none"));
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(content_disposition.as_str())?,
+    );
+    let body = match processed.data {
+        Some(data) => {
+            response_headers.insert(
+                header::CONTENT_LENGTH,
+                HeaderValue::from_str(data.len().to_string().as_str())?,
+            );
+            if with_body {
+                Body::from(data)
+            } else {
+                Body::empty()
+            }
+        }
+        None => Body::empty(),
+    };
+    Ok((StatusCode::OK, response_headers, body).into_response())
+}
+
+/// # 构造Content-Disposition头
+///
+/// 文件名按 RFC 6266/RFC 5987 同时提供两种形式:
+/// - `filename`: ASCII回退，非ASCII字符、控制字符、引号和反斜杠替换为`_`，供不支持`filename*`的客户端使用
+/// - `filename*`: UTF-8百分号编码，支持中文等非ASCII文件名
+///
+/// ## 参数
+/// - `disposition_type`: `attachment`或`inline`
+/// - `file_name`: 文件名
+fn build_content_disposition(disposition_type: &str, file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut encoded = String::with_capacity(file_name.len() * 3);
+    for byte in file_name.bytes() {
+        // RFC 5987 的 attr-char 不需要编码
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition_type, fallback, encoded
+    )
+}
+
 /// # 正则表达式，用于匹配对象ID的格式(19位数字+可选的扩展名)
 static OBJ_ID_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^(\d+)\.?([a-zA-Z0-9]*)$").unwrap());
@@ -211,3 +950,42 @@ fn parse_obj_id(obj_id: &str) -> Result<(u64, Option<String>), CtrlError> {
         Err(validator::ValidationError::new("<id>格式不正确"))?
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_disposition_ascii() {
+        assert_eq!(
+            build_content_disposition("attachment", "report-2024_v1.txt"),
+            "attachment; filename=\"report-2024_v1.txt\"; filename*=UTF-8''report-2024_v1.txt"
+        );
+        // 空格保留在ASCII回退中，在filename*中编码
+        assert_eq!(
+            build_content_disposition("inline", "my file.txt"),
+            "inline; filename=\"my file.txt\"; filename*=UTF-8''my%20file.txt"
+        );
+    }
+
+    #[test]
+    fn content_disposition_chinese() {
+        assert_eq!(
+            build_content_disposition("attachment", "中文.pdf"),
+            "attachment; filename=\"__.pdf\"; filename*=UTF-8''%E4%B8%AD%E6%96%87.pdf"
+        );
+    }
+
+    #[test]
+    fn content_disposition_quote_and_backslash() {
+        assert_eq!(
+            build_content_disposition("attachment", "a\"b\\c.txt"),
+            "attachment; filename=\"a_b_c.txt\"; filename*=UTF-8''a%22b%5Cc.txt"
+        );
+        // 控制字符也不能出现在ASCII回退中
+        assert_eq!(
+            build_content_disposition("attachment", "a\tb.txt"),
+            "attachment; filename=\"a_b.txt\"; filename*=UTF-8''a%09b.txt"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use crate::svc::{
+    OssTusChecksum, OssTusChecksumAlgorithm, OssTusObjRef, OssTusPatchResult, OssTusSvc,
+};
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::debug_handler;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use robotech::macros::log_call;
+use robotech::web::CtrlError;
+use robotech::web::ctrl_utils::get_current_user_id;
+use sea_orm::DatabaseTransaction;
+use std::collections::HashMap;
+
+/// 支持的tus协议版本
+const TUS_VERSION: &str = "1.0.0";
+/// 支持的tus扩展
+const TUS_EXTENSION: &str = "creation,checksum,termination";
+/// PATCH请求的Content-Type
+const TUS_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+static TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+static TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+static TUS_EXTENSION_HEADER: HeaderName = HeaderName::from_static("tus-extension");
+static TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+static TUS_CHECKSUM_ALGORITHM: HeaderName = HeaderName::from_static("tus-checksum-algorithm");
+static UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+static UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+static UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+static UPLOAD_CHECKSUM: HeaderName = HeaderName::from_static("upload-checksum");
+static UPLOAD_DEFER_LENGTH: HeaderName = HeaderName::from_static("upload-defer-length");
+static OSS_OBJ_REF_ID: HeaderName = HeaderName::from_static("oss-obj-ref-id");
+static OSS_DOWNLOAD_URL: HeaderName = HeaderName::from_static("oss-download-url");
+static OSS_PREVIEW_URL: HeaderName = HeaderName::from_static("oss-preview-url");
+
+/// # tus服务端能力查询
+///
+/// 该接口返回服务端支持的tus协议版本、扩展、最大文件大小和校验和算法。
+#[utoipa::path(
+    options,
+    path = "/oss/file/tus/{bucket}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称")
+    ),
+    responses((status = NO_CONTENT))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn tus_options() -> Result<Response, CtrlError> {
+    let max_size = OssTusSvc::max_size()?;
+    let mut response_headers = tus_headers();
+    response_headers.insert(
+        TUS_VERSION_HEADER.clone(),
+        HeaderValue::from_static(TUS_VERSION),
+    );
+    response_headers.insert(
+        TUS_EXTENSION_HEADER.clone(),
+        HeaderValue::from_static(TUS_EXTENSION),
+    );
+    response_headers.insert(TUS_MAX_SIZE.clone(), HeaderValue::from(max_size));
+    response_headers.insert(
+        TUS_CHECKSUM_ALGORITHM.clone(),
+        HeaderValue::from_static(OssTusChecksumAlgorithm::SUPPORTED),
+    );
+    Ok((StatusCode::NO_CONTENT, response_headers).into_response())
+}
+
+/// # 新建tus上传
+///
+/// 该接口实现tus的creation扩展，根据`Upload-Length`和`Upload-Metadata`新建上传，
+/// 在`Location`头中返回上传地址。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `Upload-Length`: 请求头，文件大小
+/// - `Upload-Metadata`: 请求头，文件名取自`filename`或`name`，可通过`hash`提供文件的SHA256
+///
+/// ## 错误处理
+/// - 如果文件大小超出上传限制，返回413
+/// - 如果存储桶不存在，返回NotFound错误
+#[utoipa::path(
+    post,
+    path = "/oss/file/tus/{bucket}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称")
+    ),
+    responses((status = CREATED))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn tus_create(
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, CtrlError> {
+    if let Some(response) = check_tus_resumable(&headers) {
+        return Ok(response);
+    }
+    if headers.contains_key(&UPLOAD_DEFER_LENGTH) {
+        return Ok(tus_error(StatusCode::BAD_REQUEST, "不支持延迟提供文件大小"));
+    }
+    let Some(length) = parse_u64_header(&headers, &UPLOAD_LENGTH) else {
+        return Ok(tus_error(StatusCode::BAD_REQUEST, "Upload-Length格式不正确"));
+    };
+    if length > OssTusSvc::max_size()? {
+        return Ok(tus_error(StatusCode::PAYLOAD_TOO_LARGE, "上传文件大小超出限制"));
+    }
+    let Some(metadata) = parse_metadata(&headers) else {
+        return Ok(tus_error(StatusCode::BAD_REQUEST, "Upload-Metadata格式不正确"));
+    };
+    let current_user_id = get_current_user_id(&headers)?;
+
+    let id = OssTusSvc::create::<DatabaseTransaction>(
+        &bucket,
+        length,
+        metadata,
+        current_user_id,
+        None,
+    )
+    .await?;
+
+    let mut response_headers = tus_headers();
+    response_headers.insert(
+        header::LOCATION,
+        HeaderValue::from_str(format!("/oss/file/tus/{}/{}", bucket, id).as_str())?,
+    );
+    response_headers.insert(UPLOAD_OFFSET.clone(), HeaderValue::from(0u64));
+    Ok((StatusCode::CREATED, response_headers).into_response())
+}
+
+/// # 获取tus上传的偏移量
+///
+/// 该接口实现tus的HEAD请求，返回已接收的大小和文件大小；上传完成后还会返回生成的对象引用。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，上传ID
+///
+/// ## 错误处理
+/// - 如果上传不存在或不是当前用户创建的，返回NotFound错误
+#[utoipa::path(
+    head,
+    path = "/oss/file/tus/{bucket}/{id}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "上传ID")
+    ),
+    responses((status = OK))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn tus_head(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Response, CtrlError> {
+    if let Some(response) = check_tus_resumable(&headers) {
+        return Ok(response);
+    }
+    let current_user_id = get_current_user_id(&headers)?;
+    let offset = OssTusSvc::get_offset(&bucket, id, current_user_id).await?;
+
+    let mut response_headers = tus_headers();
+    response_headers.insert(UPLOAD_OFFSET.clone(), HeaderValue::from(offset.offset));
+    response_headers.insert(UPLOAD_LENGTH.clone(), HeaderValue::from(offset.length));
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    if let Some(obj_ref) = &offset.obj_ref {
+        insert_obj_ref_headers(&mut response_headers, obj_ref)?;
+    }
+    Ok((StatusCode::OK, response_headers).into_response())
+}
+
+/// # 上传tus内容
+///
+/// 该接口实现tus的PATCH请求，从`Upload-Offset`处追加接收内容，支持`Upload-Checksum`校验。
+/// 接收完整个文件后生成对象引用，并在响应头中返回对象引用的ID、下载URL和预览URL。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，上传ID
+/// - `Upload-Offset`: 请求头，本次内容的起始偏移量
+/// - `Upload-Checksum`: 请求头，可选，本次内容的校验和
+///
+/// ## 错误处理
+/// - 如果上传不存在或不是当前用户创建的，返回NotFound错误
+/// - 如果`Content-Type`不是`application/offset+octet-stream`，返回415
+/// - 如果偏移量与已接收的大小不一致，返回409
+/// - 如果校验和算法不支持，返回400；如果校验和不一致，返回460
+/// - 如果该上传正在处理其它PATCH请求，返回423
+#[utoipa::path(
+    patch,
+    path = "/oss/file/tus/{bucket}/{id}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "上传ID")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/offset+octet-stream"),
+    responses((status = NO_CONTENT))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn tus_patch(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, CtrlError> {
+    if let Some(response) = check_tus_resumable(&headers) {
+        return Ok(response);
+    }
+    if headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) != Some(TUS_CONTENT_TYPE)
+    {
+        return Ok(tus_error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type必须为application/offset+octet-stream",
+        ));
+    }
+    let Some(offset) = parse_u64_header(&headers, &UPLOAD_OFFSET) else {
+        return Ok(tus_error(StatusCode::BAD_REQUEST, "Upload-Offset格式不正确"));
+    };
+    let checksum = match headers.get(&UPLOAD_CHECKSUM) {
+        Some(checksum) => match parse_checksum(checksum) {
+            Some(checksum) => Some(checksum),
+            None => return Ok(tus_error(StatusCode::BAD_REQUEST, "不支持的校验和")),
+        },
+        None => None,
+    };
+
+    let current_user_id = get_current_user_id(&headers)?;
+    let result = OssTusSvc::patch(
+        &bucket,
+        id,
+        current_user_id,
+        offset,
+        checksum,
+        body.into_data_stream(),
+    )
+    .await?;
+
+    let mut response_headers = tus_headers();
+    let status_code = match result {
+        OssTusPatchResult::Appended(new_offset) => {
+            response_headers.insert(UPLOAD_OFFSET.clone(), HeaderValue::from(new_offset));
+            StatusCode::NO_CONTENT
+        }
+        OssTusPatchResult::Completed(new_offset, obj_ref) => {
+            response_headers.insert(UPLOAD_OFFSET.clone(), HeaderValue::from(new_offset));
+            insert_obj_ref_headers(&mut response_headers, &obj_ref)?;
+            StatusCode::NO_CONTENT
+        }
+        OssTusPatchResult::OffsetMismatch(received_size) => {
+            response_headers.insert(UPLOAD_OFFSET.clone(), HeaderValue::from(received_size));
+            StatusCode::CONFLICT
+        }
+        OssTusPatchResult::ChecksumMismatch => {
+            // tus的checksum扩展规定校验和不一致时返回460
+            StatusCode::from_u16(460).unwrap()
+        }
+        OssTusPatchResult::LengthExceeded => StatusCode::PAYLOAD_TOO_LARGE,
+        OssTusPatchResult::Locked => StatusCode::LOCKED,
+    };
+    Ok((status_code, response_headers).into_response())
+}
+
+/// # 终止tus上传
+///
+/// 该接口实现tus的termination扩展，删除已接收的内容。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，上传ID
+///
+/// ## 错误处理
+/// - 如果上传不存在或不是当前用户创建的，返回NotFound错误
+#[utoipa::path(
+    delete,
+    path = "/oss/file/tus/{bucket}/{id}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "上传ID")
+    ),
+    responses((status = NO_CONTENT))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn tus_delete(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Response, CtrlError> {
+    if let Some(response) = check_tus_resumable(&headers) {
+        return Ok(response);
+    }
+    let current_user_id = get_current_user_id(&headers)?;
+    OssTusSvc::terminate::<DatabaseTransaction>(&bucket, id, current_user_id, None).await?;
+    Ok((StatusCode::NO_CONTENT, tus_headers()).into_response())
+}
+
+/// 所有tus响应都需要带上的头
+fn tus_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(TUS_RESUMABLE.clone(), HeaderValue::from_static(TUS_VERSION));
+    headers
+}
+
+/// tus协议规定的错误响应
+fn tus_error(status_code: StatusCode, message: &'static str) -> Response {
+    (status_code, tus_headers(), message).into_response()
+}
+
+/// 检查请求的tus协议版本，不支持时返回412响应
+fn check_tus_resumable(headers: &HeaderMap) -> Option<Response> {
+    if headers.get(&TUS_RESUMABLE).and_then(|v| v.to_str().ok()) == Some(TUS_VERSION) {
+        return None;
+    }
+    let mut response_headers = tus_headers();
+    response_headers.insert(
+        TUS_VERSION_HEADER.clone(),
+        HeaderValue::from_static(TUS_VERSION),
+    );
+    Some((StatusCode::PRECONDITION_FAILED, response_headers).into_response())
+}
+
+fn parse_u64_header(headers: &HeaderMap, name: &HeaderName) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse::<u64>().ok()
+}
+
+/// # 解析Upload-Metadata
+///
+/// 格式为逗号分隔的键值对，键与值以空格分隔，值为Base64编码(可省略)，如: `filename d29ybGQ=,is_confidential`
+fn parse_metadata(headers: &HeaderMap) -> Option<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    let Some(value) = headers.get(&UPLOAD_METADATA) else {
+        return Some(metadata);
+    };
+    for pair in value.to_str().ok()?.split(',') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, value)) => {
+                let value = BASE64.decode(value.trim()).ok()?;
+                (key, String::from_utf8(value).ok()?)
+            }
+            None => (pair, String::new()),
+        };
+        metadata.insert(key.to_string(), value);
+    }
+    Some(metadata)
+}
+
+/// # 解析Upload-Checksum
+///
+/// 格式为算法名与Base64编码的校验和，以空格分隔，如: `sha1 Kq5sNclPz7QV2+lfQIuc6R7oRu0=`
+fn parse_checksum(value: &HeaderValue) -> Option<OssTusChecksum> {
+    let (algorithm, expected) = value.to_str().ok()?.split_once(' ')?;
+    Some(OssTusChecksum {
+        algorithm: OssTusChecksumAlgorithm::parse(algorithm)?,
+        expected: BASE64.decode(expected.trim()).ok()?,
+    })
+}
+
+/// 在响应头中返回上传完成后生成的对象引用
+fn insert_obj_ref_headers(
+    response_headers: &mut HeaderMap,
+    obj_ref: &OssTusObjRef,
+) -> Result<(), CtrlError> {
+    response_headers.insert(OSS_OBJ_REF_ID.clone(), HeaderValue::from(obj_ref.id));
+    response_headers.insert(
+        OSS_DOWNLOAD_URL.clone(),
+        HeaderValue::from_str(&obj_ref.download_url)?,
+    );
+    if let Some(preview_url) = &obj_ref.preview_url {
+        response_headers.insert(OSS_PREVIEW_URL.clone(), HeaderValue::from_str(preview_url)?);
+    }
+    Ok(())
+}
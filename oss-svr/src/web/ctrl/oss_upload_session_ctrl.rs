@@ -0,0 +1,198 @@
+use crate::dto::oss_upload_session_dto::OssUploadSessionInitDto;
+use crate::svc::OssUploadSessionSvc;
+use crate::vo::{OssObjRefVo, OssUploadSessionVo};
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::HeaderMap;
+use axum::{Json, debug_handler};
+use robotech::macros::log_call;
+use robotech::ro::Ro;
+use robotech::web::CtrlError;
+use robotech::web::ctrl_utils::get_current_user_id;
+use sea_orm::DatabaseTransaction;
+
+/// # 新建上传会话
+///
+/// 该接口用于分片上传大文件，先新建会话，再按序号上传分片，最后完成会话。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，指定文件上传的目标存储桶名称
+/// - `dto`: 请求体，包含文件名、文件大小和可选的文件哈希值
+///
+/// ## 返回值
+/// 成功时返回包含会话信息的`Ro<OssUploadSessionVo>`对象
+///
+/// ## 错误处理
+/// - 如果存储桶不存在，返回警告信息
+/// - 如果文件大小超出上传限制，返回验证错误
+#[utoipa::path(
+    post,
+    path = "/oss/file/upload-session/{bucket}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称")
+    ),
+    request_body = OssUploadSessionInitDto,
+    responses((status = OK, body = Ro<OssUploadSessionVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn init_session(
+    Path(bucket): Path<String>,
+    headers: HeaderMap,
+    Json(dto): Json<OssUploadSessionInitDto>,
+) -> Result<Json<Ro<OssUploadSessionVo>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssUploadSessionSvc::init::<DatabaseTransaction>(&bucket, dto, current_user_id, None)
+            .await?,
+    ))
+}
+
+/// # 获取上传会话
+///
+/// 该接口返回会话信息及已接收的分片，断线重连后可据此只补传缺失的分片。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，会话ID
+///
+/// ## 返回值
+/// 成功时返回包含会话信息的`Ro<OssUploadSessionVo>`对象
+///
+/// ## 错误处理
+/// - 如果会话不存在，返回NotFound错误
+#[utoipa::path(
+    get,
+    path = "/oss/file/upload-session/{bucket}/{id}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "会话ID")
+    ),
+    responses((status = OK, body = Ro<OssUploadSessionVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn get_session(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Json<Ro<OssUploadSessionVo>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssUploadSessionSvc::get(&bucket, id, current_user_id).await?,
+    ))
+}
+
+/// # 上传分片
+///
+/// 该接口以原始请求体的方式接收一个分片，重复上传同一序号的分片会覆盖之前的分片。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，会话ID
+/// - `part_number`: 路径参数，分片序号，从1开始
+/// - `body`: 请求体，分片内容
+///
+/// ## 返回值
+/// 成功时返回包含会话信息的`Ro<OssUploadSessionVo>`对象
+///
+/// ## 错误处理
+/// - 如果会话不存在，返回NotFound错误
+/// - 如果分片序号超出范围，返回验证错误
+#[utoipa::path(
+    put,
+    path = "/oss/file/upload-session/{bucket}/{id}/{part_number}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "会话ID"),
+        ("part_number" = u32, Path, description = "分片序号")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = OK, body = Ro<OssUploadSessionVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn upload_part(
+    Path((bucket, id, part_number)): Path<(String, u64, u32)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<Ro<OssUploadSessionVo>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssUploadSessionSvc::upload_part(
+            &bucket,
+            id,
+            part_number,
+            current_user_id,
+            body.into_data_stream(),
+        )
+        .await?,
+    ))
+}
+
+/// # 完成上传会话
+///
+/// 该接口按序号合并所有分片，校验文件大小和哈希值后生成文件引用。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，会话ID
+///
+/// ## 返回值
+/// 成功时返回包含文件引用信息的`Ro<OssObjRefVo>`对象
+///
+/// ## 错误处理
+/// - 如果会话不存在，返回NotFound错误
+/// - 如果分片不完整，返回验证错误
+/// - 如果合并后的哈希值与提供的哈希值不匹配，返回错误
+#[utoipa::path(
+    post,
+    path = "/oss/file/upload-session/{bucket}/{id}/complete",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "会话ID")
+    ),
+    responses((status = OK, body = Ro<OssObjRefVo>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn complete_session(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Json<Ro<OssObjRefVo>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssUploadSessionSvc::complete(&bucket, id, current_user_id).await?,
+    ))
+}
+
+/// # 中止上传会话
+///
+/// 该接口删除会话已接收的分片及预留的对象。
+///
+/// ## 参数
+/// - `bucket`: 路径参数，存储桶名称
+/// - `id`: 路径参数，会话ID
+///
+/// ## 错误处理
+/// - 如果会话不存在，返回NotFound错误
+#[utoipa::path(
+    delete,
+    path = "/oss/file/upload-session/{bucket}/{id}",
+    params(
+        ("bucket" = String, Path, description = "存储桶名称"),
+        ("id" = u64, Path, description = "会话ID")
+    ),
+    responses((status = OK, body = Ro<()>))
+)]
+#[debug_handler]
+#[log_call]
+pub async fn abort_session(
+    Path((bucket, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Json<Ro<()>>, CtrlError> {
+    let current_user_id = get_current_user_id(&headers)?;
+    Ok(Json(
+        OssUploadSessionSvc::abort::<DatabaseTransaction>(&bucket, id, current_user_id, None)
+            .await?,
+    ))
+}
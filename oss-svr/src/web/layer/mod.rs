@@ -0,0 +1,5 @@
+mod oss_acl_layer;
+mod oss_presign_layer;
+
+pub use oss_acl_layer::*;
+pub use oss_presign_layer::*;
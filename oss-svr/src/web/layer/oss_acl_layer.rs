@@ -0,0 +1,324 @@
+use super::oss_presign_layer::forbidden;
+use crate::svc::{
+    OssAccessor, OssBucketAclSvc, OssBucketSvc, OssObjRefSvc, OssPermission, OssPresigned,
+};
+use anyhow::anyhow;
+use axum::Extension;
+use axum::body::{Body, to_bytes};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, Method, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+use robotech::svc::SvcError;
+use robotech::web::CtrlError;
+use robotech::web::ctrl_utils::get_current_user_id;
+use sea_orm::DatabaseTransaction;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use tracing::warn;
+
+/// 校验权限时读取的请求体的最大长度(增删改查的请求体都很小)
+const MAX_JSON_BODY_SIZE: usize = 1024 * 1024;
+
+/// # 校验权限的接口
+#[derive(Debug, Clone, Copy)]
+pub enum OssAclResource {
+    /// 存储桶的增删改查及级联删除
+    Bucket,
+    /// 存储桶的访问控制的增删改查
+    BucketAcl,
+    /// 对象的增删改查
+    Obj,
+    /// 对象引用的增删改查
+    ObjRef,
+    /// 上传文件(路径参数中有存储桶名称)
+    Upload,
+}
+
+/// # 访问接口需要的权限
+enum OssAclRequired {
+    /// 登录即可
+    Authenticated,
+    /// 只有超级管理员可以访问
+    Superuser,
+    /// 需要存储桶的权限
+    Bucket(u64, OssPermission),
+    /// 需要可以读取对象(拥有任一引用了该对象的存储桶的 `read` 权限)
+    Obj(u64),
+}
+
+/// # 获取访问者
+///
+/// 通过预签名URL访问时以签发者作为当前用户，不使用请求头中的角色；
+/// 否则从请求头中解析当前用户ID(未登录或解析失败时为None)及角色
+pub fn get_accessor(
+    headers: &HeaderMap,
+    presigned: Option<&OssPresigned>,
+) -> Result<OssAccessor, CtrlError> {
+    let accessor = match presigned {
+        Some(presigned) => OssAccessor {
+            user_id: Some(presigned.user_id),
+            roles: vec![],
+            presigned: true,
+        },
+        None => OssAccessor {
+            user_id: get_current_user_id(headers).ok(),
+            roles: OssBucketAclSvc::get_roles(headers)?,
+            presigned: false,
+        },
+    };
+    Ok(accessor)
+}
+
+/// # 校验存储桶的访问控制
+///
+/// 启用访问控制时，根据接口、请求方法及请求中的ID找到要访问的存储桶，校验当前用户是否拥有需要的权限:
+/// - 查看存储桶、下载文件需要 `read` 权限
+/// - 上传文件，新增、修改、删除对象引用需要 `write` 权限
+/// - 修改、删除、级联删除存储桶，管理存储桶的访问控制需要 `admin` 权限
+/// - 新增存储桶只需登录，创建者拥有该存储桶的 `admin` 权限；查询存储桶列表只需登录，由接口过滤掉没有 `read` 权限的存储桶
+/// - 新增、修改对象引用时指定了对象ID的，还需要可以读取该对象，避免通过猜测对象ID引用别人的文件
+/// - 对象的增删改查、不指定存储桶的查询及按条件删除只有超级管理员可以访问
+///
+/// 没有权限时返回403状态码；要访问的记录不存在时不做处理，由接口返回不存在
+pub async fn check_acl(
+    State(resource): State<OssAclResource>,
+    headers: HeaderMap,
+    presigned: Option<Extension<OssPresigned>>,
+    path: Option<Path<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, CtrlError> {
+    if !OssBucketAclSvc::is_enabled()? || request.method() == Method::OPTIONS {
+        return Ok(next.run(request).await);
+    }
+    let accessor = get_accessor(&headers, presigned.as_ref().map(|Extension(p)| p))?;
+    if OssBucketAclSvc::is_superuser(&accessor)? {
+        return Ok(next.run(request).await);
+    }
+
+    let params = path.map(|Path(params)| params).unwrap_or_default();
+    let (request, requireds) = get_requireds(resource, &params, request).await?;
+    for required in requireds {
+        let granted = match required {
+            OssAclRequired::Authenticated => accessor.user_id.is_some(),
+            OssAclRequired::Superuser => false,
+            OssAclRequired::Bucket(bucket_id, permission) => {
+                match OssBucketSvc::get_by_id::<DatabaseTransaction>(bucket_id, None)
+                    .await?
+                    .extra
+                {
+                    Some(bucket_vo) => {
+                        OssBucketAclSvc::has_permission::<DatabaseTransaction>(
+                            &bucket_vo, &accessor, permission, None,
+                        )
+                        .await?
+                    }
+                    None => true,
+                }
+            }
+            OssAclRequired::Obj(obj_id) => can_read_obj(obj_id, &accessor).await?,
+        };
+        if !granted {
+            warn!(
+                "无权访问<{} {}>: {:?}",
+                request.method(),
+                request.uri(),
+                accessor
+            );
+            return Ok(forbidden("无权访问".to_string()));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// 根据接口及请求方法获取需要的权限，需要时读取请求体，读取后重新放回请求中
+async fn get_requireds(
+    resource: OssAclResource,
+    params: &HashMap<String, String>,
+    request: Request,
+) -> Result<(Request, Vec<OssAclRequired>), CtrlError> {
+    let method = request.method().clone();
+    let id = params.get("id").and_then(|id| id.parse::<u64>().ok());
+    let query_bucket_id = get_query_bucket_id(request.uri());
+
+    let requireds = match (resource, method, id) {
+        (OssAclResource::Obj, _, _) => vec![OssAclRequired::Superuser],
+        (OssAclResource::Upload, _, _) => match params.get("bucket") {
+            Some(bucket) => {
+                match OssBucketSvc::get_by_name::<DatabaseTransaction>(bucket, None)
+                    .await?
+                    .extra
+                {
+                    Some(bucket_vo) => {
+                        vec![OssAclRequired::Bucket(bucket_vo.id, OssPermission::Write)]
+                    }
+                    None => vec![],
+                }
+            }
+            None => vec![OssAclRequired::Superuser],
+        },
+        (OssAclResource::Bucket, method @ (Method::POST | Method::PUT), None) => {
+            // 保存(save)时有ID为修改，否则为新增
+            let (request, body) = read_json_body(request).await?;
+            let requireds = match get_u64(&body, "id") {
+                Some(id) => vec![OssAclRequired::Bucket(id, OssPermission::Admin)],
+                None if method == Method::POST => vec![OssAclRequired::Authenticated],
+                None => vec![OssAclRequired::Superuser],
+            };
+            return Ok((request, requireds));
+        }
+        (OssAclResource::Bucket, Method::GET, None) if request.uri().path().ends_with("/list") => {
+            vec![OssAclRequired::Authenticated]
+        }
+        (OssAclResource::Bucket, Method::GET, Some(id)) => {
+            vec![OssAclRequired::Bucket(id, OssPermission::Read)]
+        }
+        (OssAclResource::Bucket, Method::DELETE, Some(id)) => {
+            vec![OssAclRequired::Bucket(id, OssPermission::Admin)]
+        }
+        (OssAclResource::ObjRef, Method::POST | Method::PUT, None) => {
+            let (request, body) = read_json_body(request).await?;
+            let mut requireds = vec![];
+            if let Some(id) = get_u64(&body, "id")
+                && let Some(bucket_id) = get_obj_ref_bucket_id(id).await?
+            {
+                requireds.push(OssAclRequired::Bucket(bucket_id, OssPermission::Write));
+            }
+            // 新增或移动到其它存储桶时还需要目标存储桶的权限
+            if let Some(bucket_id) = get_bucket_id(&body) {
+                requireds.push(OssAclRequired::Bucket(bucket_id, OssPermission::Write));
+            }
+            if requireds.is_empty() {
+                requireds.push(OssAclRequired::Superuser);
+            }
+            if let Some(obj_id) = get_u64(&body, "objId").or_else(|| get_u64(&body, "obj_id")) {
+                requireds.push(OssAclRequired::Obj(obj_id));
+            }
+            return Ok((request, requireds));
+        }
+        (OssAclResource::ObjRef, method @ (Method::GET | Method::DELETE), Some(id)) => {
+            let permission = if method == Method::GET {
+                OssPermission::Read
+            } else {
+                OssPermission::Write
+            };
+            match get_obj_ref_bucket_id(id).await? {
+                Some(bucket_id) => vec![OssAclRequired::Bucket(bucket_id, permission)],
+                None => vec![],
+            }
+        }
+        (OssAclResource::ObjRef, method @ (Method::GET | Method::DELETE), None) => {
+            let permission = if method == Method::GET {
+                OssPermission::Read
+            } else {
+                OssPermission::Write
+            };
+            match query_bucket_id {
+                Some(bucket_id) => vec![OssAclRequired::Bucket(bucket_id, permission)],
+                None => vec![OssAclRequired::Superuser],
+            }
+        }
+        (OssAclResource::BucketAcl, Method::POST | Method::PUT, None) => {
+            let (request, body) = read_json_body(request).await?;
+            let mut requireds = vec![];
+            if let Some(id) = get_u64(&body, "id")
+                && let Some(bucket_id) = get_acl_bucket_id(id).await?
+            {
+                requireds.push(OssAclRequired::Bucket(bucket_id, OssPermission::Admin));
+            }
+            if let Some(bucket_id) = get_bucket_id(&body) {
+                requireds.push(OssAclRequired::Bucket(bucket_id, OssPermission::Admin));
+            }
+            if requireds.is_empty() {
+                requireds.push(OssAclRequired::Superuser);
+            }
+            return Ok((request, requireds));
+        }
+        (OssAclResource::BucketAcl, Method::GET | Method::DELETE, Some(id)) => {
+            match get_acl_bucket_id(id).await? {
+                Some(bucket_id) => vec![OssAclRequired::Bucket(bucket_id, OssPermission::Admin)],
+                None => vec![],
+            }
+        }
+        (OssAclResource::BucketAcl, Method::GET, None) => match query_bucket_id {
+            Some(bucket_id) => vec![OssAclRequired::Bucket(bucket_id, OssPermission::Admin)],
+            None => vec![OssAclRequired::Superuser],
+        },
+        _ => vec![OssAclRequired::Superuser],
+    };
+    Ok((request, requireds))
+}
+
+/// 读取JSON格式的请求体，返回重新放入请求体的请求及解析出的JSON，不是JSON时返回 `Value::Null`
+async fn read_json_body(request: Request) -> Result<(Request, Value), CtrlError> {
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_JSON_BODY_SIZE)
+        .await
+        .map_err(|e| SvcError::Runtime(anyhow!("读取请求体失败: {}", e)))?;
+    let value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    Ok((Request::from_parts(parts, Body::from(bytes)), value))
+}
+
+/// 从JSON中获取数字，兼容以字符串表示的数字(避免前端丢失精度)
+fn get_u64(value: &Value, key: &str) -> Option<u64> {
+    match value.get(key)? {
+        Value::Number(number) => number.as_u64(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+/// 从请求体中获取存储桶ID
+fn get_bucket_id(body: &Value) -> Option<u64> {
+    get_u64(body, "bucketId").or_else(|| get_u64(body, "bucket_id"))
+}
+
+/// 从查询参数中获取存储桶ID
+fn get_query_bucket_id(uri: &Uri) -> Option<u64> {
+    let Query(query) = Query::<HashMap<String, String>>::try_from_uri(uri).ok()?;
+    query
+        .get("bucketId")
+        .or_else(|| query.get("bucket_id"))
+        .and_then(|bucket_id| bucket_id.parse().ok())
+}
+
+/// 判断访问者是否可以读取对象: 拥有任一引用了该对象的存储桶的 `read` 权限，没有被引用的对象只有超级管理员可以访问
+async fn can_read_obj(obj_id: u64, accessor: &OssAccessor) -> Result<bool, CtrlError> {
+    let bucket_ids: HashSet<u64> =
+        OssObjRefSvc::list_by_obj_id::<DatabaseTransaction>(obj_id, None)
+            .await?
+            .extra
+            .unwrap_or_default()
+            .into_iter()
+            .map(|obj_ref_vo| obj_ref_vo.bucket_id)
+            .collect();
+    for bucket_id in bucket_ids {
+        if let Some(bucket_vo) = OssBucketSvc::get_by_id::<DatabaseTransaction>(bucket_id, None)
+            .await?
+            .extra
+            && OssBucketAclSvc::has_permission::<DatabaseTransaction>(
+                &bucket_vo,
+                accessor,
+                OssPermission::Read,
+                None,
+            )
+            .await?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 获取对象引用所在的存储桶ID，对象引用不存在时返回None
+async fn get_obj_ref_bucket_id(id: u64) -> Result<Option<u64>, CtrlError> {
+    let ro = OssObjRefSvc::get_by_id::<DatabaseTransaction>(id, None).await?;
+    Ok(ro.extra.map(|obj_ref_vo| obj_ref_vo.bucket_id))
+}
+
+/// 获取访问控制记录所在的存储桶ID，记录不存在时返回None
+async fn get_acl_bucket_id(id: u64) -> Result<Option<u64>, CtrlError> {
+    let ro = OssBucketAclSvc::get_by_id::<DatabaseTransaction>(id, None).await?;
+    Ok(ro.extra.map(|acl_vo| acl_vo.bucket_id))
+}
@@ -0,0 +1,51 @@
+use crate::dto::oss_presign_dto::OssPresignQueryDto;
+use crate::svc::OssPresignSvc;
+use axum::Json;
+use axum::extract::{ConnectInfo, Query, Request};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use robotech::ro::Ro;
+use std::net::SocketAddr;
+use tracing::{debug, warn};
+
+/// # 校验预签名URL
+///
+/// 请求带有签名参数(`x-oss-signature`)时校验预签名URL，校验通过后将 `OssPresigned` 放入请求的扩展中，
+/// 校验不通过时返回403状态码；不带签名参数的请求不做处理
+pub async fn verify_presigned_url(mut request: Request, next: Next) -> Response {
+    let query = match Query::<OssPresignQueryDto>::try_from_uri(request.uri()) {
+        Ok(Query(query)) => query,
+        Err(e) => return forbidden(format!("预签名URL的参数格式错误: {}", e)),
+    };
+    if query.signature.is_none() {
+        return next.run(request).await;
+    }
+
+    let connect_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match OssPresignSvc::verify(
+        request.method(),
+        request.uri().path(),
+        &query,
+        request.headers(),
+        connect_ip,
+    ) {
+        Ok(presigned) => {
+            debug!("预签名URL校验通过: {:?}", presigned);
+            request.extensions_mut().insert(presigned);
+            next.run(request).await
+        }
+        Err(e) => {
+            warn!("预签名URL校验失败<{}>: {}", request.uri(), e);
+            forbidden(e.to_string())
+        }
+    }
+}
+
+/// 返回403状态码及原因
+pub(crate) fn forbidden(msg: String) -> Response {
+    (StatusCode::FORBIDDEN, Json(Ro::<()>::warn(msg))).into_response()
+}
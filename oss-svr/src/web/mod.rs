@@ -1,6 +1,8 @@
 mod api_doc;
 mod ctrl;
+mod layer;
 mod router;
 
 pub use api_doc::*;
 pub use ctrl::*;
+pub use layer::*;
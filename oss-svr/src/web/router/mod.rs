@@ -1,4 +1,7 @@
+mod oss_bucket_acl_router;
 mod oss_bucket_router;
 mod oss_file_router;
 mod oss_obj_ref_router;
 mod oss_obj_router;
+mod oss_tus_router;
+mod oss_upload_session_router;
@@ -0,0 +1,10 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::middleware::from_fn_with_state;
+use robotech::macros::router;
+
+#[router(routes[
+    ("/oss/bucket-acl", post(add).put(modify).layer(from_fn_with_state(OssAclResource::BucketAcl, check_acl))),                 // 新增/修改授权
+    ("/oss/bucket-acl/{id}", get(get_by_id).delete(del_by_id).layer(from_fn_with_state(OssAclResource::BucketAcl, check_acl))), // 获取/删除授权
+    ("/oss/bucket-acl/list", get(list_by_query_dto).layer(from_fn_with_state(OssAclResource::BucketAcl, check_acl))),           // 查询存储桶的授权列表
+])]
+struct OssBucketAclRouter;
@@ -1,5 +1,13 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::middleware::from_fn_with_state;
 use robotech::macros::router;
-#[router(crud, routes[
-    ("/oss/bucket/cascade/{id}", delete(del_cascade)),  // 级联删除
+
+#[router(routes[
+    ("/oss/bucket", post(add).put(modify).get(get_by_query_dto).delete(del_by_query_dto).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))), // 新增/修改存储桶，按条件获取/删除存储桶
+    ("/oss/bucket/save", post(save).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))),                                                      // 保存存储桶(有ID时修改，否则新增)
+    ("/oss/bucket/{id}", get(get_by_id).delete(del_by_id).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))),                                // 获取/删除存储桶
+    ("/oss/bucket/list", get(list_readable).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))),                                              // 查询存储桶列表
+    ("/oss/bucket/page", get(page_by_query_dto).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))),                                          // 分页查询存储桶
+    ("/oss/bucket/cascade/{id}", delete(del_cascade).layer(from_fn_with_state(OssAclResource::Bucket, check_acl))),                                     // 级联删除
 ])]
 struct OssBucketRouter;
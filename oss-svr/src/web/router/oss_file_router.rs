@@ -1,9 +1,18 @@
+use crate::web::{OssAclResource, check_acl, verify_presigned_url};
 use axum::extract::DefaultBodyLimit;
+use axum::middleware::{from_fn, from_fn_with_state};
 use robotech::macros::router;
 
 #[router(routes[
-    ("/oss/file/upload/{bucket}", post(upload).layer(DefaultBodyLimit::disable())), // 上传文件
-    ("/oss/file/download/{obj_id}", get(download)),                                 // 下载文件
-    ("/oss/file/preview/{obj_id}", get(preview)),                                   // 预览文件
+    ("/oss/file/upload/{bucket}", post(upload).layer(DefaultBodyLimit::disable()).layer(from_fn_with_state(OssAclResource::Upload, check_acl)).layer(from_fn(verify_presigned_url))),             // 上传文件
+    ("/oss/file/upload-batch/{bucket}", post(upload_batch).layer(DefaultBodyLimit::disable()).layer(from_fn_with_state(OssAclResource::Upload, check_acl)).layer(from_fn(verify_presigned_url))), // 批量上传文件
+    ("/oss/file/raw/{bucket}/{name}", put(upload_raw).layer(DefaultBodyLimit::disable()).layer(from_fn_with_state(OssAclResource::Upload, check_acl)).layer(from_fn(verify_presigned_url))),      // 以原始请求体上传文件
+    ("/oss/file/instant/{bucket}", post(instant_upload).layer(from_fn_with_state(OssAclResource::Upload, check_acl))),                                                                            // 秒传文件
+    ("/oss/file/download/{obj_id}", get(download).head(download_head).layer(from_fn(verify_presigned_url))),                                                                                      // 下载文件
+    ("/oss/file/preview/{obj_id}", get(preview).head(preview_head).layer(from_fn(verify_presigned_url))),                                                                                         // 预览文件
+    ("/oss/file/render/{obj_id}", get(render).layer(from_fn(verify_presigned_url))),                                                                                                              // 渲染预览文件(Markdown、源代码、CSV/TSV)
+    ("/oss/file/exif/{obj_id}", get(exif).layer(from_fn(verify_presigned_url))),                                                                                                                  // 读取图片的EXIF信息
+    ("/oss/file/archive", post(archive)),                                                                                                                                                         // 打包下载文件
+    ("/oss/file/presign", post(presign)),                                                                                                                                                         // 生成预签名URL
 ])]
 struct OssFileRouter;
@@ -1,4 +1,12 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::middleware::from_fn_with_state;
 use robotech::macros::router;
 
-#[router(crud)]
+#[router(routes[
+    ("/oss/obj-ref", post(add).put(modify).get(get_by_query_dto).delete(del_by_query_dto).layer(from_fn_with_state(OssAclResource::ObjRef, check_acl))), // 新增/修改对象引用，按条件获取/删除对象引用
+    ("/oss/obj-ref/save", post(save).layer(from_fn_with_state(OssAclResource::ObjRef, check_acl))),                                                      // 保存对象引用(有ID时修改，否则新增)
+    ("/oss/obj-ref/{id}", get(get_by_id).delete(del_by_id).layer(from_fn_with_state(OssAclResource::ObjRef, check_acl))),                                // 获取/删除对象引用
+    ("/oss/obj-ref/list", get(list_by_query_dto).layer(from_fn_with_state(OssAclResource::ObjRef, check_acl))),                                          // 查询对象引用列表
+    ("/oss/obj-ref/page", get(page_by_query_dto).layer(from_fn_with_state(OssAclResource::ObjRef, check_acl))),                                          // 分页查询对象引用
+])]
 struct OssObjRefRouter;
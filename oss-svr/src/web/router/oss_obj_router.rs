@@ -1,4 +1,12 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::middleware::from_fn_with_state;
 use robotech::macros::router;
 
-#[router(crud)]
+#[router(routes[
+    ("/oss/obj", post(add).put(modify).get(get_by_query_dto).delete(del_by_query_dto).layer(from_fn_with_state(OssAclResource::Obj, check_acl))), // 新增/修改对象，按条件获取/删除对象
+    ("/oss/obj/save", post(save).layer(from_fn_with_state(OssAclResource::Obj, check_acl))),                                                      // 保存对象(有ID时修改，否则新增)
+    ("/oss/obj/{id}", get(get_by_id).delete(del_by_id).layer(from_fn_with_state(OssAclResource::Obj, check_acl))),                                // 获取/删除对象
+    ("/oss/obj/list", get(list_by_query_dto).layer(from_fn_with_state(OssAclResource::Obj, check_acl))),                                          // 查询对象列表
+    ("/oss/obj/page", get(page_by_query_dto).layer(from_fn_with_state(OssAclResource::Obj, check_acl))),                                          // 分页查询对象
+])]
 struct OssObjRouter;
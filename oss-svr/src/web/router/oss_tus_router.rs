@@ -0,0 +1,10 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
+use robotech::macros::router;
+
+#[router(routes[
+    ("/oss/file/tus/{bucket}", post(tus_create).options(tus_options).layer(from_fn_with_state(OssAclResource::Upload, check_acl))),                                                                           // 新建tus上传
+    ("/oss/file/tus/{bucket}/{id}", head(tus_head).patch(tus_patch).delete(tus_delete).options(tus_options).layer(DefaultBodyLimit::disable()).layer(from_fn_with_state(OssAclResource::Upload, check_acl))), // tus上传
+])]
+struct OssTusRouter;
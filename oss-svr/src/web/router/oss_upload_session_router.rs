@@ -0,0 +1,12 @@
+use crate::web::{OssAclResource, check_acl};
+use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
+use robotech::macros::router;
+
+#[router(routes[
+    ("/oss/file/upload-session/{bucket}", post(init_session).layer(from_fn_with_state(OssAclResource::Upload, check_acl))),                                                     // 新建上传会话
+    ("/oss/file/upload-session/{bucket}/{id}", get(get_session).delete(abort_session).layer(from_fn_with_state(OssAclResource::Upload, check_acl))),                            // 获取/中止上传会话
+    ("/oss/file/upload-session/{bucket}/{id}/{part_number}", put(upload_part).layer(DefaultBodyLimit::disable()).layer(from_fn_with_state(OssAclResource::Upload, check_acl))), // 上传分片
+    ("/oss/file/upload-session/{bucket}/{id}/complete", post(complete_session).layer(from_fn_with_state(OssAclResource::Upload, check_acl))),                                   // 完成上传会话
+])]
+struct OssUploadSessionRouter;